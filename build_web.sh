@@ -15,19 +15,23 @@ else
     exit 1
 fi
 
-# Create docs directory for GitHub Pages
+# Copy the WASM package into the GitHub Pages directory. docs/ keeps its
+# own index.html and index.js, which load the model snapshot on their own,
+# while web/ holds the upload page for local development.
 echo "📁 Setting up GitHub Pages directory..."
-rm -rf docs
 mkdir -p docs
+rm -rf docs/pkg
+cp -r web/pkg docs/pkg
 
-# Copy web files to docs directory
-cp -r web/* docs/
+# Build a model snapshot from the data files, so the page downloads one
+# prebuilt model instead of the three raw dumps
+echo "📊 Building model snapshot..."
+cargo run --release -- --save-snapshot docs/model.snapshot
 
-# Copy data files to docs directory
-echo "📊 Copying data files..."
-cp data/vn_titles docs/
-cp data/tags_vn docs/
-cp data/vndb-votes-2025-06-02 docs/
+if [ $? -ne 0 ]; then
+    echo "❌ Failed to build model snapshot"
+    exit 1
+fi
 
 echo "🎉 Build completed successfully!"
 echo ""
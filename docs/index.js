@@ -30,6 +30,13 @@ class RecommendationApp {
         this.updateProgress(0, 'Starting data download...');
         
         try {
+            // A prebuilt model snapshot replaces all three dumps
+            if (await this.loadSnapshot()) {
+                this.updateProgress(100, 'Model snapshot loaded successfully!');
+                this.updateGetRecommendationsButton();
+                return;
+            }
+
             // Load VN titles
             await this.loadVnTitles();
             this.updateProgress(33, 'VN titles loaded...');
@@ -51,6 +58,21 @@ class RecommendationApp {
         }
     }
 
+    async loadSnapshot() {
+        const response = await fetch('./model.snapshot');
+        if (!response.ok) {
+            return false;
+        }
+
+        const data = new Uint8Array(await response.arrayBuffer());
+        this.recommender.load_snapshot(data);
+        this.dataLoaded = { titles: true, tags: true, votes: true };
+        this.updateDataStatus('vnTitlesStatus', 'success', 'Loaded');
+        this.updateDataStatus('tagsStatus', 'success', 'Loaded');
+        this.updateDataStatus('votesStatus', 'success', 'Loaded');
+        return true;
+    }
+
     async loadVnTitles() {
         try {
             this.updateDataStatus('vnTitlesStatus', 'loading', 'Downloading...');
@@ -61,14 +83,9 @@ class RecommendationApp {
             }
             
             const data = await response.text();
-            const success = this.recommender.load_vn_titles(data);
-            
-            if (success) {
-                this.dataLoaded.titles = true;
-                this.updateDataStatus('vnTitlesStatus', 'success', 'Loaded');
-            } else {
-                throw new Error('Failed to process VN titles data');
-            }
+            this.recommender.load_vn_titles(data);
+            this.dataLoaded.titles = true;
+            this.updateDataStatus('vnTitlesStatus', 'success', 'Loaded');
         } catch (error) {
             this.updateDataStatus('vnTitlesStatus', 'error', 'Failed');
             throw error;
@@ -85,14 +102,9 @@ class RecommendationApp {
             }
             
             const data = await response.text();
-            const success = this.recommender.load_tags(data);
-            
-            if (success) {
-                this.dataLoaded.tags = true;
-                this.updateDataStatus('tagsStatus', 'success', 'Loaded');
-            } else {
-                throw new Error('Failed to process tags data');
-            }
+            this.recommender.load_tags(data);
+            this.dataLoaded.tags = true;
+            this.updateDataStatus('tagsStatus', 'success', 'Loaded');
         } catch (error) {
             this.updateDataStatus('tagsStatus', 'error', 'Failed');
             throw error;
@@ -117,14 +129,9 @@ class RecommendationApp {
             }
             
             const data = await response.text();
-            const success = this.recommender.load_votes(data);
-            
-            if (success) {
-                this.dataLoaded.votes = true;
-                this.updateDataStatus('votesStatus', 'success', 'Loaded');
-            } else {
-                throw new Error('Failed to process votes data');
-            }
+            this.recommender.load_votes(data);
+            this.dataLoaded.votes = true;
+            this.updateDataStatus('votesStatus', 'success', 'Loaded');
         } catch (error) {
             this.updateDataStatus('votesStatus', 'error', 'Failed');
             throw error;
@@ -180,6 +187,11 @@ class RecommendationApp {
         document.getElementById('getRecommendations').addEventListener('click', () => {
             this.getRecommendations();
         });
+
+        // Vote list upload handler
+        document.getElementById('voteListFile').addEventListener('change', (event) => {
+            this.getVoteListRecommendations(event.target.files[0]);
+        });
     }
 
     updateGetRecommendationsButton() {
@@ -232,7 +244,41 @@ class RecommendationApp {
         }
     }
 
-    displayRecommendations(recommendations, sourceTitle, sourceId) {
+    async getVoteListRecommendations(file) {
+        if (!file) return;
+
+        const allDataLoaded = this.dataLoaded.titles && this.dataLoaded.tags && this.dataLoaded.votes;
+        if (!this.recommender || !allDataLoaded) {
+            this.showError('Please wait until all data is loaded.');
+            return;
+        }
+
+        this.showLoading(true);
+        this.hideError();
+        this.hideResults();
+
+        try {
+            // VNs missing from the loaded data are skipped
+            const contents = await file.text();
+            const voteScale = parseFloat(document.getElementById('voteScale').value);
+            const recommendations = this.recommender.get_recommendations_for_vote_list(contents, voteScale);
+
+            this.displayRecommendations(recommendations);
+            this.showResults();
+
+        } catch (error) {
+            console.error('Error getting recommendations:', error);
+            if (error.name === 'EmptyDataset') {
+                this.showError(`No votes for known visual novels found in ${file.name}.`);
+            } else {
+                this.showError(`Failed to get recommendations: ${error.message}`);
+            }
+        } finally {
+            this.showLoading(false);
+        }
+    }
+
+    displayRecommendations(recommendations, sourceTitle = null, sourceId = null) {
         // Display combined recommendations
         this.displayRecommendationList(
             recommendations.combined_recommendations,
@@ -285,7 +331,7 @@ class RecommendationApp {
             return;
         }
 
-        items.forEach((item, index) => {
+        items.forEach((item) => {
             const itemDiv = document.createElement('div');
             itemDiv.className = 'recommendation-item bg-white border border-gray-200 rounded-lg p-4 hover:shadow-md transition-all duration-300';
             
@@ -293,10 +339,11 @@ class RecommendationApp {
                 <div class="flex items-center justify-between">
                     <div class="flex-1">
                         <span class="inline-flex items-center justify-center w-8 h-8 bg-blue-100 text-blue-800 text-sm font-medium rounded-full mr-3">
-                            ${index + 1}
+                            ${item.rank}
                         </span>
                         <span class="text-lg font-medium text-gray-900">${item.title}</span>
                         <span class="text-sm text-gray-500 ml-2">(ID: ${item.id})</span>
+                        <span class="text-sm text-gray-400 ml-2">score ${item.score.toFixed(3)}</span>
                     </div>
                     <a href="${item.url}" target="_blank" 
                        class="inline-flex items-center px-4 py-2 bg-blue-500 hover:bg-blue-600 text-white text-sm font-medium rounded-lg transition duration-300">
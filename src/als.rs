@@ -0,0 +1,431 @@
+// Matrix factorization of the votes by alternating least squares. Every VN
+// and every user gets a vector of latent factors such that their dot product
+// approximates the vote (explicit) or the confidence that the user would vote
+// on the VN at all (implicit). VNs are then similar when their factor vectors
+// point the same way, which also works for VNs with few votes.
+use crate::error::Result;
+use crate::recommender::VisualNovelRecommender;
+use crate::scorer::Scorer;
+use crate::scoring::{self, XorShift};
+use serde::{Deserialize, Serialize};
+use sprs::{CsMat, CsMatView};
+use std::collections::HashMap;
+
+// Name of the model in Recommendation::source_scores
+pub const ALS_SOURCE: &str = "als";
+
+// Training parameters
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Als {
+    // Length of the factor vectors
+    pub factors: usize,
+    // Weight of the penalty on the size of the factors
+    pub regularization: f64,
+    // Number of alternating passes over users and VNs
+    pub iterations: usize,
+    // Fit whether users voted on a VN at all, with the vote as confidence,
+    // instead of the vote values
+    pub implicit: bool,
+    // Implicit confidence of a vote: 1 + alpha * vote / highest vote
+    pub alpha: f64,
+    // Seed of the random initial VN factors
+    pub seed: u64,
+}
+
+impl Default for Als {
+    fn default() -> Self {
+        Self {
+            factors: 32,
+            regularization: 0.1,
+            iterations: 10,
+            implicit: false,
+            alpha: 40.0,
+            seed: 42,
+        }
+    }
+}
+
+// Trained factor vectors, stored row by row; ids without votes get zeros
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Embeddings {
+    pub factors: usize,
+    pub implicit: bool,
+    pub vn_factors: Vec<f32>,
+    pub user_factors: Vec<f32>,
+}
+
+impl Embeddings {
+    pub fn vn(&self, vn_id: i32) -> Option<&[f32]> {
+        row(&self.vn_factors, self.factors, vn_id)
+    }
+
+    pub fn user(&self, user_id: i32) -> Option<&[f32]> {
+        row(&self.user_factors, self.factors, user_id)
+    }
+
+    fn vns(&self) -> impl Iterator<Item = (i32, &[f32])> {
+        self.vn_factors
+            .chunks_exact(self.factors.max(1))
+            .enumerate()
+            .map(|(vn_id, factors)| (vn_id as i32, factors))
+    }
+
+    // Cosine similarity of every VN to the weighted sum of the seed VNs' unit
+    // factor vectors
+    fn similarities(&self, seeds: &[(i32, f64)]) -> HashMap<i32, f64> {
+        let mut query = vec![0.0; self.factors];
+        for &(vn_id, weight) in seeds {
+            let Some(factors) = self.vn(vn_id) else {
+                continue;
+            };
+            let norm = norm(factors);
+            if norm == 0.0 {
+                continue;
+            }
+            for (q, x) in query.iter_mut().zip(factors) {
+                *q += weight * *x as f64 / norm;
+            }
+        }
+        if query.iter().all(|q| *q == 0.0) {
+            return HashMap::new();
+        }
+
+        self.vns()
+            .filter_map(|(vn_id, factors)| {
+                let norm = norm(factors);
+                (norm != 0.0).then(|| (vn_id, dot(&query, factors) / norm))
+            })
+            .collect()
+    }
+
+    // Predicted preference of a user for every VN `exclude` does not reject
+    pub(crate) fn user_scores(
+        &self,
+        user_id: i32,
+        exclude: impl Fn(i32) -> bool,
+    ) -> HashMap<i32, f64> {
+        let Some(user) = self.user(user_id) else {
+            return HashMap::new();
+        };
+        let user: Vec<f64> = user.iter().map(|x| *x as f64).collect();
+        if user.iter().all(|x| *x == 0.0) {
+            return HashMap::new();
+        }
+
+        self.vns()
+            .filter(|(vn_id, factors)| !exclude(*vn_id) && norm(factors) != 0.0)
+            .map(|(vn_id, factors)| (vn_id, dot(&user, factors)))
+            .collect()
+    }
+}
+
+fn row(factors: &[f32], k: usize, id: i32) -> Option<&[f32]> {
+    let start = usize::try_from(id).ok()?.checked_mul(k)?;
+    factors.get(start..start + k)
+}
+
+fn dot(a: &[f64], b: &[f32]) -> f64 {
+    a.iter().zip(b).map(|(a, b)| a * *b as f64).sum()
+}
+
+fn norm(factors: &[f32]) -> f64 {
+    factors
+        .iter()
+        .map(|x| (*x as f64) * (*x as f64))
+        .sum::<f64>()
+        .sqrt()
+}
+
+impl Als {
+    // Train on a VN x user rating matrix and its CSC form, as built by
+    // VisualNovelRecommender::build_rating_index
+    pub fn train(&self, vn_users: &CsMat<f64>, user_vns: &CsMat<f64>) -> Embeddings {
+        let k = self.factors.max(1);
+        let (vn_rows, user_cols) = vn_users.shape();
+
+        // Explicit votes are fitted around their mean, implicit ones scaled
+        // to confidences
+        let votes = vn_users.data();
+        let offset = if self.implicit || votes.is_empty() {
+            0.0
+        } else {
+            votes.iter().sum::<f64>() / votes.len() as f64
+        };
+        let max_vote = votes.iter().fold(0.0f64, |max, vote| max.max(vote.abs()));
+        let scale = if max_vote > 0.0 {
+            self.alpha / max_vote
+        } else {
+            0.0
+        };
+
+        let mut rng = XorShift(self.seed.max(1));
+        let mut vn_factors: Vec<f64> = (0..vn_rows * k)
+            .map(|_| (rng.next_f64() - 0.5) * 0.1)
+            .collect();
+        let mut user_factors = vec![0.0; user_cols * k];
+
+        for _ in 0..self.iterations {
+            self.solve(
+                user_vns.view(),
+                &vn_factors,
+                &mut user_factors,
+                offset,
+                scale,
+            );
+            self.solve(
+                vn_users.view(),
+                &user_factors,
+                &mut vn_factors,
+                offset,
+                scale,
+            );
+        }
+
+        // VNs and users without votes keep zero vectors
+        for (vn_id, votes) in vn_users.outer_iterator().enumerate() {
+            if votes.nnz() == 0 {
+                vn_factors[vn_id * k..(vn_id + 1) * k].fill(0.0);
+            }
+        }
+
+        Embeddings {
+            factors: k,
+            implicit: self.implicit,
+            vn_factors: vn_factors.iter().map(|x| *x as f32).collect(),
+            user_factors: user_factors.iter().map(|x| *x as f32).collect(),
+        }
+    }
+
+    // One half step: solve the factors of every outer row of `votes` (users
+    // of a user x VN view, or VNs of a VN x user one) with `other` held fixed
+    fn solve(
+        &self,
+        votes: CsMatView<f64>,
+        other: &[f64],
+        factors: &mut [f64],
+        offset: f64,
+        scale: f64,
+    ) {
+        let k = self.factors.max(1);
+
+        // Implicit feedback includes every pair, so start from the Gram
+        // matrix of all the fixed factors
+        let mut gram = vec![0.0; k * k];
+        if self.implicit {
+            for y in other.chunks_exact(k) {
+                add_outer(&mut gram, y, 1.0, k);
+            }
+        }
+
+        let mut a = vec![0.0; k * k];
+        let mut b = vec![0.0; k];
+        for (id, row) in votes.outer_iterator().enumerate() {
+            let x = &mut factors[id * k..(id + 1) * k];
+            if row.nnz() == 0 {
+                x.fill(0.0);
+                continue;
+            }
+
+            a.copy_from_slice(&gram);
+            b.fill(0.0);
+            for (other_id, vote) in row.iter() {
+                let y = &other[other_id * k..(other_id + 1) * k];
+                let (weight, target) = if self.implicit {
+                    let confidence = 1.0 + scale * vote.abs();
+                    (confidence - 1.0, confidence)
+                } else {
+                    (1.0, vote - offset)
+                };
+                add_outer(&mut a, y, weight, k);
+                for (b, y) in b.iter_mut().zip(y) {
+                    *b += target * y;
+                }
+            }
+
+            // Explicit factors are regularized in proportion to their number
+            // of votes, so heavy voters are not underfitted
+            let lambda = if self.implicit {
+                self.regularization
+            } else {
+                self.regularization * row.nnz() as f64
+            };
+            for i in 0..k {
+                a[i * k + i] += lambda;
+            }
+
+            cholesky_solve(&mut a, &mut b, k);
+            x.copy_from_slice(&b);
+        }
+    }
+}
+
+// a += weight * y y^T for a row-major k x k matrix
+fn add_outer(a: &mut [f64], y: &[f64], weight: f64, k: usize) {
+    for (i, yi) in y.iter().enumerate() {
+        let row = &mut a[i * k..(i + 1) * k];
+        for (a, yj) in row.iter_mut().zip(y) {
+            *a += weight * yi * yj;
+        }
+    }
+}
+
+// Solve a x = b for a symmetric positive definite row-major k x k matrix,
+// leaving x in b. `a` is overwritten by its Cholesky factor.
+fn cholesky_solve(a: &mut [f64], b: &mut [f64], k: usize) {
+    for j in 0..k {
+        let mut d = a[j * k + j];
+        for p in 0..j {
+            d -= a[j * k + p] * a[j * k + p];
+        }
+        let d = d.max(f64::EPSILON).sqrt();
+        a[j * k + j] = d;
+        for i in j + 1..k {
+            let mut s = a[i * k + j];
+            for p in 0..j {
+                s -= a[i * k + p] * a[j * k + p];
+            }
+            a[i * k + j] = s / d;
+        }
+    }
+
+    // Forward substitution with L, then back substitution with L^T
+    for i in 0..k {
+        let s: f64 = (0..i).map(|p| a[i * k + p] * b[p]).sum();
+        b[i] = (b[i] - s) / a[i * k + i];
+    }
+    for i in (0..k).rev() {
+        let s: f64 = (i + 1..k).map(|p| a[p * k + i] * b[p]).sum();
+        b[i] = (b[i] - s) / a[i * k + i];
+    }
+}
+
+// Cosine similarity of the VNs' factor vectors, from the embeddings trained
+// with VisualNovelRecommender::train_als
+#[derive(Debug, Clone, Copy, Default)]
+pub struct AlsScorer;
+
+impl Scorer for AlsScorer {
+    fn name(&self) -> &str {
+        ALS_SOURCE
+    }
+
+    // The embeddings are trained by train_als and kept on the recommender so
+    // that snapshots include them
+    fn fit(&mut self, _data: &VisualNovelRecommender) -> Result<()> {
+        Ok(())
+    }
+
+    fn score(
+        &self,
+        data: &VisualNovelRecommender,
+        seeds: &[(i32, f64)],
+        num_vns: usize,
+    ) -> HashMap<i32, f64> {
+        match &data.embeddings {
+            Some(embeddings) => {
+                scoring::seed_scores(seeds, num_vns, |seeds| embeddings.similarities(seeds))
+            }
+            None => HashMap::new(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::RecommenderConfig;
+    use crate::test_data;
+
+    fn mat_vec(a: &[f64], x: &[f64], k: usize) -> Vec<f64> {
+        a.chunks_exact(k)
+            .map(|row| row.iter().zip(x).map(|(a, x)| a * x).sum())
+            .collect()
+    }
+
+    #[test]
+    fn cholesky_solves_spd_systems() {
+        let a = [4.0, 12.0, -16.0, 12.0, 37.0, -43.0, -16.0, -43.0, 98.0];
+        let b = [1.0, 2.0, 3.0];
+        let mut factor = a;
+        let mut x = b;
+        cholesky_solve(&mut factor, &mut x, 3);
+
+        // The factor of this matrix is known exactly
+        let l = [2.0, 6.0, 1.0, -8.0, 5.0, 3.0];
+        let lower = [
+            factor[0], factor[3], factor[4], factor[6], factor[7], factor[8],
+        ];
+        for (value, expected) in lower.iter().zip(l) {
+            assert!(
+                (value - expected).abs() < 1e-12,
+                "{} != {}",
+                value,
+                expected
+            );
+        }
+        for (ax, b) in mat_vec(&a, &x, 3).iter().zip(b) {
+            assert!((ax - b).abs() < 1e-9, "{} != {}", ax, b);
+        }
+    }
+
+    #[test]
+    fn cholesky_solves_gram_matrices() {
+        let k = 4;
+        let mut a = vec![0.0; k * k];
+        let mut rng = XorShift(7);
+        for _ in 0..10 {
+            let y: Vec<f64> = (0..k).map(|_| rng.next_f64() - 0.5).collect();
+            add_outer(&mut a, &y, 1.0, k);
+        }
+        for i in 0..k {
+            a[i * k + i] += 0.1;
+        }
+        let b = [1.0, -2.0, 0.5, 3.0];
+        let mut factor = a.clone();
+        let mut x = b;
+        cholesky_solve(&mut factor, &mut x, k);
+        for (ax, b) in mat_vec(&a, &x, k).iter().zip(b) {
+            assert!((ax - b).abs() < 1e-9, "{} != {}", ax, b);
+        }
+    }
+
+    #[test]
+    fn training_reduces_reconstruction_error() {
+        let recommender = test_data::recommender(RecommenderConfig::default());
+        let vn_users = recommender.vn_users.as_ref().unwrap();
+        let user_vns = recommender.user_vns.as_ref().unwrap();
+        let votes = vn_users.data();
+        let mean = votes.iter().sum::<f64>() / votes.len() as f64;
+
+        let error = |iterations: usize| {
+            let als = Als {
+                factors: 3,
+                regularization: 0.01,
+                iterations,
+                ..Als::default()
+            };
+            let embeddings = als.train(vn_users, user_vns);
+            let mut error = 0.0;
+            for (vn_id, row) in vn_users.outer_iterator().enumerate() {
+                let vn = embeddings.vn(vn_id as i32).unwrap();
+                for (user_id, vote) in row.iter() {
+                    let user = embeddings.user(user_id as i32).unwrap();
+                    let predicted: f64 = mean
+                        + vn.iter()
+                            .zip(user)
+                            .map(|(a, b)| (a * b) as f64)
+                            .sum::<f64>();
+                    error += (predicted - vote).powi(2);
+                }
+            }
+            error
+        };
+
+        let errors: Vec<f64> = [1, 2, 5, 20].into_iter().map(error).collect();
+        for pair in errors.windows(2) {
+            assert!(pair[1] <= pair[0] + 1e-6, "{:?}", errors);
+        }
+        assert!(errors[3] < errors[0] * 0.5, "{:?}", errors);
+    }
+}
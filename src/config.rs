@@ -0,0 +1,384 @@
+use crate::data::TagCategory;
+use crate::error::{RecommenderError, Result};
+use crate::tag_filter::TagFilter;
+use crate::tag_rules::TagRule;
+use serde::{Deserialize, Serialize};
+use std::{fmt, fs, path::Path, str::FromStr};
+
+// Highest tag spoiler level: major spoilers
+pub const MAX_SPOILER: u8 = 2;
+
+// The tags ignored unless ignore_tags is set, by name. With the tag catalog
+// loaded each is matched by its name; the ids are the fallback without the
+// catalog, and for a name the catalog does not list under its id.
+pub const DEFAULT_IGNORE_TAGS: [(&str, i32); 6] = [
+    ("Male Protagonist", 32),
+    ("Sexual Content", 2040),
+    ("Nukige", 2461),
+    ("Explicit Sex", 1434),
+    ("Vaginal Sex", 1431),
+    ("Female Protagonist", 43),
+];
+
+// Weighting of the averaged tag votes in the similarity matrix, so that
+// tags most VNs have count for less than rare ones
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum TagWeighting {
+    // The tag votes as they are
+    #[default]
+    None,
+    // Tag votes times the inverse document frequency of the tag
+    Idf,
+    // BM25: tag votes saturated by bm25_k1 and normalized for the VN's
+    // number of tags by bm25_b, times the BM25 inverse document frequency
+    Bm25,
+}
+
+impl fmt::Display for TagWeighting {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            TagWeighting::None => "none",
+            TagWeighting::Idf => "idf",
+            TagWeighting::Bm25 => "bm25",
+        };
+        f.write_str(name)
+    }
+}
+
+impl FromStr for TagWeighting {
+    type Err = RecommenderError;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "none" => Ok(TagWeighting::None),
+            "idf" => Ok(TagWeighting::Idf),
+            "bm25" => Ok(TagWeighting::Bm25),
+            _ => Err(RecommenderError::InvalidConfig(format!(
+                "unknown tag weighting '{}'",
+                s
+            ))),
+        }
+    }
+}
+
+// Options of a single query, on top of the config
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct QueryOptions {
+    // Tag rules for the tag model, multiplying the weights the similarity
+    // matrix was built with. Tags removed from the matrix cannot be brought
+    // back.
+    pub tag_rules: Vec<TagRule>,
+    // Tags the recommendations of every model must or must not have
+    pub tag_filter: TagFilter,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RecommenderConfig {
+    // Number of recommendations returned per list
+    pub num_vns: usize,
+    // Weight of the tag model in combined recommendations
+    pub tag_weight: f64,
+    // Weight of the vote model in combined recommendations
+    pub vote_weight: f64,
+    // Exponent applied to each tag vote
+    pub tag_exp: f64,
+    // Exponent applied to each user vote
+    pub vote_exp: f64,
+    // Tag ids left out of the similarity matrix instead of
+    // DEFAULT_IGNORE_TAGS; an empty list keeps them all
+    pub ignore_tags: Option<Vec<i32>>,
+    // Rules ignoring or boosting tags by name, id, category or subtree,
+    // applied when the similarity matrix is built, after ignore_tags and
+    // ignore_tag_categories so that they can make exceptions to them
+    pub tag_rules: Vec<TagRule>,
+    // Tag categories left out of the similarity matrix, e.g. technical tags
+    // such as ADV or Voiced; needs the tag catalog
+    pub ignore_tag_categories: Vec<TagCategory>,
+    // Tags a VN has at a higher spoiler level than this are left out: 0
+    // keeps only tags that spoil nothing, 2 keeps all
+    pub max_tag_spoiler: u8,
+    // Fraction of a tag vote passed on to each parent tag, and again to
+    // theirs; 0 leaves the tag hierarchy out
+    pub tag_parent_decay: f64,
+    // Weighting of the tag votes in the similarity matrix
+    pub tag_weighting: TagWeighting,
+    // BM25 term saturation and VN length normalization
+    pub bm25_k1: f64,
+    pub bm25_b: f64,
+    // Print loading progress
+    pub verbose: bool,
+    // Only load titles, skipping votes and tags
+    pub skip_recs: bool,
+    // Multiplier for the weight of negative ("less like this") seed VNs
+    pub negative_seed_strength: f64,
+    // Languages to show titles in, most preferred first (e.g. "zh-Hans");
+    // VNs with none of them show an official title
+    pub title_languages: Vec<String>,
+    // Show the romanized form of titles in non-latin scripts when there is one
+    pub romanized_titles: bool,
+}
+
+impl Default for RecommenderConfig {
+    fn default() -> Self {
+        Self {
+            num_vns: 25,
+            tag_weight: 1.5,
+            vote_weight: 1.0,
+            tag_exp: 2.0,
+            vote_exp: 1.0,
+            ignore_tags: None,
+            tag_rules: Vec::new(),
+            ignore_tag_categories: Vec::new(),
+            max_tag_spoiler: MAX_SPOILER,
+            tag_parent_decay: 0.0,
+            tag_weighting: TagWeighting::None,
+            bm25_k1: 1.2,
+            bm25_b: 0.75,
+            verbose: false,
+            skip_recs: false,
+            negative_seed_strength: 1.0,
+            title_languages: vec!["en".into(), "ja".into()],
+            romanized_titles: true,
+        }
+    }
+}
+
+impl RecommenderConfig {
+    pub fn builder() -> RecommenderConfigBuilder {
+        RecommenderConfigBuilder::default()
+    }
+
+    // Load a config file, picking the format from the extension (.toml or .json)
+    pub fn from_file(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let contents = fs::read_to_string(path).map_err(|e| RecommenderError::io(path, e))?;
+
+        match path.extension().and_then(|ext| ext.to_str()) {
+            Some("json") => Self::from_json_str(&contents),
+            Some("toml") => Self::from_toml_str(&contents),
+            _ => Err(RecommenderError::InvalidConfig(format!(
+                "unsupported config file extension: {}",
+                path.display()
+            ))),
+        }
+    }
+
+    pub fn from_toml_str(contents: &str) -> Result<Self> {
+        toml::from_str(contents).map_err(|e| RecommenderError::InvalidConfig(e.to_string()))
+    }
+
+    pub fn from_json_str(contents: &str) -> Result<Self> {
+        serde_json::from_str(contents).map_err(|e| RecommenderError::InvalidConfig(e.to_string()))
+    }
+
+    pub fn to_toml_string(&self) -> Result<String> {
+        toml::to_string_pretty(self).map_err(|e| RecommenderError::InvalidConfig(e.to_string()))
+    }
+
+    pub fn to_json_string(&self) -> Result<String> {
+        serde_json::to_string_pretty(self)
+            .map_err(|e| RecommenderError::InvalidConfig(e.to_string()))
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct RecommenderConfigBuilder {
+    config: RecommenderConfig,
+}
+
+impl RecommenderConfigBuilder {
+    pub fn num_vns(mut self, num_vns: usize) -> Self {
+        self.config.num_vns = num_vns;
+        self
+    }
+
+    pub fn tag_weight(mut self, tag_weight: f64) -> Self {
+        self.config.tag_weight = tag_weight;
+        self
+    }
+
+    pub fn vote_weight(mut self, vote_weight: f64) -> Self {
+        self.config.vote_weight = vote_weight;
+        self
+    }
+
+    pub fn tag_exp(mut self, tag_exp: f64) -> Self {
+        self.config.tag_exp = tag_exp;
+        self
+    }
+
+    pub fn vote_exp(mut self, vote_exp: f64) -> Self {
+        self.config.vote_exp = vote_exp;
+        self
+    }
+
+    pub fn tag_rules(mut self, tag_rules: Vec<TagRule>) -> Self {
+        self.config.tag_rules = tag_rules;
+        self
+    }
+
+    pub fn ignore_tags(mut self, ignore_tags: Vec<i32>) -> Self {
+        self.config.ignore_tags = Some(ignore_tags);
+        self
+    }
+
+    pub fn ignore_tag_categories(mut self, ignore_tag_categories: Vec<TagCategory>) -> Self {
+        self.config.ignore_tag_categories = ignore_tag_categories;
+        self
+    }
+
+    pub fn max_tag_spoiler(mut self, max_tag_spoiler: u8) -> Self {
+        self.config.max_tag_spoiler = max_tag_spoiler;
+        self
+    }
+
+    pub fn tag_parent_decay(mut self, tag_parent_decay: f64) -> Self {
+        self.config.tag_parent_decay = tag_parent_decay;
+        self
+    }
+
+    pub fn tag_weighting(mut self, tag_weighting: TagWeighting) -> Self {
+        self.config.tag_weighting = tag_weighting;
+        self
+    }
+
+    pub fn bm25_k1(mut self, bm25_k1: f64) -> Self {
+        self.config.bm25_k1 = bm25_k1;
+        self
+    }
+
+    pub fn bm25_b(mut self, bm25_b: f64) -> Self {
+        self.config.bm25_b = bm25_b;
+        self
+    }
+
+    pub fn verbose(mut self, verbose: bool) -> Self {
+        self.config.verbose = verbose;
+        self
+    }
+
+    pub fn skip_recs(mut self, skip_recs: bool) -> Self {
+        self.config.skip_recs = skip_recs;
+        self
+    }
+
+    pub fn negative_seed_strength(mut self, negative_seed_strength: f64) -> Self {
+        self.config.negative_seed_strength = negative_seed_strength;
+        self
+    }
+
+    pub fn title_languages(mut self, title_languages: Vec<String>) -> Self {
+        self.config.title_languages = title_languages;
+        self
+    }
+
+    pub fn romanized_titles(mut self, romanized_titles: bool) -> Self {
+        self.config.romanized_titles = romanized_titles;
+        self
+    }
+
+    pub fn build(self) -> RecommenderConfig {
+        self.config
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn builder_sets_fields() {
+        let config = RecommenderConfig::builder()
+            .num_vns(10)
+            .tag_weight(2.0)
+            .vote_exp(1.5)
+            .max_tag_spoiler(1)
+            .tag_weighting(TagWeighting::Idf)
+            .title_languages(vec!["ja".into()])
+            .build();
+
+        assert_eq!(config.num_vns, 10);
+        assert_eq!(config.tag_weight, 2.0);
+        assert_eq!(config.vote_exp, 1.5);
+        assert_eq!(config.max_tag_spoiler, 1);
+        assert_eq!(config.tag_weighting, TagWeighting::Idf);
+        assert_eq!(config.title_languages, ["ja"]);
+        // Everything else keeps its default
+        assert_eq!(config.vote_weight, RecommenderConfig::default().vote_weight);
+        assert_eq!(config.tag_exp, RecommenderConfig::default().tag_exp);
+    }
+
+    #[test]
+    fn partial_config_files() {
+        let config =
+            RecommenderConfig::from_toml_str("num_vns = 5\ntag_weighting = \"bm25\"").unwrap();
+        assert_eq!(
+            config,
+            RecommenderConfig::builder()
+                .num_vns(5)
+                .tag_weighting(TagWeighting::Bm25)
+                .build()
+        );
+
+        let config = RecommenderConfig::from_json_str("{\"vote_weight\": 0.5}").unwrap();
+        assert_eq!(
+            config,
+            RecommenderConfig::builder().vote_weight(0.5).build()
+        );
+    }
+
+    #[test]
+    fn config_round_trips() {
+        let config = RecommenderConfig::builder()
+            .num_vns(7)
+            .tag_parent_decay(0.5)
+            .ignore_tag_categories(vec![TagCategory::Technical])
+            .build();
+
+        let toml = config.to_toml_string().unwrap();
+        assert_eq!(RecommenderConfig::from_toml_str(&toml).unwrap(), config);
+        let json = config.to_json_string().unwrap();
+        assert_eq!(RecommenderConfig::from_json_str(&json).unwrap(), config);
+    }
+
+    #[test]
+    fn invalid_config_files() {
+        for err in [
+            RecommenderConfig::from_toml_str("num_vns = \"many\"").unwrap_err(),
+            RecommenderConfig::from_json_str("{").unwrap_err(),
+            RecommenderConfig::from_toml_str("tag_weighting = \"tfidf\"").unwrap_err(),
+            RecommenderConfig::from_file(concat!(env!("CARGO_MANIFEST_DIR"), "/README.md"))
+                .unwrap_err(),
+        ] {
+            assert_eq!(err.kind(), "InvalidConfig");
+        }
+        assert_eq!(
+            RecommenderConfig::from_file("/nonexistent/config.toml")
+                .unwrap_err()
+                .kind(),
+            "MissingFile"
+        );
+    }
+
+    #[test]
+    fn default_ignored_tags() {
+        let config = RecommenderConfig::from_toml_str("").unwrap();
+        assert_eq!(config.ignore_tags, None);
+        assert!(config.tag_rules.is_empty());
+
+        let config = RecommenderConfig::from_toml_str("ignore_tags = []").unwrap();
+        assert_eq!(config.ignore_tags, Some(Vec::new()));
+        let config = RecommenderConfig::from_toml_str("ignore_tags = [32, 7]").unwrap();
+        assert_eq!(config.ignore_tags, Some(vec![32, 7]));
+
+        // Rules come on top of the defaults instead of replacing them
+        let config =
+            RecommenderConfig::from_toml_str("tag_rules = [{ tag = \"Mystery\", weight = 2.0 }]")
+                .unwrap();
+        assert_eq!(config.ignore_tags, None);
+        assert_eq!(config.tag_rules, [TagRule::tag("Mystery").weight(2.0)]);
+    }
+}
@@ -1,6 +1,9 @@
-use std::sync::Arc;
+use crate::config::TagWeighting;
+use crate::error::{RecommenderError, Result};
+use serde::{Deserialize, Serialize};
+use std::{collections::BTreeMap, fmt, str::FromStr, sync::Arc};
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct VnTitle {
     pub vn_id: i32,
     pub language: Arc<str>,
@@ -9,7 +12,7 @@ pub struct VnTitle {
     pub latin_title: Option<Arc<str>>,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Rating {
     pub vn_id: i32,
     pub user_id: i32,
@@ -17,9 +20,89 @@ pub struct Rating {
     pub date: Arc<str>,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Tag {
     pub tag_id: i32,
     pub vn_id: i32,
     pub rating: f64,
+    // Spoiler level the voter gave (0 none, 1 minor, 2 major), if any
+    pub spoiler: Option<u8>,
+    // The voter flagged the tag as a lie: it seems to apply but does not
+    pub lie: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum TagCategory {
+    Content,
+    Sexual,
+    Technical,
+}
+
+impl fmt::Display for TagCategory {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            TagCategory::Content => "content",
+            TagCategory::Sexual => "sexual",
+            TagCategory::Technical => "technical",
+        };
+        f.write_str(name)
+    }
+}
+
+impl FromStr for TagCategory {
+    type Err = RecommenderError;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "content" => Ok(TagCategory::Content),
+            "sexual" => Ok(TagCategory::Sexual),
+            "technical" => Ok(TagCategory::Technical),
+            _ => Err(RecommenderError::InvalidConfig(format!(
+                "unknown tag category '{}'",
+                s
+            ))),
+        }
+    }
+}
+
+// Tag metadata from the `tags` table of the database dump
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TagInfo {
+    pub tag_id: i32,
+    pub name: Arc<str>,
+    pub category: TagCategory,
+    // Spoiler level of the tag when its voters give none
+    pub default_spoiler: u8,
+}
+
+// A link of the tag DAG from the `tags_parents` table
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct TagParent {
+    pub tag_id: i32,
+    pub parent_id: i32,
+}
+
+// One entry of a recommendation list
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Recommendation {
+    pub vn_id: i32,
+    // Score the list is ordered by
+    pub score: f64,
+    // Normalized score of each model that contributed, by model name
+    pub source_scores: BTreeMap<String, f64>,
+    // Position in the list, starting at 1
+    pub rank: usize,
+}
+
+// The recommendation lists of each model for one query
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RecommendationLists {
+    pub tag: Vec<Recommendation>,
+    pub vote: Vec<Recommendation>,
+    pub combined: Vec<Recommendation>,
+    // Weighting of the tag matrix behind the tag scores
+    pub tag_weighting: TagWeighting,
+    // Lists of the scorers added with add_scorer, by scorer name
+    pub extra: BTreeMap<String, Vec<Recommendation>>,
 }
@@ -0,0 +1,294 @@
+// EASE, a linear item-item model: each VN's column of the user x VN vote
+// matrix is regressed on all other columns, which has a closed-form solution
+// (Steck, "Embarrassingly Shallow Autoencoders for Sparse Data", 2019). It is
+// SLIM without the sparsity and sign constraints; the learned weights are
+// sparsified afterwards by keeping the largest ones per VN instead.
+use crate::error::Result;
+use crate::recommender::VisualNovelRecommender;
+use crate::scorer::Scorer;
+use crate::scoring;
+use sprs::{CsMat, TriMat};
+use std::collections::HashMap;
+
+// Name of the model in Recommendation::source_scores
+pub const EASE_SOURCE: &str = "ease";
+
+// Scores candidates by the learned weights from the seed VNs to them
+#[derive(Debug, Clone)]
+pub struct EaseScorer {
+    // L2 penalty on the weights, added to the diagonal of the Gram matrix
+    pub regularization: f64,
+    // Number of weights kept per VN, largest magnitude first
+    pub top_k: usize,
+    // Only the most-voted VNs are modelled, as training inverts a dense
+    // matrix of this size
+    pub max_vns: usize,
+    // VN x VN weights, rows by seed VN
+    weights: Option<CsMat<f64>>,
+}
+
+impl Default for EaseScorer {
+    fn default() -> Self {
+        Self {
+            regularization: 250.0,
+            top_k: 100,
+            max_vns: 2000,
+            weights: None,
+        }
+    }
+}
+
+impl EaseScorer {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn regularization(mut self, regularization: f64) -> Self {
+        self.regularization = regularization;
+        self
+    }
+
+    pub fn top_k(mut self, top_k: usize) -> Self {
+        self.top_k = top_k;
+        self
+    }
+
+    pub fn max_vns(mut self, max_vns: usize) -> Self {
+        self.max_vns = max_vns;
+        self
+    }
+}
+
+impl Scorer for EaseScorer {
+    fn name(&self) -> &str {
+        EASE_SOURCE
+    }
+
+    // Solve for the weights on whether users voted on the VNs
+    fn fit(&mut self, data: &VisualNovelRecommender) -> Result<()> {
+        let (Some(vn_users), Some(user_vns)) = (&data.vn_users, &data.user_vns) else {
+            self.weights = None;
+            return Ok(());
+        };
+
+        // Most-voted VNs first, ties by id
+        let mut vns: Vec<(usize, usize)> = vn_users
+            .outer_iterator()
+            .enumerate()
+            .map(|(vn_id, votes)| (vn_id, votes.nnz()))
+            .filter(|(_, count)| *count > 0)
+            .collect();
+        vns.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+        vns.truncate(self.max_vns);
+        let positions: HashMap<usize, usize> = vns
+            .iter()
+            .enumerate()
+            .map(|(i, (vn_id, _))| (*vn_id, i))
+            .collect();
+        let n = vns.len();
+
+        // Gram matrix of the binary vote matrix: how many users voted on
+        // both of each pair of VNs
+        let mut gram: Vec<f64> = vec![0.0; n * n];
+        let mut voted: Vec<usize> = Vec::new();
+        for votes in user_vns.outer_iterator() {
+            voted.clear();
+            voted.extend(
+                votes
+                    .indices()
+                    .iter()
+                    .filter_map(|vn_id| positions.get(vn_id).copied()),
+            );
+            for &i in &voted {
+                for &j in &voted {
+                    gram[i * n + j] += 1.0;
+                }
+            }
+        }
+        for i in 0..n {
+            gram[i * n + i] += self.regularization.max(f64::EPSILON);
+        }
+
+        // With P the inverse, the weight from VN i to VN j is -P_ij / P_jj
+        invert_spd(&mut gram, n);
+        let inverse = gram;
+
+        let mut triplet_matrix = TriMat::new((vn_users.rows(), vn_users.rows()));
+        let mut row: Vec<(usize, f64)> = Vec::with_capacity(n);
+        for i in 0..n {
+            row.clear();
+            row.extend(
+                (0..n)
+                    .filter(|&j| j != i)
+                    .map(|j| (j, -inverse[i * n + j] / inverse[j * n + j])),
+            );
+            row.sort_by(|a, b| b.1.abs().total_cmp(&a.1.abs()).then(a.0.cmp(&b.0)));
+            row.truncate(self.top_k);
+            for &(j, weight) in &row {
+                triplet_matrix.add_triplet(vns[i].0, vns[j].0, weight);
+            }
+        }
+        self.weights = Some(triplet_matrix.to_csr());
+
+        Ok(())
+    }
+
+    fn score(
+        &self,
+        _data: &VisualNovelRecommender,
+        seeds: &[(i32, f64)],
+        num_vns: usize,
+    ) -> HashMap<i32, f64> {
+        let Some(weights) = &self.weights else {
+            return HashMap::new();
+        };
+
+        scoring::seed_scores(seeds, num_vns, |seeds| {
+            let mut totals: HashMap<i32, f64> = HashMap::new();
+            for &(vn_id, seed_weight) in seeds {
+                let Some(row) = weights.outer_view(vn_id as usize) else {
+                    continue;
+                };
+                for (other_id, weight) in row.iter() {
+                    *totals.entry(other_id as i32).or_insert(0.0) += seed_weight * weight;
+                }
+            }
+            totals
+        })
+    }
+}
+
+// Invert a symmetric positive definite row-major n x n matrix in place by
+// Gauss-Jordan elimination, which needs no pivoting for such matrices
+fn invert_spd(a: &mut [f64], n: usize) {
+    let mut pivot_row = vec![0.0; n];
+    for k in 0..n {
+        let pivot = 1.0 / a[k * n + k];
+        a[k * n + k] = 1.0;
+        for value in &mut a[k * n..(k + 1) * n] {
+            *value *= pivot;
+        }
+        pivot_row.copy_from_slice(&a[k * n..(k + 1) * n]);
+
+        for i in (0..n).filter(|&i| i != k) {
+            let factor = a[i * n + k];
+            if factor == 0.0 {
+                continue;
+            }
+            a[i * n + k] = 0.0;
+            for (value, pivot_value) in a[i * n..(i + 1) * n].iter_mut().zip(&pivot_row) {
+                *value -= factor * pivot_value;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::RecommenderConfig;
+    use crate::test_data;
+
+    fn assert_inverse(a: &[f64], inverse: &[f64], n: usize) {
+        for i in 0..n {
+            for j in 0..n {
+                let product: f64 = (0..n).map(|p| a[i * n + p] * inverse[p * n + j]).sum();
+                let identity = if i == j { 1.0 } else { 0.0 };
+                assert!(
+                    (product - identity).abs() < 1e-9,
+                    "({}, {}): {}",
+                    i,
+                    j,
+                    product
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn inverts_spd_matrices() {
+        let a = [4.0, 12.0, -16.0, 12.0, 37.0, -43.0, -16.0, -43.0, 98.0];
+        let mut inverse = a;
+        invert_spd(&mut inverse, 3);
+        assert_inverse(&a, &inverse, 3);
+
+        let a = [2.0, 0.0, 0.0, 0.0, 0.5, 0.0, 0.0, 0.0, 4.0];
+        let mut inverse = a;
+        invert_spd(&mut inverse, 3);
+        assert_eq!(inverse, [0.5, 0.0, 0.0, 0.0, 2.0, 0.0, 0.0, 0.0, 0.25]);
+    }
+
+    #[test]
+    fn inverts_regularized_gram_matrices() {
+        // Co-vote counts of four VNs, as fit builds them
+        let n = 4;
+        let mut a = vec![
+            3.0, 2.0, 1.0, 0.0, //
+            2.0, 4.0, 2.0, 1.0, //
+            1.0, 2.0, 3.0, 1.0, //
+            0.0, 1.0, 1.0, 2.0,
+        ];
+        for i in 0..n {
+            a[i * n + i] += 0.5;
+        }
+        let mut inverse = a.clone();
+        invert_spd(&mut inverse, n);
+        assert_inverse(&a, &inverse, n);
+    }
+
+    fn fitted_weights(scorer: EaseScorer) -> CsMat<f64> {
+        let data = test_data::recommender(RecommenderConfig::default());
+        let mut scorer = scorer;
+        scorer.fit(&data).unwrap();
+        scorer.weights.unwrap()
+    }
+
+    #[test]
+    fn max_vns_caps_the_modelled_vns() {
+        // v1 to v7 have three votes each and v8 two, so ties by id keep v1
+        // and v2, voted on together by users 1 and 4: the inverse of
+        // [[3 + 1, 2], [2, 3 + 1]] is [[4, -2], [-2, 4]] / 12, a weight of
+        // 2 / 4 each way
+        let weights = fitted_weights(EaseScorer::new().regularization(1.0).max_vns(2));
+        let entries: Vec<(usize, usize, f64)> = weights
+            .iter()
+            .map(|(weight, (i, j))| (i, j, *weight))
+            .collect();
+        assert_eq!(entries, [(1, 2, 0.5), (2, 1, 0.5)]);
+
+        let weights = fitted_weights(EaseScorer::new().max_vns(5));
+        assert!(weights.iter().all(|(_, (i, j))| i <= 5 && j <= 5));
+        assert_eq!(weights.nnz(), 5 * 4);
+    }
+
+    #[test]
+    fn top_k_keeps_the_largest_weights() {
+        let all = fitted_weights(EaseScorer::new());
+        assert_eq!(all.nnz(), 8 * 7);
+
+        for top_k in [1, 3] {
+            let kept = fitted_weights(EaseScorer::new().top_k(top_k));
+            for vn_id in 1..=8 {
+                let mut row: Vec<(usize, f64)> = all
+                    .outer_view(vn_id)
+                    .unwrap()
+                    .iter()
+                    .map(|(other_id, weight)| (other_id, *weight))
+                    .collect();
+                row.sort_by(|a, b| b.1.abs().total_cmp(&a.1.abs()).then(a.0.cmp(&b.0)));
+                row.truncate(top_k);
+                row.sort_by_key(|(other_id, _)| *other_id);
+
+                let kept_row: Vec<(usize, f64)> = kept
+                    .outer_view(vn_id)
+                    .unwrap()
+                    .iter()
+                    .map(|(other_id, weight)| (other_id, *weight))
+                    .collect();
+                assert_eq!(kept_row, row, "v{}", vn_id);
+            }
+        }
+
+        assert_eq!(fitted_weights(EaseScorer::new().top_k(0)).nnz(), 0);
+    }
+}
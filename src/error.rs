@@ -0,0 +1,140 @@
+use std::{fmt, io, path::PathBuf};
+
+pub type Result<T> = std::result::Result<T, RecommenderError>;
+
+#[derive(Debug)]
+pub enum RecommenderError {
+    // A required data file does not exist
+    MissingFile(PathBuf),
+    // No `vndb-votes-*` file was found in the data directory
+    NoVotesFile(PathBuf),
+    // Reading a data file failed for a reason other than it being absent
+    Io {
+        path: PathBuf,
+        source: io::Error,
+    },
+    // A line could not be read or decoded at all
+    MalformedLine {
+        file: PathBuf,
+        line: usize,
+        reason: String,
+    },
+    // The requested VN id is not part of the loaded dataset
+    UnknownVn(i32),
+    // The VN exists but has no votes
+    NoRatings(i32),
+    // A data file was read successfully but yielded no usable records
+    EmptyDataset(&'static str),
+    // A config file could not be parsed or serialized
+    InvalidConfig(String),
+    // A snapshot file is corrupt, truncated or of an unsupported version
+    InvalidSnapshot(String),
+    // A query was given no seed VNs with a positive weight
+    EmptyQuery,
+    // The requested user id has no votes in the loaded dataset
+    UnknownUser(i32),
+    // A query needs a model that has not been trained, e.g. ALS embeddings
+    ModelNotTrained(&'static str),
+    // A tag rule names a tag that is not in the tag catalog
+    UnknownTag(String),
+}
+
+impl RecommenderError {
+    // Stable, machine-readable name of the variant for non-Rust callers
+    pub fn kind(&self) -> &'static str {
+        match self {
+            RecommenderError::MissingFile(_) => "MissingFile",
+            RecommenderError::NoVotesFile(_) => "NoVotesFile",
+            RecommenderError::Io { .. } => "Io",
+            RecommenderError::MalformedLine { .. } => "MalformedLine",
+            RecommenderError::UnknownVn(_) => "UnknownVn",
+            RecommenderError::NoRatings(_) => "NoRatings",
+            RecommenderError::EmptyDataset(_) => "EmptyDataset",
+            RecommenderError::InvalidConfig(_) => "InvalidConfig",
+            RecommenderError::InvalidSnapshot(_) => "InvalidSnapshot",
+            RecommenderError::EmptyQuery => "EmptyQuery",
+            RecommenderError::UnknownUser(_) => "UnknownUser",
+            RecommenderError::ModelNotTrained(_) => "ModelNotTrained",
+            RecommenderError::UnknownTag(_) => "UnknownTag",
+        }
+    }
+
+    pub fn io(path: impl Into<PathBuf>, source: io::Error) -> Self {
+        let path = path.into();
+        if source.kind() == io::ErrorKind::NotFound {
+            RecommenderError::MissingFile(path)
+        } else {
+            RecommenderError::Io { path, source }
+        }
+    }
+}
+
+impl fmt::Display for RecommenderError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RecommenderError::MissingFile(path) => {
+                write!(f, "data file not found: {}", path.display())
+            }
+            RecommenderError::NoVotesFile(dir) => write!(
+                f,
+                "no votes file matching 'vndb-votes-*' found in {}",
+                dir.display()
+            ),
+            RecommenderError::Io { path, source } => {
+                write!(f, "failed to read {}: {}", path.display(), source)
+            }
+            RecommenderError::MalformedLine { file, line, reason } => {
+                write!(f, "{}:{}: malformed line: {}", file.display(), line, reason)
+            }
+            RecommenderError::UnknownVn(vn_id) => write!(f, "unknown VN id v{}", vn_id),
+            RecommenderError::NoRatings(vn_id) => {
+                write!(f, "no ratings available for v{}", vn_id)
+            }
+            RecommenderError::EmptyDataset(name) => {
+                write!(f, "dataset '{}' contains no usable records", name)
+            }
+            RecommenderError::InvalidConfig(reason) => write!(f, "invalid config: {}", reason),
+            RecommenderError::InvalidSnapshot(reason) => write!(f, "invalid snapshot: {}", reason),
+            RecommenderError::EmptyQuery => write!(f, "no positive seed VNs given"),
+            RecommenderError::UnknownUser(user_id) => {
+                write!(f, "no votes from user u{} in the loaded data", user_id)
+            }
+            RecommenderError::ModelNotTrained(model) => {
+                write!(f, "the {} model has not been trained", model)
+            }
+            RecommenderError::UnknownTag(name) => write!(
+                f,
+                "unknown tag '{}' (tags are looked up in the tags table of the dump)",
+                name
+            ),
+        }
+    }
+}
+
+impl std::error::Error for RecommenderError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            RecommenderError::Io { source, .. } => Some(source),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::error::Error;
+
+    #[test]
+    fn io_errors() {
+        let err = RecommenderError::io("data/vn_titles", io::ErrorKind::NotFound.into());
+        assert_eq!(err.kind(), "MissingFile");
+        assert_eq!(err.to_string(), "data file not found: data/vn_titles");
+        assert!(err.source().is_none());
+
+        let err = RecommenderError::io("data/tags_vn", io::ErrorKind::PermissionDenied.into());
+        assert_eq!(err.kind(), "Io");
+        assert!(err.to_string().starts_with("failed to read data/tags_vn: "));
+        assert!(err.source().is_some());
+    }
+}
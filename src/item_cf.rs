@@ -0,0 +1,365 @@
+// Item-item collaborative filtering. Two VNs are similar when the same users
+// rated them alike, by one of several similarity measures over the users'
+// votes, shrunk towards zero when few users rated both.
+use crate::error::{RecommenderError, Result};
+use crate::recommender::VisualNovelRecommender;
+use crate::scorer::Scorer;
+use crate::scoring;
+use serde::{Deserialize, Serialize};
+use sprs::CsMat;
+use std::{collections::HashMap, fmt, str::FromStr};
+
+// Name of the model in Recommendation::source_scores
+pub const ITEM_CF_SOURCE: &str = "item_cf";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Similarity {
+    // Cosine of the VNs' vote vectors
+    Cosine,
+    // Cosine after subtracting each user's mean vote
+    AdjustedCosine,
+    // Correlation of the votes of the users who rated both VNs, each VN's
+    // votes centred on their mean over those users
+    Pearson,
+    // Users who rated both VNs over users who rated either
+    Jaccard,
+}
+
+impl fmt::Display for Similarity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Similarity::Cosine => "cosine",
+            Similarity::AdjustedCosine => "adjusted-cosine",
+            Similarity::Pearson => "pearson",
+            Similarity::Jaccard => "jaccard",
+        };
+        f.write_str(name)
+    }
+}
+
+impl FromStr for Similarity {
+    type Err = RecommenderError;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "cosine" => Ok(Similarity::Cosine),
+            "adjusted-cosine" => Ok(Similarity::AdjustedCosine),
+            "pearson" => Ok(Similarity::Pearson),
+            "jaccard" => Ok(Similarity::Jaccard),
+            _ => Err(RecommenderError::InvalidConfig(format!(
+                "unknown similarity measure '{}'",
+                s
+            ))),
+        }
+    }
+}
+
+// Scores candidates by their similarity to the seed VNs
+#[derive(Debug, Clone)]
+pub struct ItemCfScorer {
+    pub similarity: Similarity,
+    // Similarities are multiplied by n / (n + shrinkage), where n is the
+    // number of users who rated both VNs
+    pub shrinkage: f64,
+    // Pairs of VNs rated by fewer users than this are not compared
+    pub min_support: usize,
+    // The votes as used by the measure, VN x user and its transpose
+    vn_users: Option<CsMat<f64>>,
+    user_vns: Option<CsMat<f64>>,
+    // Norm of each VN's vote vector, or its number of votes for Jaccard
+    norms: Vec<f64>,
+}
+
+impl ItemCfScorer {
+    pub fn new(similarity: Similarity) -> Self {
+        Self {
+            similarity,
+            shrinkage: 10.0,
+            min_support: 2,
+            vn_users: None,
+            user_vns: None,
+            norms: Vec::new(),
+        }
+    }
+
+    pub fn shrinkage(mut self, shrinkage: f64) -> Self {
+        self.shrinkage = shrinkage;
+        self
+    }
+
+    pub fn min_support(mut self, min_support: usize) -> Self {
+        self.min_support = min_support;
+        self
+    }
+
+    // Similarity of `vn_id` to every VN that shares a rater with it
+    fn similarities(&self, vn_id: i32) -> HashMap<i32, f64> {
+        let (Some(vn_users), Some(user_vns)) = (&self.vn_users, &self.user_vns) else {
+            return HashMap::new();
+        };
+        let row_idx = vn_id as usize;
+        let Some(users_who_rated) = vn_users.outer_view(row_idx) else {
+            return HashMap::new();
+        };
+
+        // Sums over the common raters of each other VN
+        let mut sums: HashMap<usize, CoRatings> = HashMap::new();
+        for (user_id, x) in users_who_rated.iter() {
+            let Some(user_row) = user_vns.outer_view(user_id) else {
+                continue;
+            };
+            for (other_id, y) in user_row.iter() {
+                if other_id != row_idx {
+                    sums.entry(other_id).or_default().add(*x, *y);
+                }
+            }
+        }
+
+        sums.into_iter()
+            .filter(|(_, sums)| sums.support >= self.min_support.max(1))
+            .filter_map(|(other_id, sums)| {
+                let support = sums.support as f64;
+                let similarity = match self.similarity {
+                    Similarity::Cosine | Similarity::AdjustedCosine => {
+                        sums.dot / (self.norms[row_idx] * self.norms[other_id])
+                    }
+                    Similarity::Pearson => {
+                        // Sums of products of the deviations from the
+                        // co-rater means
+                        let xy = sums.dot - sums.x * sums.y / support;
+                        let xx = sums.xx - sums.x * sums.x / support;
+                        let yy = sums.yy - sums.y * sums.y / support;
+                        xy / (xx * yy).sqrt()
+                    }
+                    Similarity::Jaccard => {
+                        support / (self.norms[row_idx] + self.norms[other_id] - support)
+                    }
+                };
+                let similarity = similarity * support / (support + self.shrinkage);
+                similarity
+                    .is_finite()
+                    .then_some((other_id as i32, similarity))
+            })
+            .collect()
+    }
+}
+
+impl Scorer for ItemCfScorer {
+    fn name(&self) -> &str {
+        ITEM_CF_SOURCE
+    }
+
+    // Transform the votes for the measure and compute the VN norms
+    fn fit(&mut self, data: &VisualNovelRecommender) -> Result<()> {
+        let (Some(vn_users), Some(user_vns)) = (&data.vn_users, &data.user_vns) else {
+            self.vn_users = None;
+            self.user_vns = None;
+            self.norms = Vec::new();
+            return Ok(());
+        };
+        let mut vn_users = vn_users.clone();
+
+        match self.similarity {
+            Similarity::Cosine => {}
+            Similarity::AdjustedCosine => {
+                let user_means: Vec<f64> = user_vns
+                    .outer_iterator()
+                    .map(|votes| mean(votes.data()))
+                    .collect();
+                for mut votes in vn_users.outer_iterator_mut() {
+                    for (user_id, vote) in votes.iter_mut() {
+                        *vote -= user_means[user_id];
+                    }
+                }
+            }
+            // Centred per pair of VNs, on the votes of their common raters
+            Similarity::Pearson => {}
+            Similarity::Jaccard => {
+                vn_users.map_inplace(|_| 1.0);
+            }
+        }
+
+        self.norms = match self.similarity {
+            Similarity::Jaccard => vn_users
+                .outer_iterator()
+                .map(|votes| votes.nnz() as f64)
+                .collect(),
+            _ => scoring::row_norms(vn_users.view()),
+        };
+        self.user_vns = Some(vn_users.to_csc());
+        self.vn_users = Some(vn_users);
+
+        Ok(())
+    }
+
+    fn score(
+        &self,
+        _data: &VisualNovelRecommender,
+        seeds: &[(i32, f64)],
+        num_vns: usize,
+    ) -> HashMap<i32, f64> {
+        scoring::seed_scores(seeds, num_vns, |seeds| {
+            let mut totals: HashMap<i32, f64> = HashMap::new();
+            for &(vn_id, weight) in seeds {
+                for (other_id, similarity) in self.similarities(vn_id) {
+                    *totals.entry(other_id).or_insert(0.0) += weight * similarity;
+                }
+            }
+            totals
+        })
+    }
+}
+
+// Votes x of one VN and y of another by the users who rated both
+#[derive(Debug, Default)]
+struct CoRatings {
+    support: usize,
+    x: f64,
+    y: f64,
+    dot: f64,
+    xx: f64,
+    yy: f64,
+}
+
+impl CoRatings {
+    fn add(&mut self, x: f64, y: f64) {
+        self.support += 1;
+        self.x += x;
+        self.y += y;
+        self.dot += x * y;
+        self.xx += x * x;
+        self.yy += y * y;
+    }
+}
+
+fn mean(values: &[f64]) -> f64 {
+    if values.is_empty() {
+        0.0
+    } else {
+        values.iter().sum::<f64>() / values.len() as f64
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::RecommenderConfig;
+    use crate::source::{DataSource, DataSources};
+    use crate::test_data;
+
+    // Users 1 to 3 rated v1 and v2, users 1 and 3 also v3, which user 4
+    // rated alone
+    const VOTES: &str = "\
+1 1 80 2020-01-01
+2 1 60 2020-01-01
+3 1 70 2020-01-01
+1 2 60 2020-01-01
+2 2 40 2020-01-01
+1 3 70 2020-01-01
+2 3 80 2020-01-01
+3 3 50 2020-01-01
+3 4 90 2020-01-01
+";
+
+    fn fitted(scorer: ItemCfScorer) -> ItemCfScorer {
+        let sources =
+            DataSources::new(DataSource::text(test_data::TITLES)).votes(DataSource::text(VOTES));
+        let data =
+            VisualNovelRecommender::from_sources(RecommenderConfig::default(), sources).unwrap();
+        let mut scorer = scorer;
+        scorer.fit(&data).unwrap();
+        scorer
+    }
+
+    fn similarities(similarity: Similarity) -> HashMap<i32, f64> {
+        fitted(ItemCfScorer::new(similarity).shrinkage(0.0)).similarities(1)
+    }
+
+    fn assert_similarities(similarity: Similarity, v2: f64, v3: f64) {
+        let similarities = similarities(similarity);
+        assert_eq!(similarities.len(), 2, "{}", similarity);
+        for (vn_id, expected) in [(2, v2), (3, v3)] {
+            assert!(
+                (similarities[&vn_id] - expected).abs() < 1e-12,
+                "{} of v1 and v{}: {} != {}",
+                similarity,
+                vn_id,
+                similarities[&vn_id],
+                expected
+            );
+        }
+    }
+
+    #[test]
+    fn cosine() {
+        // Over the whole vote vectors: v1 (80, 60, 70), v2 (60, 40, 80) and
+        // v3 (70, 50, 90)
+        let v1 = (80.0f64 * 80.0 + 60.0 * 60.0 + 70.0 * 70.0).sqrt();
+        let v2 = (60.0f64 * 60.0 + 40.0 * 40.0 + 80.0 * 80.0).sqrt();
+        let v3 = (70.0f64 * 70.0 + 50.0 * 50.0 + 90.0 * 90.0).sqrt();
+        assert_similarities(
+            Similarity::Cosine,
+            (80.0 * 60.0 + 60.0 * 40.0 + 70.0 * 80.0) / (v1 * v2),
+            (80.0 * 70.0 + 70.0 * 50.0) / (v1 * v3),
+        );
+    }
+
+    #[test]
+    fn adjusted_cosine() {
+        // User means 70, 50, 200/3 and 90 leave v1 (10, 10, 10/3), v2
+        // (-10, -10, 40/3) and v3 (0, -50/3, 0)
+        assert_similarities(
+            Similarity::AdjustedCosine,
+            -1400.0 / (1900.0f64 * 3400.0).sqrt(),
+            -500.0 / (1900.0f64 * 2500.0).sqrt(),
+        );
+    }
+
+    #[test]
+    fn pearson() {
+        // v1 (80, 60, 70) and v2 (60, 40, 80) deviate from their means 70
+        // and 60 by (10, -10, 0) and (0, -20, 20): 200 / sqrt(200 * 800).
+        // Over users 1 and 3, v1 (80, 70) and v3 (70, 50) deviate from 75
+        // and 60 by (5, -5) and (10, -10), perfectly correlated, though v3's
+        // mean over all its raters would be 70.
+        assert_similarities(Similarity::Pearson, 0.5, 1.0);
+    }
+
+    #[test]
+    fn jaccard() {
+        // Three raters in common of three and three, two of three and three
+        assert_similarities(Similarity::Jaccard, 1.0, 0.5);
+    }
+
+    #[test]
+    fn shrinkage() {
+        // Multiplied by n / (n + 2) for n = 3 and n = 2 common raters
+        let scorer = fitted(ItemCfScorer::new(Similarity::Jaccard).shrinkage(2.0));
+        assert_eq!(
+            scorer.similarities(1),
+            HashMap::from([(2, 3.0 / 5.0), (3, 0.5 * 2.0 / 4.0)])
+        );
+    }
+
+    #[test]
+    fn min_support() {
+        let scorer = |min_support| {
+            fitted(
+                ItemCfScorer::new(Similarity::Jaccard)
+                    .shrinkage(0.0)
+                    .min_support(min_support),
+            )
+        };
+        // v3 shares two raters with v1, v2 three
+        assert_eq!(scorer(3).similarities(1), HashMap::from([(2, 1.0)]));
+        assert!(scorer(4).similarities(1).is_empty());
+        // One common rater is always needed: v4 has none with any VN
+        assert_eq!(scorer(0).similarities(1).len(), 2);
+        assert_eq!(
+            scorer(0).similarities(3),
+            HashMap::from([(1, 0.5), (2, 0.5)])
+        );
+        assert!(scorer(0).similarities(4).is_empty());
+    }
+}
@@ -1,5 +1,42 @@
+pub mod als;
+pub mod config;
 mod data;
+pub mod ease;
+pub mod error;
+pub mod item_cf;
+pub mod mapped;
+pub mod parser;
+pub mod random_walk;
 pub mod recommender;
+pub mod scorer;
+mod scoring;
+pub mod snapshot;
+pub mod source;
+pub mod tag_catalog;
+pub mod tag_filter;
+pub mod tag_rules;
+pub mod tag_tree;
+#[cfg(test)]
+mod test_data;
+pub mod titles;
+
+pub use als::{Als, AlsScorer, Embeddings};
+pub use config::{QueryOptions, RecommenderConfig, TagWeighting};
+pub use data::{
+    Rating, Recommendation, RecommendationLists, Tag, TagCategory, TagInfo, TagParent, VnTitle,
+};
+pub use ease::EaseScorer;
+pub use error::RecommenderError;
+pub use item_cf::{ItemCfScorer, Similarity};
+pub use mapped::{MappedModel, MappedSnapshot};
+pub use random_walk::RandomWalkScorer;
+pub use scorer::{Scorer, TagScorer, VoteScorer};
+pub use source::{Compression, DataSource, DataSources};
+pub use tag_catalog::TagCatalog;
+pub use tag_filter::{TagCondition, TagFilter};
+pub use tag_rules::TagRule;
+pub use tag_tree::TagTree;
+pub use titles::TitleIndex;
 
 #[cfg(target_arch = "wasm32")]
 mod wasm;
@@ -1,88 +1,665 @@
 use clap::Parser;
-use visual_novel_recommendation_engine::recommender::VisualNovelRecommender;
+use std::{
+    fs::{self, File},
+    io::BufWriter,
+    path::{Path, PathBuf},
+    process::ExitCode,
+};
+use visual_novel_recommendation_engine::{
+    Als, AlsScorer, DataSource, DataSources, EaseScorer, ItemCfScorer, MappedSnapshot,
+    QueryOptions, RandomWalkScorer, Recommendation, RecommendationLists, RecommenderConfig,
+    RecommenderError, Similarity, TagCategory, TagCondition, TagFilter, TagRule, TagWeighting,
+    parser::{self, Parsed},
+    recommender::VisualNovelRecommender,
+    source::{find_data_file, latest_db_dump, latest_votes_file},
+};
 
 #[derive(Parser)]
 #[command(author, version, about)]
 struct Args {
-    #[arg(short, long, default_value_t = 0)]
-    vn_id: i32,
+    /// VN to get recommendations for, as ID or ID:WEIGHT; repeat to combine several
+    #[arg(short, long, value_parser = parse_seed)]
+    vn_id: Vec<(i32, f64)>,
 
-    #[arg(short, long, default_value_t = 25)]
-    num_recommendations: usize,
+    /// VNDB user (as u123 or 123) to get personal recommendations for from their votes
+    #[arg(short, long, value_parser = parse_user)]
+    user: Option<i32>,
 
-    #[arg(long, default_value_t = 1.5)]
-    tag_weight: f64,
+    /// Personal vote list to get recommendations for: VNDB's XML list export, or vn_id,vote CSV or JSON
+    #[arg(long, conflicts_with = "user")]
+    vote_list: Option<PathBuf>,
 
-    #[arg(long, default_value_t = 1.0)]
-    vote_weight: f64,
+    /// Top of the vote scale of --vote-list: 100 as in VNDB's XML and JSON exports, or 10 for 1-10 votes [default: 100]
+    #[arg(long, requires = "vote_list")]
+    vote_scale: Option<f64>,
+
+    /// VN to get fewer recommendations like, as ID or ID:WEIGHT; can be repeated
+    #[arg(long, value_parser = parse_seed)]
+    less_like: Vec<(i32, f64)>,
+
+    /// Multiplier for the weight of --less-like VNs [default: 1.0]
+    #[arg(long)]
+    negative_strength: Option<f64>,
+
+    #[arg(short, long)]
+    num_recommendations: Option<usize>,
+
+    #[arg(long)]
+    tag_weight: Option<f64>,
+
+    #[arg(long)]
+    vote_weight: Option<f64>,
+
+    /// Leave out tags a VN has at a higher spoiler level than this: 0 none, 1 minor, 2 major [default: 2]
+    #[arg(long, value_parser = clap::value_parser!(u8).range(0..=2), conflicts_with_all = ["snapshot", "mapped_snapshot"])]
+    max_tag_spoiler: Option<u8>,
+
+    /// Tag categories to leave out of the tag matrix (content, sexual, technical), using the tags table
+    #[arg(long, value_delimiter = ',', conflicts_with_all = ["snapshot", "mapped_snapshot"])]
+    ignore_tag_categories: Option<Vec<TagCategory>>,
+
+    /// Tag rule for this query, repeatable: NAME, gID, category:CATEGORY or tree:NAME (the tag and its descendants), with =WEIGHT to boost or dampen instead of ignoring
+    #[arg(long, conflicts_with = "mapped_snapshot")]
+    tag_rule: Vec<TagRule>,
+
+    /// Tag every recommendation must have, repeatable: NAME, gID or tree:NAME (the tag or any descendant), with >=SCORE for a minimum tag score
+    #[arg(long, conflicts_with = "mapped_snapshot")]
+    require_tag: Vec<TagCondition>,
+
+    /// Tag no recommendation may have, repeatable, in the form of --require-tag
+    #[arg(long, conflicts_with = "mapped_snapshot")]
+    exclude_tag: Vec<TagCondition>,
+
+    /// Fraction of a tag vote passed on to each parent tag, using the tags_parents table [default: 0]
+    #[arg(long, conflicts_with_all = ["snapshot", "mapped_snapshot"])]
+    tag_parent_decay: Option<f64>,
+
+    /// Weighting of the tag matrix: none, idf or bm25; rare tags count for more with the latter two
+    #[arg(long, conflicts_with_all = ["snapshot", "mapped_snapshot"])]
+    tag_weighting: Option<TagWeighting>,
+
+    /// Add item-item collaborative filtering to the combined recommendations,
+    /// with one of: cosine, adjusted-cosine, pearson, jaccard
+    #[arg(long, conflicts_with = "mapped_snapshot")]
+    item_cf: Option<Similarity>,
+
+    /// Weight of the item-item model in combined recommendations [default: 1.0]
+    #[arg(long)]
+    item_cf_weight: Option<f64>,
+
+    /// Shrinkage of item-item similarities towards zero for VNs with few common raters [default: 10]
+    #[arg(long)]
+    item_cf_shrinkage: Option<f64>,
+
+    /// Minimum number of common raters for the item-item model to compare two VNs [default: 2]
+    #[arg(long)]
+    item_cf_min_support: Option<usize>,
+
+    /// Add the EASE linear item-item model, trained on the votes, to the combined recommendations
+    #[arg(long, conflicts_with = "mapped_snapshot")]
+    ease: bool,
+
+    /// Weight of the EASE model in combined recommendations [default: 1.0]
+    #[arg(long)]
+    ease_weight: Option<f64>,
+
+    /// EASE regularization weight [default: 250]
+    #[arg(long)]
+    ease_regularization: Option<f64>,
+
+    /// Number of EASE weights kept per VN [default: 100]
+    #[arg(long)]
+    ease_top_k: Option<usize>,
+
+    /// Number of most-voted VNs the EASE model covers [default: 2000]
+    #[arg(long)]
+    ease_max_vns: Option<usize>,
+
+    /// Add random walks with restart from the seed VNs over the VN-user-tag graph to the combined recommendations
+    #[arg(long, conflicts_with = "mapped_snapshot")]
+    random_walk: bool,
+
+    /// Weight of the random walk model in combined recommendations [default: 1.0]
+    #[arg(long)]
+    random_walk_weight: Option<f64>,
+
+    /// Total number of steps walked per query [default: 100000]
+    #[arg(long)]
+    walk_length: Option<usize>,
+
+    /// Chance of jumping back to the seed VN after each step [default: 0.5]
+    #[arg(long)]
+    walk_restart: Option<f64>,
+
+    /// Relative chance of leaving a VN through its voters [default: 1.0]
+    #[arg(long)]
+    walk_vote_weight: Option<f64>,
+
+    /// Relative chance of leaving a VN through its tags [default: 1.0]
+    #[arg(long)]
+    walk_tag_weight: Option<f64>,
+
+    /// Follow votes and tags uniformly instead of in proportion to the vote and tag score
+    #[arg(long)]
+    walk_unweighted: bool,
+
+    /// Train ALS matrix factorization embeddings on the votes; they are saved with --save-snapshot
+    #[arg(long, conflicts_with = "mapped_snapshot")]
+    train_als: bool,
+
+    /// Number of ALS factors per VN and user [default: 32]
+    #[arg(long)]
+    als_factors: Option<usize>,
+
+    /// Number of ALS training iterations [default: 10]
+    #[arg(long)]
+    als_iterations: Option<usize>,
+
+    /// ALS regularization weight [default: 0.1]
+    #[arg(long)]
+    als_regularization: Option<f64>,
+
+    /// Train ALS on implicit feedback, with votes as confidence, instead of the vote values
+    #[arg(long)]
+    als_implicit: bool,
+
+    /// Confidence scale of implicit ALS [default: 40]
+    #[arg(long)]
+    als_alpha: Option<f64>,
+
+    /// Weight of the ALS model in combined recommendations when the model has embeddings [default: 1.0]
+    #[arg(long)]
+    als_weight: Option<f64>,
+
+    /// Languages to show titles in, most preferred first (e.g. zh-Hans,en,ja)
+    #[arg(long, value_delimiter = ',')]
+    title_languages: Option<Vec<String>>,
+
+    /// Show titles in their original script instead of romanized
+    #[arg(long)]
+    native_titles: bool,
+
+    /// TOML or JSON config file; command line options override its values
+    #[arg(short, long)]
+    config: Option<PathBuf>,
+
+    /// Print the effective config as TOML and exit
+    #[arg(long)]
+    print_config: bool,
+
+    /// Directory holding vn_titles, tags_vn and vndb-votes-* [default: data]
+    #[arg(long)]
+    data_dir: Option<PathBuf>,
+
+    /// Titles file, overriding <data-dir>/vn_titles
+    #[arg(long)]
+    titles: Option<PathBuf>,
+
+    /// Votes file, overriding the latest <data-dir>/vndb-votes-*
+    #[arg(long)]
+    votes: Option<PathBuf>,
+
+    /// Tags file, overriding <data-dir>/tags_vn
+    #[arg(long)]
+    tags: Option<PathBuf>,
+
+    /// VNDB database dump (vndb-db-*.tar.zst) to read titles and tags from
+    #[arg(long)]
+    db_dump: Option<PathBuf>,
+
+    /// Load the model from a snapshot instead of the data files
+    #[arg(long)]
+    snapshot: Option<PathBuf>,
+
+    /// Save the loaded model as a snapshot; --vn-id is optional with this
+    #[arg(long)]
+    save_snapshot: Option<PathBuf>,
+
+    /// Query a memory-mapped snapshot in place instead of loading a model
+    #[arg(long, conflicts_with = "snapshot")]
+    mapped_snapshot: Option<PathBuf>,
+
+    /// Save the loaded model as a memory-mapped snapshot; --vn-id is optional with this
+    #[arg(long)]
+    save_mapped_snapshot: Option<PathBuf>,
+}
+
+fn build_config(args: &Args) -> Result<RecommenderConfig, RecommenderError> {
+    // Without a config file the CLI keeps printing loading progress
+    let mut config = match &args.config {
+        Some(path) => RecommenderConfig::from_file(path)?,
+        None => RecommenderConfig::builder().verbose(true).build(),
+    };
+    if let Some(max_tag_spoiler) = args.max_tag_spoiler {
+        config.max_tag_spoiler = max_tag_spoiler;
+    }
+    if let Some(categories) = &args.ignore_tag_categories {
+        config.ignore_tag_categories = categories.clone();
+    }
+    if let Some(tag_parent_decay) = args.tag_parent_decay {
+        config.tag_parent_decay = tag_parent_decay;
+    }
+    // Snapshots keep the weighting their tag matrix was built with
+    if let Some(tag_weighting) = args.tag_weighting {
+        config.tag_weighting = tag_weighting;
+    }
+    apply_overrides(args, &mut config);
+
+    Ok(config)
+}
+
+// Apply the query options given on the command line
+fn apply_overrides(args: &Args, config: &mut RecommenderConfig) {
+    if let Some(num_recommendations) = args.num_recommendations {
+        config.num_vns = num_recommendations;
+    }
+    if let Some(tag_weight) = args.tag_weight {
+        config.tag_weight = tag_weight;
+    }
+    if let Some(vote_weight) = args.vote_weight {
+        config.vote_weight = vote_weight;
+    }
+    if let Some(negative_strength) = args.negative_strength {
+        config.negative_seed_strength = negative_strength;
+    }
+    if let Some(title_languages) = &args.title_languages {
+        config.title_languages = title_languages.clone();
+    }
+    if args.native_titles {
+        config.romanized_titles = false;
+    }
+}
+
+fn build_sources(
+    args: &Args,
+    config: &RecommenderConfig,
+) -> Result<DataSources<'static>, RecommenderError> {
+    let data_dir = args.data_dir.as_deref().unwrap_or(Path::new("data"));
+
+    // Titles come from --titles, <data-dir>/vn_titles[.gz|.zst], or failing
+    // that from a database dump tarball
+    let mut titles = args
+        .titles
+        .clone()
+        .or_else(|| find_data_file(data_dir, "vn_titles"));
+    let mut sources = match &args.db_dump {
+        Some(path) => DataSources::from_db_dump(DataSource::path(path)),
+        None => match titles.take() {
+            Some(titles) => DataSources::new(DataSource::path(titles)),
+            None => DataSources::from_db_dump(DataSource::path(latest_db_dump(data_dir)?)),
+        },
+    };
+    if let Some(titles) = titles {
+        sources = sources.titles(DataSource::path(titles));
+    }
+
+    // Votes and tags are not read at all when recommendations are skipped
+    if !config.skip_recs {
+        let votes = match &args.votes {
+            Some(path) => path.clone(),
+            None => latest_votes_file(data_dir)?,
+        };
+        sources = sources.votes(DataSource::path(votes));
+
+        let tags = args
+            .tags
+            .clone()
+            .or_else(|| find_data_file(data_dir, "tags_vn"));
+        match tags {
+            Some(tags) => sources = sources.tags(DataSource::path(tags)),
+            // Without a tarball to fall back on, report the missing file
+            None if sources.db_dump.is_none() => {
+                sources = sources.tags(DataSource::path(data_dir.join("tags_vn")))
+            }
+            None => {}
+        }
+
+        // The tag catalog and hierarchy are optional outside of a tarball
+        if let Some(tag_info) = find_data_file(data_dir, "tags") {
+            sources = sources.tag_info(DataSource::path(tag_info));
+        }
+        if let Some(tag_parents) = find_data_file(data_dir, "tags_parents") {
+            sources = sources.tag_parents(DataSource::path(tag_parents));
+        }
+    }
+
+    Ok(sources)
 }
 
-fn main() {
+fn main() -> ExitCode {
     let args = Args::parse();
 
-    if args.vn_id <= 0 {
-        println!("Please provide a valid VN ID with --vn-id");
-        return;
+    if args.print_config {
+        return match build_config(&args).and_then(|config| config.to_toml_string()) {
+            Ok(toml) => {
+                print!("{}", toml);
+                ExitCode::SUCCESS
+            }
+            Err(e) => {
+                eprintln!("Error: {}", e);
+                ExitCode::FAILURE
+            }
+        };
+    }
+
+    if args.vn_id.is_empty()
+        && args.user.is_none()
+        && args.vote_list.is_none()
+        && args.save_snapshot.is_none()
+        && args.save_mapped_snapshot.is_none()
+    {
+        println!(
+            "Please provide a valid VN ID with --vn-id, a user ID with --user or a --vote-list"
+        );
+        return ExitCode::FAILURE;
+    }
+
+    match run(&args) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            ExitCode::FAILURE
+        }
     }
+}
 
-    // Default ignored tags
-    let ignore_tags = vec![32, 2040, 2461, 1434, 1431, 43];
+fn run(args: &Args) -> Result<(), RecommenderError> {
+    if let Some(path) = &args.mapped_snapshot {
+        return run_mapped(args, path);
+    }
 
     // Initialize recommender
-    let recommender = VisualNovelRecommender::new(
-        args.num_recommendations,
-        args.tag_weight,
-        args.vote_weight,
-        2.0, // tag_exp
-        1.0, // vote_exp
-        ignore_tags,
-        true,  // verbose
-        false, // skip_recs
+    let mut recommender = match &args.snapshot {
+        Some(path) => {
+            let mut recommender = VisualNovelRecommender::load_snapshot(DataSource::path(path))?;
+            apply_overrides(args, &mut recommender.config);
+            recommender
+        }
+        None => {
+            let config = build_config(args)?;
+            let sources = build_sources(args, &config)?;
+            VisualNovelRecommender::from_sources(config, sources)?
+        }
+    };
+
+    if args.train_als {
+        recommender.train_als(&build_als(args))?;
+    }
+
+    if let Some(path) = &args.save_snapshot {
+        save_file(path, |writer| recommender.save_snapshot(writer))?;
+        println!("Saved snapshot to {}", path.display());
+    }
+
+    if let Some(path) = &args.save_mapped_snapshot {
+        save_file(path, |writer| recommender.save_mapped_snapshot(writer))?;
+        println!("Saved memory-mapped snapshot to {}", path.display());
+    }
+
+    if args.vn_id.is_empty() && args.user.is_none() && args.vote_list.is_none() {
+        return Ok(());
+    }
+
+    if let Some(similarity) = args.item_cf {
+        let mut scorer = ItemCfScorer::new(similarity);
+        if let Some(shrinkage) = args.item_cf_shrinkage {
+            scorer = scorer.shrinkage(shrinkage);
+        }
+        if let Some(min_support) = args.item_cf_min_support {
+            scorer = scorer.min_support(min_support);
+        }
+        recommender.add_scorer(Box::new(scorer), args.item_cf_weight.unwrap_or(1.0))?;
+    }
+    if args.ease {
+        let mut scorer = EaseScorer::new();
+        if let Some(regularization) = args.ease_regularization {
+            scorer = scorer.regularization(regularization);
+        }
+        if let Some(top_k) = args.ease_top_k {
+            scorer = scorer.top_k(top_k);
+        }
+        if let Some(max_vns) = args.ease_max_vns {
+            scorer = scorer.max_vns(max_vns);
+        }
+        recommender.add_scorer(Box::new(scorer), args.ease_weight.unwrap_or(1.0))?;
+    }
+    if args.random_walk {
+        let mut scorer = RandomWalkScorer::new().weighted_edges(!args.walk_unweighted);
+        if let Some(walk_length) = args.walk_length {
+            scorer = scorer.walk_length(walk_length);
+        }
+        if let Some(restart) = args.walk_restart {
+            scorer = scorer.restart_probability(restart);
+        }
+        if let Some(vote_weight) = args.walk_vote_weight {
+            scorer = scorer.vote_weight(vote_weight);
+        }
+        if let Some(tag_weight) = args.walk_tag_weight {
+            scorer = scorer.tag_weight(tag_weight);
+        }
+        recommender.add_scorer(Box::new(scorer), args.random_walk_weight.unwrap_or(1.0))?;
+    }
+    if recommender.embeddings.is_some() {
+        recommender.add_scorer(Box::new(AlsScorer), args.als_weight.unwrap_or(1.0))?;
+    }
+
+    // Get recommendations
+    let mut seeds = match (args.user, &args.vote_list) {
+        (Some(user_id), _) => recommender.user_profile(user_id)?,
+        (None, Some(path)) => recommender
+            .vote_list_profile(&read_vote_list(path)?, args.vote_scale.unwrap_or(100.0))?,
+        (None, None) => Vec::new(),
+    };
+    seeds.extend(query_seeds(args));
+    let options = QueryOptions {
+        tag_rules: args.tag_rule.clone(),
+        tag_filter: TagFilter {
+            required: args.require_tag.clone(),
+            forbidden: args.exclude_tag.clone(),
+        },
+    };
+    let recommendations = recommender.recommend_for_seeds_with(&seeds, &options)?;
+
+    print_recommendations(
+        args,
+        |vn_id| recommender.get_title(vn_id).to_string(),
+        &recommendations,
     );
 
+    // With embeddings a user also gets the VNs their own factors predict
+    if let Some(user_id) = args.user
+        && recommender.embeddings.is_some()
+    {
+        println!("--------------------------------------------------");
+        println!("ALS Recommendations for u{}:", user_id);
+        print_list(
+            |vn_id| recommender.get_title(vn_id).to_string(),
+            &recommender.get_als_user_recommendations_with(user_id, &options)?,
+        );
+    }
+
+    Ok(())
+}
+
+// Write a file next to `path` and rename it over `path` once it is complete,
+// so that processes that have the old file mapped keep reading the old data
+// instead of a truncated file
+fn save_file(
+    path: &Path,
+    write: impl FnOnce(&mut BufWriter<File>) -> Result<(), RecommenderError>,
+) -> Result<(), RecommenderError> {
+    let file_name = path.file_name().unwrap_or_default().to_string_lossy();
+    let temp_path = path.with_file_name(format!(".{}.{}.tmp", file_name, std::process::id()));
+
+    let result = File::create(&temp_path)
+        .map_err(|e| RecommenderError::io(path, e))
+        .and_then(|file| {
+            let mut writer = BufWriter::new(file);
+            write(&mut writer)?;
+            let file = writer
+                .into_inner()
+                .map_err(|e| RecommenderError::io(&temp_path, e.into_error()))?;
+            file.sync_all()
+                .map_err(|e| RecommenderError::io(&temp_path, e))
+        })
+        .and_then(|_| fs::rename(&temp_path, path).map_err(|e| RecommenderError::io(path, e)));
+    if result.is_err() {
+        let _ = fs::remove_file(&temp_path);
+    }
+    result
+}
+
+// Query a memory-mapped snapshot in place, without loading the model
+fn run_mapped(args: &Args, path: &Path) -> Result<(), RecommenderError> {
+    let snapshot = MappedSnapshot::open(path)?;
+    let mut model = snapshot.model()?;
+    apply_overrides(args, &mut model.config);
+
     // Get recommendations
-    let combined_recommendations = recommender.get_combined_recommendations(args.vn_id);
-    let tag_recommendations = recommender.get_tag_recommendations(args.vn_id);
-    let user_recommendations = recommender.get_user_recommendations(args.vn_id);
+    let mut seeds = match (args.user, &args.vote_list) {
+        (Some(user_id), _) => model.user_profile(user_id)?,
+        (None, Some(path)) => {
+            model.vote_list_profile(&read_vote_list(path)?, args.vote_scale.unwrap_or(100.0))?
+        }
+        (None, None) => Vec::new(),
+    };
+    seeds.extend(query_seeds(args));
+    let recommendations = model.recommend_for_seeds(&seeds)?;
 
-    // Display results
-    println!(
-        "Recommendations for {}: {}",
-        args.vn_id,
-        recommender.get_title(args.vn_id)
+    print_recommendations(
+        args,
+        |vn_id| model.get_title(vn_id).into_owned(),
+        &recommendations,
     );
+
+    Ok(())
+}
+
+// ALS training parameters from the command line
+fn build_als(args: &Args) -> Als {
+    let defaults = Als::default();
+    Als {
+        factors: args.als_factors.unwrap_or(defaults.factors),
+        iterations: args.als_iterations.unwrap_or(defaults.iterations),
+        regularization: args.als_regularization.unwrap_or(defaults.regularization),
+        implicit: args.als_implicit,
+        alpha: args.als_alpha.unwrap_or(defaults.alpha),
+        ..defaults
+    }
+}
+
+// Read the (vn_id, vote) pairs of a --vote-list file
+fn read_vote_list(path: &Path) -> Result<Vec<(i32, f64)>, RecommenderError> {
+    let (path, reader) = DataSource::path(path).open("vote list")?;
+    let Parsed { records, report } = parser::parse_vote_list(reader, &path)?;
+    if report.total_skipped() > 0 {
+        println!("{}", report);
+    }
+    Ok(records)
+}
+
+// The --vn-id seeds followed by the --less-like seeds with negative weights
+fn query_seeds(args: &Args) -> Vec<(i32, f64)> {
+    let less_like = args
+        .less_like
+        .iter()
+        .map(|(vn_id, weight)| (*vn_id, -weight.abs()));
+    args.vn_id.iter().copied().chain(less_like).collect()
+}
+
+// Parse a --vn-id value: `17`, `v17` or with a weight `17:2.5`
+fn parse_seed(value: &str) -> Result<(i32, f64), String> {
+    let (id, weight) = match value.split_once(':') {
+        Some((id, weight)) => {
+            let weight = weight
+                .parse()
+                .map_err(|_| format!("invalid weight '{}'", weight))?;
+            (id, weight)
+        }
+        None => (value, 1.0),
+    };
+    match id.strip_prefix('v').unwrap_or(id).parse() {
+        Ok(id) if id > 0 => Ok((id, weight)),
+        _ => Err(format!("invalid VN id '{}'", id)),
+    }
+}
+
+// Parse a --user value: `123` or `u123`
+fn parse_user(value: &str) -> Result<i32, String> {
+    match value.strip_prefix('u').unwrap_or(value).parse() {
+        Ok(id) if id > 0 => Ok(id),
+        _ => Err(format!("invalid user id '{}'", value)),
+    }
+}
+
+fn print_recommendations(
+    args: &Args,
+    title: impl Fn(i32) -> String,
+    recommendations: &RecommendationLists,
+) {
+    // Display results
+    let seed_titles = |seeds: &[(i32, f64)]| {
+        seeds
+            .iter()
+            .map(|(vn_id, _)| format!("{}: {}", vn_id, title(*vn_id)))
+            .collect::<Vec<String>>()
+    };
+    let mut query = seed_titles(&args.vn_id);
+    if let Some(user_id) = args.user {
+        query.insert(0, format!("u{}", user_id));
+    }
+    if let Some(path) = &args.vote_list {
+        query.insert(0, path.display().to_string());
+    }
+    println!("Recommendations for {}", query.join(", "));
+    if !args.less_like.is_empty() {
+        println!("Less like {}", seed_titles(&args.less_like).join(", "));
+    }
+    if !args.tag_rule.is_empty() {
+        let rules: Vec<String> = args.tag_rule.iter().map(TagRule::to_string).collect();
+        println!("Tag rules: {}", rules.join(", "));
+    }
+    let conditions = |conditions: &[TagCondition]| {
+        conditions
+            .iter()
+            .map(TagCondition::to_string)
+            .collect::<Vec<String>>()
+            .join(", ")
+    };
+    if !args.require_tag.is_empty() {
+        println!("Required tags: {}", conditions(&args.require_tag));
+    }
+    if !args.exclude_tag.is_empty() {
+        println!("Excluded tags: {}", conditions(&args.exclude_tag));
+    }
     println!("--------------------------------------------------");
-    println!("Tag Recommendations:");
-    for (i, vn_id) in tag_recommendations.iter().enumerate() {
-        println!(
-            "{}. {} (ID: {}) - https://vndb.org/v{}",
-            i + 1,
-            recommender.get_title(*vn_id),
-            vn_id,
-            vn_id
-        );
+    match recommendations.tag_weighting {
+        TagWeighting::None => println!("Tag Recommendations:"),
+        weighting => println!("Tag Recommendations ({} weighting):", weighting),
     }
+    print_list(&title, &recommendations.tag);
     println!("--------------------------------------------------");
     println!("Vote Recommendations:");
-    for (i, vn_id) in user_recommendations.iter().enumerate() {
-        println!(
-            "{}. {} (ID: {}) - https://vndb.org/v{}",
-            i + 1,
-            recommender.get_title(*vn_id),
-            vn_id,
-            vn_id
-        );
-    }
+    print_list(&title, &recommendations.vote);
     println!("--------------------------------------------------");
     println!("Combined Recommendations:");
+    print_list(&title, &recommendations.combined);
+    for (name, list) in &recommendations.extra {
+        println!("--------------------------------------------------");
+        println!("Recommendations by {}:", name);
+        print_list(&title, list);
+    }
+}
 
-    for (i, vn_id) in combined_recommendations.iter().enumerate() {
+fn print_list(title: impl Fn(i32) -> String, recommendations: &[Recommendation]) {
+    for rec in recommendations {
         println!(
-            "{}. {} (ID: {}) - https://vndb.org/v{}",
-            i + 1,
-            recommender.get_title(*vn_id),
-            vn_id,
-            vn_id
+            "{}. {} (ID: {}, score: {:.3}) - https://vndb.org/v{}",
+            rec.rank,
+            title(rec.vn_id),
+            rec.vn_id,
+            rec.score,
+            rec.vn_id
         );
     }
 }
@@ -0,0 +1,777 @@
+// Read-only snapshot layout that is memory-mapped and queried in place, so a
+// CLI run or a server worker starts without deserializing anything and
+// several processes share one copy of the model in the page cache.
+//
+// Layout (all integers little-endian, sections 8-byte aligned):
+//   magic     4 bytes  "VNRM"
+//   version   u32
+//   sections  u32      number of entries in the section table
+//   reserved  u32
+//   table              (offset u64, length u64) per section, see Section
+//   sections           raw arrays
+//
+// The sparse matrices are stored as CSR arrays (indptr u64, indices u32,
+// data f64). Titles are grouped by VN id and refer to an interned string
+// table, so each distinct language code or title is stored once.
+use crate::config::RecommenderConfig;
+use crate::data::{Recommendation, RecommendationLists};
+use crate::error::{RecommenderError, Result};
+use crate::recommender::VisualNovelRecommender;
+use crate::scoring;
+use crate::titles::{self, TitleRef};
+use bytemuck::Pod;
+use memmap2::Mmap;
+use sprs::{CsMat, CsMatViewI};
+use std::{
+    borrow::Cow,
+    collections::{BTreeMap, HashMap},
+    fs::File,
+    io::Write,
+    path::{Path, PathBuf},
+};
+
+pub const MAPPED_MAGIC: [u8; 4] = *b"VNRM";
+pub const MAPPED_VERSION: u32 = 1;
+
+const HEADER_LEN: usize = 16;
+const ALIGN: usize = 8;
+// Marks a title without a latin form
+const NO_STRING: u32 = u32::MAX;
+
+#[derive(Debug, Clone, Copy)]
+enum Section {
+    // [tag rows, tag columns, VN rows, user rows] of the matrices
+    Shapes,
+    // RecommenderConfig as JSON
+    Config,
+    TagIndptr,
+    TagIndices,
+    TagData,
+    // The tag matrix in CSC order, i.e. the VNs of each tag
+    TagVnIndptr,
+    TagVnIndices,
+    TagVnData,
+    // Norm of each row of the tag matrix
+    TagNorms,
+    VoteIndptr,
+    VoteIndices,
+    VoteData,
+    UserIndptr,
+    UserIndices,
+    UserData,
+    // Average rating indexed by VN id, NaN for VNs without votes
+    AverageRatings,
+    // Start of each VN's titles in Titles, indexed by VN id
+    TitleIndptr,
+    // [language, official, title, latin title] per title, as string ids
+    Titles,
+    StringOffsets,
+    Strings,
+}
+
+const SECTION_COUNT: usize = Section::Strings as usize + 1;
+
+fn invalid(reason: impl Into<String>) -> RecommenderError {
+    RecommenderError::InvalidSnapshot(reason.into())
+}
+
+fn check_endianness() -> Result<()> {
+    if cfg!(target_endian = "big") {
+        return Err(invalid(
+            "memory-mapped snapshots require a little-endian host",
+        ));
+    }
+    Ok(())
+}
+
+// Split an owned CSR or CSC matrix into the arrays stored in the file
+fn csr_arrays(matrix: &CsMat<f64>) -> (Vec<u64>, Vec<u32>, Vec<f64>) {
+    (
+        matrix
+            .indptr()
+            .to_proper()
+            .iter()
+            .map(|&i| i as u64)
+            .collect(),
+        matrix.indices().iter().map(|&i| i as u32).collect(),
+        matrix.data().to_vec(),
+    )
+}
+
+// Interns strings into one blob with an offset table
+struct StringTable<'s> {
+    ids: HashMap<&'s str, u32>,
+    offsets: Vec<u64>,
+    blob: Vec<u8>,
+}
+
+impl<'s> StringTable<'s> {
+    fn new() -> Self {
+        Self {
+            ids: HashMap::new(),
+            offsets: vec![0],
+            blob: Vec::new(),
+        }
+    }
+
+    fn intern(&mut self, s: &'s str) -> u32 {
+        if let Some(id) = self.ids.get(s) {
+            return *id;
+        }
+        let id = self.ids.len() as u32;
+        self.blob.extend_from_slice(s.as_bytes());
+        self.offsets.push(self.blob.len() as u64);
+        self.ids.insert(s, id);
+        id
+    }
+}
+
+impl VisualNovelRecommender {
+    // Write the model in the memory-mappable layout read by MappedSnapshot
+    pub fn save_mapped_snapshot(&self, mut writer: impl Write) -> Result<()> {
+        check_endianness()?;
+
+        // Tag matrix and its inverted index
+        let (tag_rows, tag_cols, (tag_indptr, tag_indices, tag_data)) =
+            match &self.similarity_matrix {
+                Some(matrix) => (matrix.rows(), matrix.cols(), csr_arrays(matrix)),
+                None => (0, 0, (vec![0], Vec::new(), Vec::new())),
+            };
+        let (tag_vn_indptr, tag_vn_indices, tag_vn_data) = match &self.tag_index {
+            Some(index) => csr_arrays(index),
+            None => (vec![0; tag_cols + 1], Vec::new(), Vec::new()),
+        };
+
+        // Ratings as VN x user, and user x VN for the user side
+        let (vn_rows, user_rows, (vote_indptr, vote_indices, vote_data)) = match &self.vn_users {
+            Some(matrix) => (matrix.rows(), matrix.cols(), csr_arrays(matrix)),
+            None => (0, 0, (vec![0], Vec::new(), Vec::new())),
+        };
+        // The CSC arrays of VN x user are the CSR arrays of user x VN
+        let (user_indptr, user_indices, user_data) = match &self.user_vns {
+            Some(matrix) => csr_arrays(matrix),
+            None => (vec![0; user_rows + 1], Vec::new(), Vec::new()),
+        };
+
+        // Dense average ratings
+        let average_len = self
+            .average_ratings
+            .keys()
+            .filter(|id| **id >= 0)
+            .map(|id| *id as usize + 1)
+            .max()
+            .unwrap_or(0);
+        let mut average_ratings = vec![f64::NAN; average_len];
+        for (vn_id, rating) in &self.average_ratings {
+            if *vn_id >= 0 {
+                average_ratings[*vn_id as usize] = *rating;
+            }
+        }
+
+        // Titles grouped by VN id, keeping their order within a VN
+        let titles: Vec<_> = self.titles.iter().filter(|t| t.vn_id >= 0).collect();
+        let title_rows = titles.last().map_or(0, |t| t.vn_id as usize + 1);
+        let mut title_indptr = vec![0u64; title_rows + 1];
+        let mut strings = StringTable::new();
+        let mut title_records: Vec<[u32; 4]> = Vec::with_capacity(titles.len());
+        for title in &titles {
+            title_indptr[title.vn_id as usize + 1] += 1;
+            title_records.push([
+                strings.intern(&title.language),
+                title.official as u32,
+                strings.intern(&title.title),
+                title
+                    .latin_title
+                    .as_deref()
+                    .map_or(NO_STRING, |latin| strings.intern(latin)),
+            ]);
+        }
+        for i in 1..title_indptr.len() {
+            title_indptr[i] += title_indptr[i - 1];
+        }
+
+        let shapes = [
+            tag_rows as u64,
+            tag_cols as u64,
+            vn_rows as u64,
+            user_rows as u64,
+        ];
+        let config = serde_json::to_vec(&self.config).map_err(|e| invalid(e.to_string()))?;
+
+        let sections: [&[u8]; SECTION_COUNT] = [
+            bytemuck::cast_slice(&shapes),
+            &config,
+            bytemuck::cast_slice(&tag_indptr),
+            bytemuck::cast_slice(&tag_indices),
+            bytemuck::cast_slice(&tag_data),
+            bytemuck::cast_slice(&tag_vn_indptr),
+            bytemuck::cast_slice(&tag_vn_indices),
+            bytemuck::cast_slice(&tag_vn_data),
+            bytemuck::cast_slice(&self.tag_norms),
+            bytemuck::cast_slice(&vote_indptr),
+            bytemuck::cast_slice(&vote_indices),
+            bytemuck::cast_slice(&vote_data),
+            bytemuck::cast_slice(&user_indptr),
+            bytemuck::cast_slice(&user_indices),
+            bytemuck::cast_slice(&user_data),
+            bytemuck::cast_slice(&average_ratings),
+            bytemuck::cast_slice(&title_indptr),
+            bytemuck::cast_slice(&title_records),
+            bytemuck::cast_slice(&strings.offsets),
+            &strings.blob,
+        ];
+
+        // Header and section table, then the sections at aligned offsets
+        let mut header = Vec::new();
+        header.extend_from_slice(&MAPPED_MAGIC);
+        header.extend_from_slice(&MAPPED_VERSION.to_le_bytes());
+        header.extend_from_slice(&(SECTION_COUNT as u32).to_le_bytes());
+        header.extend_from_slice(&0u32.to_le_bytes());
+        let mut offset = HEADER_LEN + SECTION_COUNT * 16;
+        for section in &sections {
+            offset = offset.next_multiple_of(ALIGN);
+            header.extend_from_slice(&(offset as u64).to_le_bytes());
+            header.extend_from_slice(&(section.len() as u64).to_le_bytes());
+            offset += section.len();
+        }
+
+        let write = |writer: &mut dyn Write| -> std::io::Result<()> {
+            writer.write_all(&header)?;
+            let mut written = header.len();
+            for section in &sections {
+                let padding = written.next_multiple_of(ALIGN) - written;
+                writer.write_all(&[0u8; ALIGN][..padding])?;
+                writer.write_all(section)?;
+                written += padding + section.len();
+            }
+            writer.flush()
+        };
+        write(&mut writer).map_err(|e| RecommenderError::io("snapshot", e))
+    }
+}
+
+// A memory-mapped snapshot file. Queries go through the MappedModel returned
+// by `model`, which borrows the mapped arrays.
+pub struct MappedSnapshot {
+    path: PathBuf,
+    mmap: Mmap,
+}
+
+impl MappedSnapshot {
+    pub fn open(path: impl AsRef<Path>) -> Result<Self> {
+        check_endianness()?;
+
+        let path = path.as_ref().to_path_buf();
+        let file = File::open(&path).map_err(|e| RecommenderError::io(&path, e))?;
+        // Safety: the file is only read, and a snapshot is never rewritten in
+        // place; writers create a new file and rename it over the old one
+        let mmap = unsafe { Mmap::map(&file) }.map_err(|e| RecommenderError::io(&path, e))?;
+
+        if mmap.len() < HEADER_LEN + SECTION_COUNT * 16 {
+            return Err(invalid("file is too short to be a memory-mapped snapshot"));
+        }
+        if mmap[0..4] != MAPPED_MAGIC {
+            return Err(invalid("not a memory-mapped snapshot file"));
+        }
+        let version = u32::from_le_bytes(mmap[4..8].try_into().unwrap());
+        if version != MAPPED_VERSION {
+            return Err(invalid(format!(
+                "unsupported memory-mapped snapshot version {} (expected {})",
+                version, MAPPED_VERSION
+            )));
+        }
+        let sections = u32::from_le_bytes(mmap[8..12].try_into().unwrap());
+        if sections as usize != SECTION_COUNT {
+            return Err(invalid(format!(
+                "expected {} sections, found {}",
+                SECTION_COUNT, sections
+            )));
+        }
+
+        Ok(Self { path, mmap })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    fn section<T: Pod>(&self, section: Section) -> Result<&[T]> {
+        let entry = HEADER_LEN + section as usize * 16;
+        let offset = u64::from_le_bytes(self.mmap[entry..entry + 8].try_into().unwrap());
+        let len = u64::from_le_bytes(self.mmap[entry + 8..entry + 16].try_into().unwrap());
+        let bytes = offset
+            .checked_add(len)
+            .and_then(|end| self.mmap.get(offset as usize..end as usize))
+            .ok_or_else(|| invalid(format!("{:?} section is out of bounds", section)))?;
+        bytemuck::try_cast_slice(bytes)
+            .map_err(|e| invalid(format!("{:?} section: {:?}", section, e)))
+    }
+
+    fn csr<'a>(
+        &'a self,
+        shape: (usize, usize),
+        sections: [Section; 3],
+    ) -> Result<CsMatViewI<'a, f64, u32, u64>> {
+        let [indptr, indices, data] = sections;
+        CsMatViewI::try_new(
+            shape,
+            self.section(indptr)?,
+            self.section(indices)?,
+            self.section(data)?,
+        )
+        .map_err(|(.., e)| invalid(format!("{:?}: {}", indptr, e)))
+    }
+
+    // Validate the arrays once and return a model that queries them in place
+    pub fn model(&self) -> Result<MappedModel<'_>> {
+        let shapes: &[u64] = self.section(Section::Shapes)?;
+        let [tag_rows, tag_cols, vn_rows, user_rows] = *shapes else {
+            return Err(invalid("Shapes section has the wrong length"));
+        };
+        let (tag_rows, tag_cols) = (tag_rows as usize, tag_cols as usize);
+        let (vn_rows, user_rows) = (vn_rows as usize, user_rows as usize);
+
+        let config = serde_json::from_slice(self.section(Section::Config)?)
+            .map_err(|e| invalid(format!("config: {}", e)))?;
+
+        let tag_matrix = self.csr(
+            (tag_rows, tag_cols),
+            [Section::TagIndptr, Section::TagIndices, Section::TagData],
+        )?;
+        // Stored as the CSR arrays of the tag x VN matrix
+        let tag_index = self.csr(
+            (tag_cols, tag_rows),
+            [
+                Section::TagVnIndptr,
+                Section::TagVnIndices,
+                Section::TagVnData,
+            ],
+        )?;
+        let tag_norms: &[f64] = self.section(Section::TagNorms)?;
+        if tag_norms.len() != tag_rows {
+            return Err(invalid("TagNorms section has the wrong length"));
+        }
+        let vn_users = self.csr(
+            (vn_rows, user_rows),
+            [Section::VoteIndptr, Section::VoteIndices, Section::VoteData],
+        )?;
+        let user_vns = self.csr(
+            (user_rows, vn_rows),
+            [Section::UserIndptr, Section::UserIndices, Section::UserData],
+        )?;
+
+        // Strings must split the blob at character boundaries
+        let strings = std::str::from_utf8(self.section(Section::Strings)?)
+            .map_err(|e| invalid(format!("Strings section: {}", e)))?;
+        let string_offsets: &[u64] = self.section(Section::StringOffsets)?;
+        if string_offsets.first() != Some(&0)
+            || string_offsets.windows(2).any(|w| w[0] > w[1])
+            || string_offsets
+                .iter()
+                .any(|&o| !strings.is_char_boundary(o as usize))
+        {
+            return Err(invalid("StringOffsets section is inconsistent"));
+        }
+
+        let title_indptr: &[u64] = self.section(Section::TitleIndptr)?;
+        let titles: &[[u32; 4]] = self.section(Section::Titles)?;
+        let string_count = string_offsets.len() as u32 - 1;
+        if title_indptr.first().is_some_and(|&start| start != 0)
+            || title_indptr.windows(2).any(|w| w[0] > w[1])
+            || title_indptr.last().map_or(0, |&end| end as usize) != titles.len()
+            || titles.iter().any(|[language, _, title, latin]| {
+                *language >= string_count
+                    || *title >= string_count
+                    || (*latin != NO_STRING && *latin >= string_count)
+            })
+        {
+            return Err(invalid("Titles section is inconsistent"));
+        }
+
+        Ok(MappedModel {
+            config,
+            tag_matrix,
+            tag_index,
+            tag_norms,
+            vn_users,
+            user_vns,
+            average_ratings: self.section(Section::AverageRatings)?,
+            title_indptr,
+            titles,
+            string_offsets,
+            strings,
+        })
+    }
+}
+
+// A recommender whose arrays live in a MappedSnapshot. It answers the same
+// queries as VisualNovelRecommender.
+pub struct MappedModel<'a> {
+    pub config: RecommenderConfig,
+    tag_matrix: CsMatViewI<'a, f64, u32, u64>,
+    tag_index: CsMatViewI<'a, f64, u32, u64>,
+    tag_norms: &'a [f64],
+    vn_users: CsMatViewI<'a, f64, u32, u64>,
+    user_vns: CsMatViewI<'a, f64, u32, u64>,
+    average_ratings: &'a [f64],
+    title_indptr: &'a [u64],
+    titles: &'a [[u32; 4]],
+    string_offsets: &'a [u64],
+    strings: &'a str,
+}
+
+impl<'a> MappedModel<'a> {
+    fn string(&self, id: u32) -> &'a str {
+        let start = self.string_offsets[id as usize] as usize;
+        let end = self.string_offsets[id as usize + 1] as usize;
+        &self.strings[start..end]
+    }
+
+    fn vn_titles(&self, vn_id: i32) -> &'a [[u32; 4]] {
+        let vn = vn_id as usize;
+        if vn_id < 0 || vn + 1 >= self.title_indptr.len() {
+            return &[];
+        }
+        &self.titles[self.title_indptr[vn] as usize..self.title_indptr[vn + 1] as usize]
+    }
+
+    pub fn get_average_rating(&self, vn_id: i32) -> Result<f64> {
+        match self.average_ratings.get(vn_id as usize) {
+            Some(rating) if vn_id >= 0 && !rating.is_nan() => Ok(*rating),
+            _ => {
+                self.ensure_known(vn_id)?;
+                Err(RecommenderError::NoRatings(vn_id))
+            }
+        }
+    }
+
+    // Fail with UnknownVn if the id appears neither in the titles nor in the votes
+    pub fn ensure_known(&self, vn_id: i32) -> Result<()> {
+        let rated = vn_id >= 0
+            && self
+                .average_ratings
+                .get(vn_id as usize)
+                .is_some_and(|rating| !rating.is_nan());
+        if rated || !self.vn_titles(vn_id).is_empty() {
+            Ok(())
+        } else {
+            Err(RecommenderError::UnknownVn(vn_id))
+        }
+    }
+
+    // Display title in the configured languages, or `v<id>` if there is none
+    pub fn get_title(&self, vn_id: i32) -> Cow<'a, str> {
+        self.get_title_in(
+            vn_id,
+            &self.config.title_languages,
+            self.config.romanized_titles,
+        )
+    }
+
+    // Display title in the first of `languages` the VN has a title in
+    pub fn get_title_in(&self, vn_id: i32, languages: &[String], romanized: bool) -> Cow<'a, str> {
+        let vn_data = self.vn_titles(vn_id);
+        let refs: Vec<TitleRef> = vn_data
+            .iter()
+            .map(|&[language, official, _, latin]| TitleRef {
+                language: self.string(language),
+                official: official != 0,
+                latin_title: (latin != NO_STRING).then(|| self.string(latin)),
+            })
+            .collect();
+
+        match titles::pick_title(&refs, languages, romanized) {
+            Some((i, true)) => Cow::Borrowed(refs[i].latin_title.unwrap()),
+            Some((i, false)) => Cow::Borrowed(self.string(vn_data[i][2])),
+            None => Cow::Owned(format!("v{}", vn_id)),
+        }
+    }
+
+    pub fn get_user_recommendations_scores(&self, seeds: &[(i32, f64)]) -> HashMap<i32, f64> {
+        self.vote_scores(&self.query_seeds(seeds))
+    }
+
+    pub fn get_tag_recommendations_score(&self, seeds: &[(i32, f64)]) -> HashMap<i32, f64> {
+        self.tag_scores(&self.query_seeds(seeds))
+    }
+
+    pub fn get_combined_recommendations_score(&self, seeds: &[(i32, f64)]) -> HashMap<i32, f64> {
+        let seeds = self.query_seeds(seeds);
+        self.combined_scores(&self.vote_scores(&seeds), &self.tag_scores(&seeds))
+    }
+
+    pub fn get_user_recommendations(&self, vn_id: i32) -> Result<Vec<Recommendation>> {
+        Ok(self.recommend_for_seeds(&[(vn_id, 1.0)])?.vote)
+    }
+
+    pub fn get_tag_recommendations(&self, vn_id: i32) -> Result<Vec<Recommendation>> {
+        Ok(self.recommend_for_seeds(&[(vn_id, 1.0)])?.tag)
+    }
+
+    pub fn get_combined_recommendations(&self, vn_id: i32) -> Result<Vec<Recommendation>> {
+        Ok(self.recommend_for_seeds(&[(vn_id, 1.0)])?.combined)
+    }
+
+    // Tag, vote and combined recommendations for weighted seed VNs, as in
+    // VisualNovelRecommender::recommend_for_seeds
+    pub fn recommend_for_seeds(&self, seeds: &[(i32, f64)]) -> Result<RecommendationLists> {
+        if !seeds.iter().any(|(_, weight)| *weight > 0.0) {
+            return Err(RecommenderError::EmptyQuery);
+        }
+        for (vn_id, _) in seeds {
+            self.ensure_known(*vn_id)?;
+        }
+
+        let seeds = &self.query_seeds(seeds);
+        let num_vns = self.config.num_vns;
+        let vote_scores = self.vote_scores(seeds);
+        let tag_scores = self.tag_scores(seeds);
+        let combined_scores = self.combined_scores(&vote_scores, &tag_scores);
+
+        Ok(RecommendationLists {
+            tag: scoring::ranked(&tag_scores, &[(scoring::TAG_SOURCE, &tag_scores)], num_vns),
+            vote: scoring::ranked(
+                &vote_scores,
+                &[(scoring::VOTE_SOURCE, &vote_scores)],
+                num_vns,
+            ),
+            combined: scoring::ranked(
+                &combined_scores,
+                &[
+                    (scoring::TAG_SOURCE, &tag_scores),
+                    (scoring::VOTE_SOURCE, &vote_scores),
+                ],
+                num_vns,
+            ),
+            tag_weighting: self.config.tag_weighting,
+            extra: BTreeMap::new(),
+        })
+    }
+
+    // The votes of a user as seeds, as in VisualNovelRecommender::user_profile
+    pub fn user_profile(&self, user_id: i32) -> Result<Vec<(i32, f64)>> {
+        let profile = scoring::user_profile(self.user_vns, user_id);
+        if profile.is_empty() {
+            return Err(RecommenderError::UnknownUser(user_id));
+        }
+        Ok(profile)
+    }
+
+    pub fn recommend_for_user(&self, user_id: i32) -> Result<RecommendationLists> {
+        self.recommend_for_seeds(&self.user_profile(user_id)?)
+    }
+
+    // An imported vote list as seeds, as in
+    // VisualNovelRecommender::vote_list_profile
+    pub fn vote_list_profile(
+        &self,
+        votes: &[(i32, f64)],
+        vote_scale: f64,
+    ) -> Result<Vec<(i32, f64)>> {
+        scoring::check_vote_scale(vote_scale)?;
+        let profile =
+            scoring::vote_list_profile(votes, vote_scale, self.config.vote_exp, |vn_id| {
+                self.ensure_known(vn_id).is_ok()
+            });
+        if profile.is_empty() {
+            return Err(RecommenderError::EmptyDataset("vote list"));
+        }
+        Ok(profile)
+    }
+
+    pub fn recommend_for_vote_list(
+        &self,
+        votes: &[(i32, f64)],
+        vote_scale: f64,
+    ) -> Result<RecommendationLists> {
+        self.recommend_for_seeds(&self.vote_list_profile(votes, vote_scale)?)
+    }
+
+    // Scale the weight of negative seeds by the configured strength
+    fn query_seeds(&self, seeds: &[(i32, f64)]) -> Vec<(i32, f64)> {
+        scoring::scale_negative_seeds(seeds, self.config.negative_seed_strength)
+    }
+
+    fn vote_scores(&self, seeds: &[(i32, f64)]) -> HashMap<i32, f64> {
+        scoring::corater_scores(self.vn_users, self.user_vns, seeds, self.config.num_vns)
+    }
+
+    fn tag_scores(&self, seeds: &[(i32, f64)]) -> HashMap<i32, f64> {
+        let index = scoring::TagIndex {
+            vn_tags: self.tag_matrix,
+            tag_vns: self.tag_index,
+            norms: self.tag_norms,
+            tag_weights: &HashMap::new(),
+        };
+        scoring::tag_scores(index, seeds, self.config.num_vns)
+    }
+
+    fn combined_scores(
+        &self,
+        vote_scores: &HashMap<i32, f64>,
+        tag_scores: &HashMap<i32, f64>,
+    ) -> HashMap<i32, f64> {
+        scoring::combine_scores(
+            &[
+                (vote_scores, self.config.vote_weight),
+                (tag_scores, self.config.tag_weight),
+            ],
+            self.config.num_vns,
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_data;
+    use std::ops::Range;
+
+    fn snapshot_bytes() -> (VisualNovelRecommender, Vec<u8>) {
+        let recommender = test_data::recommender(RecommenderConfig::default());
+        let mut bytes = Vec::new();
+        recommender.save_mapped_snapshot(&mut bytes).unwrap();
+        (recommender, bytes)
+    }
+
+    // Write the bytes to a file of their own and map it
+    fn open(name: &str, bytes: &[u8]) -> Result<MappedSnapshot> {
+        let path = std::env::temp_dir().join(format!(
+            "vnrm-test-{}-{}.snapshot",
+            std::process::id(),
+            name
+        ));
+        std::fs::write(&path, bytes).unwrap();
+        let snapshot = MappedSnapshot::open(&path);
+        std::fs::remove_file(&path).unwrap();
+        snapshot
+    }
+
+    fn reason(result: Result<impl Sized>) -> String {
+        match result {
+            Err(RecommenderError::InvalidSnapshot(reason)) => reason,
+            Err(err) => panic!("expected InvalidSnapshot, got {}", err),
+            Ok(_) => panic!("expected InvalidSnapshot"),
+        }
+    }
+
+    fn section_range(bytes: &[u8], section: Section) -> Range<usize> {
+        let entry = HEADER_LEN + section as usize * 16;
+        let offset = u64::from_le_bytes(bytes[entry..entry + 8].try_into().unwrap()) as usize;
+        let len = u64::from_le_bytes(bytes[entry + 8..entry + 16].try_into().unwrap()) as usize;
+        offset..offset + len
+    }
+
+    fn set_u64(bytes: &mut [u8], section: Section, index: usize, value: u64) {
+        let start = section_range(bytes, section).start + index * 8;
+        bytes[start..start + 8].copy_from_slice(&value.to_le_bytes());
+    }
+
+    #[test]
+    fn round_trip() {
+        let (recommender, bytes) = snapshot_bytes();
+        let snapshot = open("round-trip", &bytes).unwrap();
+        let model = snapshot.model().unwrap();
+
+        assert_eq!(model.config, recommender.config);
+        for seeds in [vec![(1, 1.0)], vec![(3, 1.0), (6, 0.5), (2, -1.0)]] {
+            test_data::assert_same_lists(
+                &model.recommend_for_seeds(&seeds).unwrap(),
+                &recommender.recommend_for_seeds(&seeds).unwrap(),
+            );
+        }
+        for vn_id in 1..=8 {
+            assert_eq!(model.get_title(vn_id), &*recommender.get_title(vn_id));
+            assert_eq!(
+                model.get_average_rating(vn_id).unwrap(),
+                recommender.get_average_rating(vn_id).unwrap()
+            );
+        }
+        assert_eq!(model.get_title(99), "v99");
+        assert_eq!(model.ensure_known(99).unwrap_err().kind(), "UnknownVn");
+        assert_eq!(
+            model.user_profile(5).unwrap(),
+            recommender.user_profile(5).unwrap()
+        );
+    }
+
+    #[test]
+    fn truncated() {
+        let (_, bytes) = snapshot_bytes();
+        assert_eq!(
+            reason(open("too-short", &bytes[..HEADER_LEN])),
+            "file is too short to be a memory-mapped snapshot"
+        );
+
+        // The section table is intact but the last section runs past the end
+        let snapshot = open("truncated", &bytes[..bytes.len() - 1]).unwrap();
+        assert_eq!(reason(snapshot.model()), "Strings section is out of bounds");
+    }
+
+    #[test]
+    fn bad_header() {
+        let (_, bytes) = snapshot_bytes();
+
+        let mut wrong_magic = bytes.clone();
+        wrong_magic[0..4].copy_from_slice(b"VNRS");
+        assert_eq!(
+            reason(open("magic", &wrong_magic)),
+            "not a memory-mapped snapshot file"
+        );
+
+        let mut wrong_version = bytes.clone();
+        wrong_version[4..8].copy_from_slice(&(MAPPED_VERSION + 1).to_le_bytes());
+        assert!(reason(open("version", &wrong_version)).starts_with("unsupported"));
+
+        let mut wrong_count = bytes.clone();
+        wrong_count[8..12].copy_from_slice(&(SECTION_COUNT as u32 - 1).to_le_bytes());
+        assert_eq!(
+            reason(open("sections", &wrong_count)),
+            format!(
+                "expected {} sections, found {}",
+                SECTION_COUNT,
+                SECTION_COUNT - 1
+            )
+        );
+    }
+
+    #[test]
+    fn inconsistent_titles() {
+        let (_, bytes) = snapshot_bytes();
+        let rows = section_range(&bytes, Section::TitleIndptr).len() / 8;
+
+        // Not starting at 0, decreasing, and not ending at the title count
+        for (name, index, value) in [
+            ("title-start", 0, 1),
+            ("title-order", 3, 0),
+            ("title-end", rows - 1, 1000),
+        ] {
+            let mut bytes = bytes.clone();
+            set_u64(&mut bytes, Section::TitleIndptr, index, value);
+            let snapshot = open(name, &bytes).unwrap();
+            assert_eq!(reason(snapshot.model()), "Titles section is inconsistent");
+        }
+    }
+
+    #[test]
+    fn inconsistent_strings() {
+        let (_, bytes) = snapshot_bytes();
+        let count = section_range(&bytes, Section::StringOffsets).len() / 8;
+        let blob_len = section_range(&bytes, Section::Strings).len() as u64;
+
+        // Not starting at 0, decreasing, and running past the blob
+        for (name, index, value) in [
+            ("strings-start", 0, 1),
+            ("strings-order", 2, 0),
+            ("strings-end", count - 1, blob_len + 1),
+        ] {
+            let mut bytes = bytes.clone();
+            set_u64(&mut bytes, Section::StringOffsets, index, value);
+            let snapshot = open(name, &bytes).unwrap();
+            assert_eq!(
+                reason(snapshot.model()),
+                "StringOffsets section is inconsistent"
+            );
+        }
+    }
+}
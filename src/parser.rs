@@ -0,0 +1,743 @@
+// Parsers for the VNDB dump formats. Each format has a line parser that
+// either yields a record or says why the line was skipped, and a reader-level
+// function that runs it over a whole file and keeps a ParseReport. Personal
+// vote lists are parsed the same way, entry by entry.
+use crate::data::{Rating, Tag, TagCategory, TagInfo, TagParent, VnTitle};
+use crate::error::{RecommenderError, Result};
+use std::{
+    collections::BTreeMap,
+    fmt,
+    io::{self, BufRead},
+    path::{Path, PathBuf},
+};
+
+// Number of skipped lines kept as examples in a ParseReport
+const MAX_EXAMPLES: usize = 20;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum SkipReason {
+    EmptyLine,
+    TooFewColumns,
+    InvalidVnId,
+    InvalidUserId,
+    InvalidTagId,
+    InvalidVote,
+    InvalidCategory,
+    // A vote list entry without a vote, e.g. a wishlisted VN
+    NoVote,
+    Header,
+}
+
+impl fmt::Display for SkipReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let reason = match self {
+            SkipReason::EmptyLine => "empty line",
+            SkipReason::TooFewColumns => "too few columns",
+            SkipReason::InvalidVnId => "invalid VN id",
+            SkipReason::InvalidUserId => "invalid user id",
+            SkipReason::InvalidTagId => "invalid tag id",
+            SkipReason::InvalidVote => "invalid vote",
+            SkipReason::InvalidCategory => "invalid tag category",
+            SkipReason::NoVote => "no vote",
+            SkipReason::Header => "header line",
+        };
+        f.write_str(reason)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SkippedLine {
+    pub line: usize,
+    pub reason: SkipReason,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct ParseReport {
+    // The file (or source label) that was parsed
+    pub file: PathBuf,
+    // Total number of lines read
+    pub lines: usize,
+    // Number of lines that produced a record
+    pub parsed: usize,
+    // Number of skipped lines per reason
+    pub skipped: BTreeMap<SkipReason, usize>,
+    // The first few skipped lines, for tracking down bad input
+    pub examples: Vec<SkippedLine>,
+}
+
+impl ParseReport {
+    pub fn new(file: impl Into<PathBuf>) -> Self {
+        Self {
+            file: file.into(),
+            ..Self::default()
+        }
+    }
+
+    pub fn total_skipped(&self) -> usize {
+        self.skipped.values().sum()
+    }
+
+    fn skip(&mut self, line: usize, reason: SkipReason) {
+        *self.skipped.entry(reason).or_insert(0) += 1;
+        if self.examples.len() < MAX_EXAMPLES {
+            self.examples.push(SkippedLine { line, reason });
+        }
+    }
+}
+
+impl fmt::Display for ParseReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}: {} records from {} lines",
+            self.file.display(),
+            self.parsed,
+            self.lines
+        )?;
+        if !self.skipped.is_empty() {
+            let reasons: Vec<String> = self
+                .skipped
+                .iter()
+                .map(|(reason, count)| format!("{} {}", count, reason))
+                .collect();
+            write!(
+                f,
+                ", {} skipped ({})",
+                self.total_skipped(),
+                reasons.join(", ")
+            )?;
+        }
+        Ok(())
+    }
+}
+
+// Records parsed from one file together with the report
+#[derive(Debug, Clone)]
+pub struct Parsed<T> {
+    pub records: Vec<T>,
+    pub report: ParseReport,
+}
+
+// Turn an error from reading one line into a RecommenderError that names the line
+fn line_error(path: &Path, line: usize, err: io::Error) -> RecommenderError {
+    if err.kind() == io::ErrorKind::InvalidData {
+        RecommenderError::MalformedLine {
+            file: path.to_path_buf(),
+            line,
+            reason: err.to_string(),
+        }
+    } else {
+        RecommenderError::io(path, err)
+    }
+}
+
+// Parse an id with an optional one-letter prefix such as `v17` or `g32`
+fn parse_id(field: &str, prefix: char) -> Option<i32> {
+    parse_plain_id(field.strip_prefix(prefix).unwrap_or(field))
+}
+
+// Parse an id without a prefix. VNDB ids start at 1, and the matrices are
+// sized by the largest id, so anything else is rejected.
+fn parse_plain_id(field: &str) -> Option<i32> {
+    field.parse().ok().filter(|id| *id > 0)
+}
+
+// Parse a nullable PostgreSQL text field
+fn parse_nullable(field: &str) -> Option<&str> {
+    if field == "\\N" { None } else { Some(field) }
+}
+
+// vn_titles: `v17  en  t  Ever17 -the out of infinity-  \N`
+pub fn parse_title_line(line: &str) -> std::result::Result<VnTitle, SkipReason> {
+    if line.is_empty() {
+        return Err(SkipReason::EmptyLine);
+    }
+    let parts: Vec<&str> = line.split('\t').collect();
+    if parts.len() < 5 {
+        return Err(SkipReason::TooFewColumns);
+    }
+
+    let vn_id = parse_id(parts[0], 'v').ok_or(SkipReason::InvalidVnId)?;
+
+    Ok(VnTitle {
+        vn_id,
+        language: parts[1].into(),
+        official: parts[2] == "t",
+        title: parts[3].into(),
+        latin_title: parse_nullable(parts[4]).map(|x| x.into()),
+    })
+}
+
+// vndb-votes: `17 2 80 2008-08-06`, with the raw vote in `rating`
+pub fn parse_vote_line(line: &str) -> std::result::Result<Rating, SkipReason> {
+    let parts: Vec<&str> = line.split_whitespace().collect();
+    if parts.is_empty() {
+        return Err(SkipReason::EmptyLine);
+    }
+    if parts.len() < 4 {
+        return Err(SkipReason::TooFewColumns);
+    }
+
+    let vn_id = parse_plain_id(parts[0]).ok_or(SkipReason::InvalidVnId)?;
+    let user_id = parse_plain_id(parts[1]).ok_or(SkipReason::InvalidUserId)?;
+    let rating = parts[2].parse().map_err(|_| SkipReason::InvalidVote)?;
+
+    Ok(Rating {
+        vn_id,
+        user_id,
+        rating,
+        date: parts[3].into(),
+    })
+}
+
+// tags_vn: `<date>  g32  v17  u2  2  0  f  ...`, with the raw vote in
+// `rating`, then the spoiler level (or `\N`) and the lie flag
+pub fn parse_tag_line(line: &str) -> std::result::Result<Tag, SkipReason> {
+    if line.is_empty() {
+        return Err(SkipReason::EmptyLine);
+    }
+    let parts: Vec<&str> = line.split('\t').collect();
+    if parts.len() < 5 {
+        return Err(SkipReason::TooFewColumns);
+    }
+
+    let tag_id = parse_id(parts[1], 'g').ok_or(SkipReason::InvalidTagId)?;
+    let vn_id = parse_id(parts[2], 'v').ok_or(SkipReason::InvalidVnId)?;
+    let rating = parts[4].parse().map_err(|_| SkipReason::InvalidVote)?;
+    // Older dumps lack these columns
+    let spoiler = parts
+        .get(5)
+        .and_then(|field| parse_nullable(field))
+        .and_then(|field| field.parse().ok());
+    let lie = parts.get(6) == Some(&"t");
+
+    Ok(Tag {
+        tag_id,
+        vn_id,
+        rating,
+        spoiler,
+        lie,
+    })
+}
+
+// tags: `g7  cont  0  t  t  Mystery  ...`
+pub fn parse_tag_info_line(line: &str) -> std::result::Result<TagInfo, SkipReason> {
+    if line.is_empty() {
+        return Err(SkipReason::EmptyLine);
+    }
+    let parts: Vec<&str> = line.split('\t').collect();
+    if parts.len() < 6 {
+        return Err(SkipReason::TooFewColumns);
+    }
+
+    let tag_id = parse_id(parts[0], 'g').ok_or(SkipReason::InvalidTagId)?;
+    let category = match parts[1] {
+        "cont" => TagCategory::Content,
+        "ero" => TagCategory::Sexual,
+        "tech" => TagCategory::Technical,
+        _ => return Err(SkipReason::InvalidCategory),
+    };
+    let default_spoiler = parts[2].parse().unwrap_or(0);
+
+    Ok(TagInfo {
+        tag_id,
+        name: parts[5].into(),
+        category,
+        default_spoiler,
+    })
+}
+
+// tags_parents: `g8  g7  t`, a tag and one of its parents
+pub fn parse_tag_parent_line(line: &str) -> std::result::Result<TagParent, SkipReason> {
+    if line.is_empty() {
+        return Err(SkipReason::EmptyLine);
+    }
+    let parts: Vec<&str> = line.split('\t').collect();
+    if parts.len() < 2 {
+        return Err(SkipReason::TooFewColumns);
+    }
+
+    let tag_id = parse_id(parts[0], 'g').ok_or(SkipReason::InvalidTagId)?;
+    let parent_id = parse_id(parts[1], 'g').ok_or(SkipReason::InvalidTagId)?;
+
+    Ok(TagParent { tag_id, parent_id })
+}
+
+// Run a line parser over every line of a reader
+pub fn parse_lines<T, R: BufRead>(
+    reader: R,
+    path: &Path,
+    parse_line: impl Fn(&str) -> std::result::Result<T, SkipReason>,
+) -> Result<Parsed<T>> {
+    let mut records = Vec::new();
+    let mut report = ParseReport::new(path);
+
+    for (line_no, line) in reader.lines().enumerate() {
+        let line = line.map_err(|e| line_error(path, line_no + 1, e))?;
+        report.lines += 1;
+        match parse_line(&line) {
+            Ok(record) => {
+                records.push(record);
+                report.parsed += 1;
+            }
+            Err(reason) => report.skip(line_no + 1, reason),
+        }
+    }
+
+    Ok(Parsed { records, report })
+}
+
+pub fn parse_titles<R: BufRead>(reader: R, path: &Path) -> Result<Parsed<VnTitle>> {
+    parse_lines(reader, path, parse_title_line)
+}
+
+pub fn parse_votes<R: BufRead>(reader: R, path: &Path) -> Result<Parsed<Rating>> {
+    parse_lines(reader, path, parse_vote_line)
+}
+
+pub fn parse_tags<R: BufRead>(reader: R, path: &Path) -> Result<Parsed<Tag>> {
+    parse_lines(reader, path, parse_tag_line)
+}
+
+pub fn parse_tag_info<R: BufRead>(reader: R, path: &Path) -> Result<Parsed<TagInfo>> {
+    parse_lines(reader, path, parse_tag_info_line)
+}
+
+pub fn parse_tag_parents<R: BufRead>(reader: R, path: &Path) -> Result<Parsed<TagParent>> {
+    parse_lines(reader, path, parse_tag_parent_line)
+}
+
+// Vote list CSV: `17,85` or `v17,8.5`, optionally after a `vn_id,vote` header
+pub fn parse_vote_list_line(line: &str) -> std::result::Result<(i32, f64), SkipReason> {
+    let line = line.trim();
+    if line.is_empty() {
+        return Err(SkipReason::EmptyLine);
+    }
+    let parts: Vec<&str> = line.split(',').map(str::trim).collect();
+    if parts.len() < 2 {
+        return Err(SkipReason::TooFewColumns);
+    }
+
+    let vn_id = parse_id(parts[0], 'v').ok_or(SkipReason::InvalidVnId)?;
+    if parts[1].is_empty() {
+        return Err(SkipReason::NoVote);
+    }
+    let vote = parts[1].parse().map_err(|_| SkipReason::InvalidVote)?;
+
+    Ok((vn_id, vote))
+}
+
+// One entry of a JSON vote list: `{"vn_id": 17, "vote": 85}`, the VNDB API's
+// `{"id": "v17", "vote": 85}`, or a `[17, 85]` pair
+fn parse_vote_list_entry(entry: &serde_json::Value) -> std::result::Result<(i32, f64), SkipReason> {
+    use serde_json::Value;
+
+    let (id, vote) = match entry {
+        Value::Array(pair) if pair.len() >= 2 => (&pair[0], &pair[1]),
+        Value::Array(_) => return Err(SkipReason::TooFewColumns),
+        Value::Object(fields) => {
+            let id = fields
+                .get("vn_id")
+                .or_else(|| fields.get("id"))
+                .ok_or(SkipReason::InvalidVnId)?;
+            (id, fields.get("vote").unwrap_or(&Value::Null))
+        }
+        _ => return Err(SkipReason::TooFewColumns),
+    };
+
+    let vn_id = match id {
+        Value::Number(id) => id
+            .as_i64()
+            .and_then(|id| i32::try_from(id).ok())
+            .filter(|id| *id > 0),
+        Value::String(id) => parse_id(id, 'v'),
+        _ => None,
+    }
+    .ok_or(SkipReason::InvalidVnId)?;
+    let vote = match vote {
+        Value::Null => return Err(SkipReason::NoVote),
+        Value::Number(vote) => vote.as_f64(),
+        Value::String(vote) => vote.parse().ok(),
+        _ => None,
+    }
+    .ok_or(SkipReason::InvalidVote)?;
+
+    Ok((vn_id, vote))
+}
+
+// One `<vn id="v17">...</vn>` element of VNDB's XML list export, whose vote
+// is on the 10-100 scale: `<vote timestamp="...">85</vote>`
+fn parse_vote_list_element(element: &str) -> std::result::Result<(i32, f64), SkipReason> {
+    let tag_end = element.find('>').ok_or(SkipReason::InvalidVnId)?;
+    let vn_id = element[..tag_end]
+        .split_once("id=\"")
+        .and_then(|(_, rest)| rest.split_once('"'))
+        .and_then(|(id, _)| parse_id(id, 'v'))
+        .ok_or(SkipReason::InvalidVnId)?;
+
+    let vote = element
+        .split_once("<vote")
+        .and_then(|(_, rest)| rest.split_once('>'))
+        .and_then(|(_, rest)| rest.split_once("</vote>"))
+        .ok_or(SkipReason::NoVote)?
+        .0;
+    let vote = vote.trim().parse().map_err(|_| SkipReason::InvalidVote)?;
+
+    Ok((vn_id, vote))
+}
+
+// Run an entry parser over the entries of a vote list, numbered by `line`
+fn parse_entries<E>(
+    entries: impl IntoIterator<Item = (usize, E)>,
+    path: &Path,
+    parse_entry: impl Fn(E) -> std::result::Result<(i32, f64), SkipReason>,
+) -> Parsed<(i32, f64)> {
+    let mut records = Vec::new();
+    let mut report = ParseReport::new(path);
+
+    for (line, entry) in entries {
+        report.lines += 1;
+        match parse_entry(entry) {
+            Ok(record) => {
+                records.push(record);
+                report.parsed += 1;
+            }
+            Err(reason) => report.skip(line, reason),
+        }
+    }
+
+    Parsed { records, report }
+}
+
+// A personal vote list as (vn_id, vote) pairs. The format is detected from
+// the contents: VNDB's XML list export, a JSON array of votes (or a VNDB API
+// response holding one under `results`), or `vn_id,vote` CSV. Votes are
+// returned as written, on whatever scale the list uses.
+pub fn parse_vote_list<R: BufRead>(mut reader: R, path: &Path) -> Result<Parsed<(i32, f64)>> {
+    let mut contents = String::new();
+    reader
+        .read_to_string(&mut contents)
+        .map_err(|e| line_error(path, 1, e))?;
+    let line_of = |offset: usize| contents[..offset].matches('\n').count() + 1;
+
+    match contents.trim_start().chars().next() {
+        Some('<') => {
+            // Every element starts with `<vn `, which `<vns>` does not match
+            let elements = contents
+                .match_indices("<vn ")
+                .map(|(offset, _)| (line_of(offset), &contents[offset + 4..]))
+                .map(|(line, rest)| (line, rest.split("</vn>").next().unwrap_or(rest)));
+            Ok(parse_entries(elements, path, parse_vote_list_element))
+        }
+        Some('[' | '{') => {
+            let value: serde_json::Value =
+                serde_json::from_str(&contents).map_err(|e| RecommenderError::MalformedLine {
+                    file: path.to_path_buf(),
+                    line: e.line(),
+                    reason: e.to_string(),
+                })?;
+            let entries = match value.get("results").unwrap_or(&value) {
+                serde_json::Value::Array(entries) => entries,
+                _ => {
+                    return Err(RecommenderError::MalformedLine {
+                        file: path.to_path_buf(),
+                        line: 1,
+                        reason: "expected an array of votes".to_string(),
+                    });
+                }
+            };
+            let entries = entries.iter().enumerate().map(|(i, entry)| (i + 1, entry));
+            Ok(parse_entries(entries, path, parse_vote_list_entry))
+        }
+        _ => {
+            // A first line without a VN id is taken for a column header
+            let lines = contents
+                .lines()
+                .enumerate()
+                .map(|(i, line)| (i + 1, (i, line)));
+            Ok(parse_entries(
+                lines,
+                path,
+                |(i, line)| match parse_vote_list_line(line) {
+                    Err(SkipReason::InvalidVnId) if i == 0 => Err(SkipReason::Header),
+                    result => result,
+                },
+            ))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn vote_list(contents: &str) -> Parsed<(i32, f64)> {
+        parse_vote_list(contents.as_bytes(), Path::new("list")).unwrap()
+    }
+
+    fn skipped(report: &ParseReport) -> Vec<(usize, SkipReason)> {
+        report
+            .examples
+            .iter()
+            .map(|skipped| (skipped.line, skipped.reason))
+            .collect()
+    }
+
+    #[test]
+    fn title_lines() {
+        let title = parse_title_line("v17\ten\tt\tEver17\t\\N").unwrap();
+        assert_eq!(title.vn_id, 17);
+        assert_eq!(&*title.language, "en");
+        assert!(title.official);
+        assert_eq!(&*title.title, "Ever17");
+        assert!(title.latin_title.is_none());
+
+        let title = parse_title_line("v4\tja\tf\tクラナド\tCLANNAD").unwrap();
+        assert!(!title.official);
+        assert_eq!(title.latin_title.as_deref(), Some("CLANNAD"));
+
+        let cases = [
+            ("", SkipReason::EmptyLine),
+            ("v17\ten\tt\tEver17", SkipReason::TooFewColumns),
+            ("vx\ten\tt\tEver17\t\\N", SkipReason::InvalidVnId),
+            ("v-1\ten\tt\tEver17\t\\N", SkipReason::InvalidVnId),
+            ("v0\ten\tt\tEver17\t\\N", SkipReason::InvalidVnId),
+        ];
+        for (line, reason) in cases {
+            assert_eq!(parse_title_line(line).unwrap_err(), reason, "{:?}", line);
+        }
+    }
+
+    #[test]
+    fn vote_lines() {
+        let rating = parse_vote_line("17 2 80 2008-08-06").unwrap();
+        assert_eq!((rating.vn_id, rating.user_id, rating.rating), (17, 2, 80.0));
+        assert_eq!(&*rating.date, "2008-08-06");
+
+        let cases = [
+            ("", SkipReason::EmptyLine),
+            ("   ", SkipReason::EmptyLine),
+            ("17 2 80", SkipReason::TooFewColumns),
+            ("v17 2 80 2008-08-06", SkipReason::InvalidVnId),
+            ("17 u2 80 2008-08-06", SkipReason::InvalidUserId),
+            ("-1 2 80 2008-08-06", SkipReason::InvalidVnId),
+            ("17 0 80 2008-08-06", SkipReason::InvalidUserId),
+            ("17 2 high 2008-08-06", SkipReason::InvalidVote),
+        ];
+        for (line, reason) in cases {
+            assert_eq!(parse_vote_line(line).unwrap_err(), reason, "{:?}", line);
+        }
+    }
+
+    #[test]
+    fn tag_lines() {
+        let tag = parse_tag_line("2010-01-01\tg32\tv17\tu2\t2\t1\tt").unwrap();
+        assert_eq!((tag.tag_id, tag.vn_id, tag.rating), (32, 17, 2.0));
+        assert_eq!(tag.spoiler, Some(1));
+        assert!(tag.lie);
+
+        let tag = parse_tag_line("2010-01-01\tg32\tv17\tu2\t-1\t\\N\tf").unwrap();
+        assert_eq!(tag.rating, -1.0);
+        assert_eq!(tag.spoiler, None);
+        assert!(!tag.lie);
+
+        // Older dumps without the spoiler and lie columns
+        let tag = parse_tag_line("2010-01-01\tg32\tv17\tu2\t3").unwrap();
+        assert_eq!(tag.spoiler, None);
+        assert!(!tag.lie);
+
+        let cases = [
+            ("", SkipReason::EmptyLine),
+            ("2010-01-01\tg32\tv17\tu2", SkipReason::TooFewColumns),
+            ("2010-01-01\tgx\tv17\tu2\t2", SkipReason::InvalidTagId),
+            ("2010-01-01\tg32\tvx\tu2\t2", SkipReason::InvalidVnId),
+            ("2010-01-01\tg-1\tv17\tu2\t2", SkipReason::InvalidTagId),
+            ("2010-01-01\tg32\tv0\tu2\t2", SkipReason::InvalidVnId),
+            ("2010-01-01\tg32\tv17\tu2\tx", SkipReason::InvalidVote),
+        ];
+        for (line, reason) in cases {
+            assert_eq!(parse_tag_line(line).unwrap_err(), reason, "{:?}", line);
+        }
+    }
+
+    #[test]
+    fn tag_info_lines() {
+        let info = parse_tag_info_line("g7\tcont\t1\tt\tt\tMystery\t").unwrap();
+        assert_eq!(info.tag_id, 7);
+        assert_eq!(info.category, TagCategory::Content);
+        assert_eq!(info.default_spoiler, 1);
+        assert_eq!(&*info.name, "Mystery");
+        let categories = [
+            ("ero", TagCategory::Sexual),
+            ("tech", TagCategory::Technical),
+        ];
+        for (category, expected) in categories {
+            let line = format!("g8\t{}\t0\tt\tt\tName", category);
+            assert_eq!(parse_tag_info_line(&line).unwrap().category, expected);
+        }
+
+        let cases = [
+            ("", SkipReason::EmptyLine),
+            ("g7\tcont\t0\tt\tt", SkipReason::TooFewColumns),
+            ("x7\tcont\t0\tt\tt\tMystery", SkipReason::InvalidTagId),
+            ("g0\tcont\t0\tt\tt\tMystery", SkipReason::InvalidTagId),
+            ("g7\tplot\t0\tt\tt\tMystery", SkipReason::InvalidCategory),
+        ];
+        for (line, reason) in cases {
+            assert_eq!(parse_tag_info_line(line).unwrap_err(), reason, "{:?}", line);
+        }
+    }
+
+    #[test]
+    fn tag_parent_lines() {
+        let link = parse_tag_parent_line("g8\tg7\tt").unwrap();
+        assert_eq!((link.tag_id, link.parent_id), (8, 7));
+
+        let cases = [
+            ("", SkipReason::EmptyLine),
+            ("g8", SkipReason::TooFewColumns),
+            ("gx\tg7", SkipReason::InvalidTagId),
+            ("g8\tgx", SkipReason::InvalidTagId),
+            ("g8\tg-7", SkipReason::InvalidTagId),
+        ];
+        for (line, reason) in cases {
+            assert_eq!(
+                parse_tag_parent_line(line).unwrap_err(),
+                reason,
+                "{:?}",
+                line
+            );
+        }
+    }
+
+    #[test]
+    fn report_counts_skipped_lines() {
+        let input = "17 2 80 2008-08-06\n\n17 x 80 2008-08-06\n18 3 60 2009-01-01\n19 3\n";
+        let parsed = parse_votes(input.as_bytes(), Path::new("votes")).unwrap();
+        assert_eq!(parsed.records.len(), 2);
+        assert_eq!(parsed.report.lines, 5);
+        assert_eq!(parsed.report.parsed, 2);
+        assert_eq!(parsed.report.total_skipped(), 3);
+        assert_eq!(parsed.report.skipped[&SkipReason::EmptyLine], 1);
+        assert_eq!(parsed.report.skipped[&SkipReason::InvalidUserId], 1);
+        assert_eq!(parsed.report.skipped[&SkipReason::TooFewColumns], 1);
+        assert_eq!(
+            skipped(&parsed.report),
+            [
+                (2, SkipReason::EmptyLine),
+                (3, SkipReason::InvalidUserId),
+                (5, SkipReason::TooFewColumns),
+            ]
+        );
+        assert_eq!(
+            parsed.report.to_string(),
+            "votes: 2 records from 5 lines, 3 skipped (1 empty line, 1 too few columns, 1 invalid user id)"
+        );
+    }
+
+    #[test]
+    fn report_keeps_few_examples() {
+        let input = "x\n".repeat(MAX_EXAMPLES + 5);
+        let parsed = parse_votes(input.as_bytes(), Path::new("votes")).unwrap();
+        assert_eq!(parsed.report.total_skipped(), MAX_EXAMPLES + 5);
+        assert_eq!(parsed.report.examples.len(), MAX_EXAMPLES);
+    }
+
+    #[test]
+    fn csv_vote_lists() {
+        let parsed = vote_list("vn_id,vote\n17,85\nv4, 8.5\n\n5,\n6\nx,7\nv-1,8\n");
+        assert_eq!(parsed.records, [(17, 85.0), (4, 8.5)]);
+        assert_eq!(
+            skipped(&parsed.report),
+            [
+                (1, SkipReason::Header),
+                (4, SkipReason::EmptyLine),
+                (5, SkipReason::NoVote),
+                (6, SkipReason::TooFewColumns),
+                (7, SkipReason::InvalidVnId),
+                (8, SkipReason::InvalidVnId),
+            ]
+        );
+
+        // Without a header the first line is a vote
+        let parsed = vote_list("17,85\n18,70\n");
+        assert_eq!(parsed.records, [(17, 85.0), (18, 70.0)]);
+        assert_eq!(parsed.report.total_skipped(), 0);
+
+        let parsed = vote_list("17,high\n");
+        assert_eq!(skipped(&parsed.report), [(1, SkipReason::InvalidVote)]);
+    }
+
+    #[test]
+    fn json_vote_lists() {
+        let parsed = vote_list(
+            r#"[{"vn_id": 17, "vote": 85}, {"id": "v4", "vote": "70"}, [5, 60],
+                {"id": "v6", "vote": null}, {"vote": 50}, [7], {"id": "v8", "vote": true}, 9,
+                [-3, 50]]"#,
+        );
+        assert_eq!(parsed.records, [(17, 85.0), (4, 70.0), (5, 60.0)]);
+        assert_eq!(
+            skipped(&parsed.report),
+            [
+                (4, SkipReason::NoVote),
+                (5, SkipReason::InvalidVnId),
+                (6, SkipReason::TooFewColumns),
+                (7, SkipReason::InvalidVote),
+                (8, SkipReason::TooFewColumns),
+                (9, SkipReason::InvalidVnId),
+            ]
+        );
+
+        // A VNDB API response
+        let parsed = vote_list(r#"{"results": [{"id": "v17", "vote": 90}], "more": false}"#);
+        assert_eq!(parsed.records, [(17, 90.0)]);
+
+        let err = parse_vote_list(r#"{"vote": 1}"#.as_bytes(), Path::new("list")).unwrap_err();
+        assert_eq!(err.kind(), "MalformedLine");
+        let err = parse_vote_list("[1, 2".as_bytes(), Path::new("list")).unwrap_err();
+        assert_eq!(err.kind(), "MalformedLine");
+    }
+
+    #[test]
+    fn xml_vote_lists() {
+        let parsed = vote_list(
+            "<vndb-export>\n<vns>\n\
+             <vn id=\"v17\" private=\"false\">\n<title>Ever17</title>\n<vote timestamp=\"2020-01-01\">85</vote>\n</vn>\n\
+             <vn id=\"v4\">\n<title>Clannad</title>\n</vn>\n\
+             <vn id=\"v5\"><vote>high</vote></vn>\n\
+             <vn id=\"x\"><vote>7</vote></vn>\n\
+             </vns>\n</vndb-export>\n",
+        );
+        assert_eq!(parsed.records, [(17, 85.0)]);
+        assert_eq!(
+            skipped(&parsed.report),
+            [
+                (7, SkipReason::NoVote),
+                (10, SkipReason::InvalidVote),
+                (11, SkipReason::InvalidVnId),
+            ]
+        );
+    }
+
+    #[test]
+    fn vote_list_formats() {
+        // The format is told by the first character after any whitespace
+        let parsed = vote_list("\n  <vndb-export><vns><vn id=\"v3\"><vote>70</vote></vn></vns>");
+        assert_eq!(parsed.records, [(3, 70.0)]);
+        let parsed = vote_list("\n  [[3, 70]]");
+        assert_eq!(parsed.records, [(3, 70.0)]);
+        let parsed = vote_list("\n3,70\n");
+        assert_eq!(parsed.records, [(3, 70.0)]);
+
+        // An export without votes and an empty file are empty lists
+        let parsed = vote_list("<vndb-export><vns></vns></vndb-export>");
+        assert!(parsed.records.is_empty());
+        assert_eq!(parsed.report.lines, 0);
+        let parsed = vote_list("");
+        assert!(parsed.records.is_empty());
+        assert_eq!(parsed.report.lines, 0);
+
+        let err = parse_vote_list(r#"{"results": 5}"#.as_bytes(), Path::new("list")).unwrap_err();
+        assert_eq!(err.kind(), "MalformedLine");
+    }
+}
@@ -0,0 +1,370 @@
+// Random walk with restart over the graph of VNs, users and tags, in the
+// style of Pixie (Eksombatchai et al., 2018). Each step hops from the current
+// VN to one of its voters or tags and on to another VN of that user or tag,
+// and jumps back to the seed VN with the restart probability. How often the
+// walks visit a VN approximates its personalized PageRank for the seeds, and
+// unlike the cosine models it reaches VNs that share no direct neighbours
+// with them.
+use crate::error::Result;
+use crate::recommender::VisualNovelRecommender;
+use crate::scorer::Scorer;
+use crate::scoring::{self, XorShift};
+use sprs::CsMat;
+use std::collections::HashMap;
+
+// Name of the model in Recommendation::source_scores
+pub const RANDOM_WALK_SOURCE: &str = "random_walk";
+
+// Scores candidates by how often walks from the seed VNs visit them
+#[derive(Debug, Clone)]
+pub struct RandomWalkScorer {
+    // Steps walked per query, shared out among the seeds by weight
+    pub walk_length: usize,
+    // Chance of jumping back to the seed VN after each step
+    pub restart_probability: f64,
+    // Relative chance of leaving a VN through its votes or through its tags
+    pub vote_weight: f64,
+    pub tag_weight: f64,
+    // Follow votes and tags in proportion to the vote and tag score, instead
+    // of picking uniformly among the positive ones
+    pub weighted_edges: bool,
+    // Seed of the walks, so that a query always gives the same results
+    pub seed: u64,
+    // Running sums of the edge weights within each outer row, aligned with
+    // the data arrays of vn_users, user_vns, similarity_matrix and tag_index
+    vn_user_weights: Vec<f64>,
+    user_vn_weights: Vec<f64>,
+    vn_tag_weights: Vec<f64>,
+    tag_vn_weights: Vec<f64>,
+}
+
+impl Default for RandomWalkScorer {
+    fn default() -> Self {
+        Self {
+            walk_length: 100_000,
+            restart_probability: 0.5,
+            vote_weight: 1.0,
+            tag_weight: 1.0,
+            weighted_edges: true,
+            seed: 42,
+            vn_user_weights: Vec::new(),
+            user_vn_weights: Vec::new(),
+            vn_tag_weights: Vec::new(),
+            tag_vn_weights: Vec::new(),
+        }
+    }
+}
+
+impl RandomWalkScorer {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn walk_length(mut self, walk_length: usize) -> Self {
+        self.walk_length = walk_length;
+        self
+    }
+
+    pub fn restart_probability(mut self, restart_probability: f64) -> Self {
+        self.restart_probability = restart_probability;
+        self
+    }
+
+    pub fn vote_weight(mut self, vote_weight: f64) -> Self {
+        self.vote_weight = vote_weight;
+        self
+    }
+
+    pub fn tag_weight(mut self, tag_weight: f64) -> Self {
+        self.tag_weight = tag_weight;
+        self
+    }
+
+    pub fn weighted_edges(mut self, weighted_edges: bool) -> Self {
+        self.weighted_edges = weighted_edges;
+        self
+    }
+
+    pub fn seed(mut self, seed: u64) -> Self {
+        self.seed = seed;
+        self
+    }
+
+    fn running_sums(&self, matrix: &Option<CsMat<f64>>) -> Vec<f64> {
+        let Some(matrix) = matrix else {
+            return Vec::new();
+        };
+        let mut sums = Vec::with_capacity(matrix.nnz());
+        for row in matrix.outer_iterator() {
+            let mut sum = 0.0;
+            for &value in row.data() {
+                // Low votes still link a VN to its voter, but negative tag
+                // scores mean the VN is not like the tag at all
+                sum += match (self.weighted_edges, value > 0.0) {
+                    (_, false) => 0.0,
+                    (true, true) => value,
+                    (false, true) => 1.0,
+                };
+                sums.push(sum);
+            }
+        }
+        sums
+    }
+
+    // Walk from the seeds, splitting walk_length among them by the size of
+    // their weights, and return the visits of each VN weighted likewise
+    fn visits(&self, data: &VisualNovelRecommender, seeds: &[(i32, f64)]) -> HashMap<i32, f64> {
+        let mut visits: HashMap<i32, f64> = HashMap::new();
+        let total_weight: f64 = seeds.iter().map(|(_, weight)| weight.abs()).sum();
+        if total_weight == 0.0 || self.walk_length == 0 {
+            return visits;
+        }
+        let visit_score = total_weight / self.walk_length as f64;
+        let restart_probability = self.restart_probability.clamp(0.0, 1.0);
+
+        let mut rng = XorShift(self.seed.max(1));
+        for &(vn_id, weight) in seeds {
+            let steps = (self.walk_length as f64 * weight.abs() / total_weight).round() as usize;
+            let mut current = vn_id as usize;
+            for _ in 0..steps {
+                match self.step(data, current, &mut rng) {
+                    Some(next) => {
+                        *visits.entry(next as i32).or_insert(0.0) += visit_score.copysign(weight);
+                        current = next;
+                        if rng.next_f64() < restart_probability {
+                            current = vn_id as usize;
+                        }
+                    }
+                    // Dead end, start over
+                    None => current = vn_id as usize,
+                }
+            }
+        }
+        visits
+    }
+
+    // Hop from a VN to one of its voters or tags and on to one of their VNs
+    fn step(&self, data: &VisualNovelRecommender, vn: usize, rng: &mut XorShift) -> Option<usize> {
+        let votes = data
+            .vn_users
+            .as_ref()
+            .map(|matrix| (matrix, &self.vn_user_weights));
+        let tags = data
+            .similarity_matrix
+            .as_ref()
+            .map(|matrix| (matrix, &self.vn_tag_weights));
+        let chance = |edges, weight: f64| {
+            if total(edges, vn) > 0.0 {
+                weight.max(0.0)
+            } else {
+                0.0
+            }
+        };
+        let vote_chance = chance(votes, self.vote_weight);
+        let tag_chance = chance(tags, self.tag_weight);
+        if vote_chance + tag_chance <= 0.0 {
+            return None;
+        }
+
+        if rng.next_f64() * (vote_chance + tag_chance) < vote_chance {
+            let user = pick(votes, vn, rng)?;
+            let user_vns = data.user_vns.as_ref().map(|m| (m, &self.user_vn_weights));
+            pick(user_vns, user, rng)
+        } else {
+            let tag = pick(tags, vn, rng)?;
+            let tag_vns = data.tag_index.as_ref().map(|m| (m, &self.tag_vn_weights));
+            pick(tag_vns, tag, rng)
+        }
+    }
+}
+
+// Sum of the edge weights of an outer row, or zero when the row is missing
+// or the weights were computed for different data
+fn total(edges: Option<(&CsMat<f64>, &Vec<f64>)>, outer: usize) -> f64 {
+    let Some((matrix, weights)) = edges else {
+        return 0.0;
+    };
+    if outer >= matrix.outer_dims() || weights.len() != matrix.nnz() {
+        return 0.0;
+    }
+    let range = matrix.indptr().outer_inds_sz(outer);
+    if range.is_empty() {
+        0.0
+    } else {
+        weights[range.end - 1]
+    }
+}
+
+// Pick an inner index of an outer row with probability proportional to its
+// edge weight
+fn pick(
+    edges: Option<(&CsMat<f64>, &Vec<f64>)>,
+    outer: usize,
+    rng: &mut XorShift,
+) -> Option<usize> {
+    let total = total(edges, outer);
+    if total <= 0.0 {
+        return None;
+    }
+    let (matrix, weights) = edges?;
+    let range = matrix.indptr().outer_inds_sz(outer);
+    let target = rng.next_f64() * total;
+    let offset = weights[range.clone()].partition_point(|sum| *sum <= target);
+    Some(matrix.indices()[(range.start + offset).min(range.end - 1)])
+}
+
+impl Scorer for RandomWalkScorer {
+    fn name(&self) -> &str {
+        RANDOM_WALK_SOURCE
+    }
+
+    // Precompute the running edge weights for sampling
+    fn fit(&mut self, data: &VisualNovelRecommender) -> Result<()> {
+        self.vn_user_weights = self.running_sums(&data.vn_users);
+        self.user_vn_weights = self.running_sums(&data.user_vns);
+        self.vn_tag_weights = self.running_sums(&data.similarity_matrix);
+        self.tag_vn_weights = self.running_sums(&data.tag_index);
+        Ok(())
+    }
+
+    fn score(
+        &self,
+        data: &VisualNovelRecommender,
+        seeds: &[(i32, f64)],
+        num_vns: usize,
+    ) -> HashMap<i32, f64> {
+        scoring::seed_scores(seeds, num_vns, |seeds| self.visits(data, seeds))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::RecommenderConfig;
+    use crate::source::{DataSource, DataSources};
+    use crate::test_data;
+
+    // Two components without tags: v1 - u1 - v2 - u2 - v3, with user 1's
+    // vote for v1 far below that for v2, and v6 - u3 - v7. v5's only vote is
+    // zero, which links it to no one, and v4 has no votes at all.
+    const VOTES: &str = "\
+1 1 10 2020-01-01
+2 1 90 2020-01-01
+2 2 50 2020-01-01
+3 2 50 2020-01-01
+6 3 50 2020-01-01
+7 3 50 2020-01-01
+5 4 0 2020-01-01
+";
+
+    fn data() -> VisualNovelRecommender {
+        let sources =
+            DataSources::new(DataSource::text(test_data::TITLES)).votes(DataSource::text(VOTES));
+        VisualNovelRecommender::from_sources(RecommenderConfig::default(), sources).unwrap()
+    }
+
+    fn walk_visits(
+        scorer: RandomWalkScorer,
+        data: &VisualNovelRecommender,
+        seeds: &[(i32, f64)],
+    ) -> HashMap<i32, f64> {
+        let mut scorer = scorer.walk_length(10_000);
+        scorer.fit(data).unwrap();
+        scorer.visits(data, seeds)
+    }
+
+    fn visit(visits: &HashMap<i32, f64>, vn_id: i32) -> f64 {
+        visits.get(&vn_id).copied().unwrap_or(0.0)
+    }
+
+    #[test]
+    fn walks_are_deterministic() {
+        let data = data();
+        let walk = |seed| walk_visits(RandomWalkScorer::new().seed(seed), &data, &[(1, 1.0)]);
+        assert_eq!(walk(7), walk(7));
+        assert_ne!(walk(7), walk(8));
+    }
+
+    #[test]
+    fn restart_probability() {
+        let data = data();
+        // Restarting after every step only reaches the VNs of v1's voters:
+        // v1 itself for 10 of user 1's 100 vote points, v2 for 90
+        let visits = walk_visits(
+            RandomWalkScorer::new().restart_probability(1.0),
+            &data,
+            &[(1, 1.0)],
+        );
+        assert_eq!(visits.len(), 2);
+        assert!((visit(&visits, 1) - 0.1).abs() < 0.02);
+        assert!((visit(&visits, 2) - 0.9).abs() < 0.02);
+
+        // Never restarting wanders on to v3 through user 2
+        let visits = walk_visits(
+            RandomWalkScorer::new().restart_probability(0.0),
+            &data,
+            &[(1, 1.0)],
+        );
+        assert!(visit(&visits, 3) > 0.1);
+        assert_eq!(visit(&visits, 6), 0.0);
+    }
+
+    #[test]
+    fn walk_length_is_shared_by_weight() {
+        let data = data();
+        // Each step visits one VN for total_weight / walk_length, and the
+        // seeds walk 7500 and 2500 steps in their own components
+        let visits = walk_visits(RandomWalkScorer::new(), &data, &[(1, 3.0), (6, -1.0)]);
+        assert!((visit(&visits, 1) + visit(&visits, 2) + visit(&visits, 3) - 3.0).abs() < 1e-9);
+        assert!((visit(&visits, 6) + visit(&visits, 7) + 1.0).abs() < 1e-9);
+        assert!(visit(&visits, 6) < 0.0 && visit(&visits, 7) < 0.0);
+
+        let mut scorer = RandomWalkScorer::new().walk_length(0);
+        scorer.fit(&data).unwrap();
+        assert!(scorer.visits(&data, &[(1, 1.0)]).is_empty());
+    }
+
+    #[test]
+    fn edge_weighting() {
+        let data = data();
+        // Without weights user 1 leads to v1 and v2 alike
+        let visits = walk_visits(
+            RandomWalkScorer::new()
+                .restart_probability(1.0)
+                .weighted_edges(false),
+            &data,
+            &[(1, 1.0)],
+        );
+        assert!((visit(&visits, 1) - 0.5).abs() < 0.02);
+        assert!((visit(&visits, 2) - 0.5).abs() < 0.02);
+
+        // Leaving only through tags, of which there are none, goes nowhere
+        let visits = walk_visits(RandomWalkScorer::new().vote_weight(0.0), &data, &[(1, 1.0)]);
+        assert!(visits.is_empty());
+    }
+
+    #[test]
+    fn dead_ends() {
+        let data = data();
+        // v4 has no voters and v5's single zero vote is no edge either
+        for vn_id in [4, 5, 99] {
+            assert!(walk_visits(RandomWalkScorer::new(), &data, &[(vn_id, 1.0)]).is_empty());
+        }
+        // Unweighted, v5's zero vote is still no edge
+        let scorer = RandomWalkScorer::new().weighted_edges(false);
+        assert!(walk_visits(scorer, &data, &[(5, 1.0)]).is_empty());
+
+        // A dead-end seed uses up its share of the steps without visits
+        let visits = walk_visits(RandomWalkScorer::new(), &data, &[(1, 1.0), (4, 1.0)]);
+        assert!((visits.values().sum::<f64>() - 1.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn seeds_are_left_out() {
+        let data = data();
+        let mut scorer = RandomWalkScorer::new().walk_length(1000);
+        scorer.fit(&data).unwrap();
+        let scores = scorer.score(&data, &[(1, 1.0), (2, 1.0)], 10);
+        assert_eq!(scores.keys().copied().collect::<Vec<_>>(), [3]);
+    }
+}
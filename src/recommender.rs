@@ -1,736 +1,753 @@
 // src/recommender.rs
-use crate::data::{Rating, Tag, VnTitle};
-use csv::ReaderBuilder;
+use crate::als::{ALS_SOURCE, Als, Embeddings};
+use crate::config::{
+    DEFAULT_IGNORE_TAGS, MAX_SPOILER, QueryOptions, RecommenderConfig, TagWeighting,
+};
+use crate::data::{Rating, Recommendation, RecommendationLists, Tag};
+use crate::error::{RecommenderError, Result};
+use crate::parser::{self, ParseReport, Parsed};
+use crate::scorer::{Scorer, TagScorer, VoteScorer};
+use crate::scoring;
+use crate::source::{DataSource, DataSources};
+use crate::tag_catalog::TagCatalog;
+use crate::tag_filter::CandidateFilter;
+use crate::tag_rules::{self, TagRule};
+use crate::tag_tree::TagTree;
+use crate::titles::TitleIndex;
 use sprs::{CsMat, TriMat};
 use std::{
-    collections::HashMap,
-    error::Error,
-    fs,
-    fs::File,
-    io::{BufRead, BufReader},
-    path::Path,
+    collections::{HashMap, HashSet},
     sync::Arc,
 };
 
 pub struct VisualNovelRecommender {
-    pub num_vns: usize,
-    pub tag_weight: f64,
-    pub vote_weight: f64,
-    pub tag_exp: f64,
-    pub vote_exp: f64,
-    pub ignore_tags: Vec<i32>,
-    pub verbose: bool,
-    pub skip_recs: bool,
+    pub config: RecommenderConfig,
     // Data structures
-    pub vn_titles: Vec<VnTitle>,
+    pub titles: TitleIndex,
     pub ratings: Vec<Rating>,
     pub average_ratings: HashMap<i32, f64>,
+    // Tag votes as in the dump, before the tag exponent
     pub tags: Vec<Tag>,
+    // Average tag vote of each VN on VNDB's scale of -3 to 3, VN x tag, over
+    // all votes regardless of spoiler level, rules and weighting; what the
+    // tag filters of a query check
+    pub tag_votes: Option<CsMat<f64>>,
+    // Tag metadata and hierarchy, when the dump tables were loaded
+    pub tag_catalog: TagCatalog,
+    pub tag_tree: TagTree,
     pub similarity_matrix: Option<CsMat<f64>>,
+    // The similarity matrix in CSC order (tag -> VNs) and its row norms,
+    // derived from it by build_tag_index
+    pub tag_index: Option<CsMat<f64>>,
+    pub tag_norms: Vec<f64>,
+    // Ratings indexed both ways: VN x user in CSR order (the users of each
+    // VN) and in CSC order (the VNs of each user), built by build_rating_index
+    pub vn_users: Option<CsMat<f64>>,
+    pub user_vns: Option<CsMat<f64>>,
+    // VN and user factor vectors trained by train_als, used by AlsScorer
+    pub embeddings: Option<Embeddings>,
+    // Scorers blended into the combined recommendations next to the tag and
+    // vote models, with their weights
+    pub scorers: Vec<(Box<dyn Scorer>, f64)>,
 }
 
 impl VisualNovelRecommender {
-    // Constructor and load_data are implemented as shown in previous response
-    pub fn new(
-        num_vns: usize,
-        tag_weight: f64,
-        vote_weight: f64,
-        tag_exp: f64,
-        vote_exp: f64,
-        ignore_tags: Vec<i32>,
-        verbose: bool,
-        skip_recs: bool,
-    ) -> Self {
-        let mut recommender = Self {
-            num_vns,
-            tag_weight,
-            vote_weight,
-            tag_exp,
-            vote_exp,
-            ignore_tags,
-            verbose,
-            skip_recs,
-            vn_titles: Vec::new(),
+    // Create a recommender without any data; use the load_* methods to fill it
+    pub fn empty(config: RecommenderConfig) -> Self {
+        Self {
+            config,
+            titles: TitleIndex::default(),
             ratings: Vec::new(),
             average_ratings: HashMap::new(),
             tags: Vec::new(),
+            tag_votes: None,
+            tag_catalog: TagCatalog::default(),
+            tag_tree: TagTree::default(),
             similarity_matrix: None,
-        };
-
-        recommender.load_data().unwrap();
-        recommender
+            tag_index: None,
+            tag_norms: Vec::new(),
+            vn_users: None,
+            user_vns: None,
+            embeddings: None,
+            scorers: Vec::new(),
+        }
     }
-    pub fn load_data(&mut self) -> Result<(), Box<dyn Error>> {
-        // On WASM, we don't load data automatically since file system is not available
+
+    // Load from the default `data/` directory. On WASM there is no file
+    // system, so the recommender starts out empty instead.
+    pub fn new(config: RecommenderConfig) -> Result<Self> {
         #[cfg(target_arch = "wasm32")]
         {
-            if self.verbose {
-                println!("WASM environment detected, skipping automatic data loading");
-            }
-            return Ok(());
+            Ok(Self::empty(config))
         }
 
-        // Load titles
-        if self.verbose {
-            println!("Loading titles");
+        #[cfg(not(target_arch = "wasm32"))]
+        {
+            let data_dir = std::path::Path::new("data");
+            let sources = if config.skip_recs {
+                DataSources::new(DataSource::path(data_dir.join("vn_titles")))
+            } else {
+                DataSources::from_dir(data_dir)?
+            };
+            Self::from_sources(config, sources)
         }
+    }
 
-        let file = File::open("data/vn_titles")?;
-        let reader = BufReader::new(file);
-        let mut vn_titles = Vec::new();
-
-        for line in reader.lines() {
-            let line = line?;
-            let parts: Vec<&str> = line.split('\t').collect();
-            if parts.len() >= 5 {
-                let vn_id_str = parts[0].trim_start_matches('v');
-                let vn_id = match vn_id_str.parse::<i32>() {
-                    Ok(id) => id,
-                    Err(_) => continue, // Skip invalid IDs
-                };
-
-                let language = parts[1].to_string();
-                let official = parts[2] == "t";
-                let title = parts[3].to_string();
-                let latin_title = if parts[4] == "\\N" {
-                    None
-                } else {
-                    Some(parts[4].to_string())
-                };
-
-                vn_titles.push(VnTitle {
-                    vn_id,
-                    language: language.into(),
-                    official,
-                    title: title.into(),
-                    latin_title: latin_title.map(|x| x.into()),
-                });
-            }
+    pub fn from_sources(config: RecommenderConfig, sources: DataSources) -> Result<Self> {
+        let mut recommender = Self::empty(config);
+        recommender.load_sources(sources)?;
+        Ok(recommender)
+    }
+
+    pub fn load_sources(&mut self, sources: DataSources) -> Result<()> {
+        // Only take from the tarball what was not given as a separate file
+        if let Some(db_dump) = sources.db_dump {
+            let want_titles = sources.titles.is_none();
+            let want_tags = sources.tags.is_none() && !self.config.skip_recs;
+            let want_tag_info = sources.tag_info.is_none() && !self.config.skip_recs;
+            let want_tag_parents = sources.tag_parents.is_none() && !self.config.skip_recs;
+            self.load_db_dump_entries(
+                db_dump,
+                want_titles,
+                want_tags,
+                want_tag_info,
+                want_tag_parents,
+            )?;
         }
 
-        self.vn_titles = vn_titles;
+        if let Some(titles) = sources.titles {
+            self.load_titles(titles)?;
+        }
 
-        if self.skip_recs {
-            return Ok(());
+        if self.titles.is_empty() {
+            return Err(RecommenderError::EmptyDataset("vn_titles"));
         }
 
-        // Load ratings
-        if self.verbose {
-            println!("Loading votes");
+        if self.config.skip_recs {
+            return Ok(());
         }
 
-        // Find the latest votes file
-        let data_dir = Path::new("data");
-        let mut votes_files: Vec<_> = fs::read_dir(data_dir)?
-            .filter_map(Result::ok)
-            .filter(|entry| {
-                entry
-                    .file_name()
-                    .to_str()
-                    .is_some_and(|name| name.starts_with("vndb-votes-"))
-            })
-            .collect();
+        if let Some(votes) = sources.votes {
+            self.load_votes(votes)?;
+        }
 
-        // Sort files by date in filename (descending) to get the latest
-        votes_files.sort_by_key(|entry| {
-            entry.file_name().to_str().unwrap_or("").to_string() // Convert to String to allow for sorting
-        });
-        votes_files.reverse(); // sort_by_key sorts ascending by default
-
-        let votes_file_path = votes_files
-            .first()
-            .map(|entry| entry.path())
-            .ok_or("No votes file found matching pattern 'vndb-votes-*'")?;
-
-        if self.verbose {
-            println!("Using votes file: {:?}", votes_file_path);
-        }
-
-        let file = File::open(votes_file_path)?;
-        let reader = BufReader::new(file);
-        let mut ratings = Vec::new();
-
-        for line in reader.lines() {
-            let line = line?;
-            let parts: Vec<&str> = line.split_whitespace().collect();
-            if parts.len() >= 4 {
-                let vn_id = match parts[0].parse::<i32>() {
-                    Ok(id) => id,
-                    Err(_) => continue,
-                };
-
-                let user_id = match parts[1].parse::<i32>() {
-                    Ok(id) => id,
-                    Err(_) => continue,
-                };
-
-                let raw_rating = match parts[2].parse::<f64>() {
-                    Ok(r) => r,
-                    Err(_) => continue,
-                };
-
-                // Apply the vote exponential transformation
-                let rating = raw_rating.signum() * raw_rating.abs().powf(self.vote_exp);
-
-                let date = parts[3].to_string();
-
-                ratings.push(Rating {
-                    vn_id,
-                    user_id,
-                    rating,
-                    date: date.into(),
-                });
-            }
+        // The tag catalog and hierarchy are needed before the tag matrix is
+        // built
+        if let Some(tag_info) = sources.tag_info {
+            self.load_tag_info(tag_info)?;
+        }
+        if let Some(tag_parents) = sources.tag_parents {
+            self.load_tag_parents(tag_parents)?;
         }
 
-        self.ratings = ratings;
+        if let Some(tags) = sources.tags {
+            self.load_tags(tags)?;
+        }
 
-        // Calculate average ratings
-        if self.verbose {
-            println!("Calculating average ratings");
+        if self.config.verbose {
+            println!("Loading complete.");
+            println!();
         }
 
-        let mut rating_sums: HashMap<i32, f64> = HashMap::new();
-        let mut rating_counts: HashMap<i32, i32> = HashMap::new();
+        Ok(())
+    }
 
-        for rating in &self.ratings {
-            *rating_sums.entry(rating.vn_id).or_insert(0.0) += rating.rating;
-            *rating_counts.entry(rating.vn_id).or_insert(0) += 1;
+    pub fn load_titles(&mut self, source: DataSource) -> Result<ParseReport> {
+        if self.config.verbose {
+            println!("Loading titles");
         }
 
-        self.average_ratings = rating_sums
-            .iter()
-            .map(|(vn_id, sum)| {
-                let count = *rating_counts.get(vn_id).unwrap_or(&1) as f64;
-                (*vn_id, sum / count)
-            })
-            .collect();
+        let (path, reader) = source.open("vn_titles")?;
+        let Parsed { records, report } = parser::parse_titles(reader, &path)?;
+        self.log_report(&report);
 
-        // Load tag data
-        if self.verbose {
-            println!("Loading tags_vn");
+        if records.is_empty() {
+            return Err(RecommenderError::EmptyDataset("vn_titles"));
         }
+        self.titles = TitleIndex::new(records);
 
-        let mut reader = ReaderBuilder::new()
-            .delimiter(b'\t')
-            .has_headers(false)
-            .from_path("data/tags_vn")?;
-
-        let mut tags = Vec::new();
+        Ok(report)
+    }
 
-        while let Some(result) = reader.records().next() {
-            let record = result?;
-            if record.len() >= 5 {
-                let tag_id_str = &record[1][1..];
-                let tag_id = match tag_id_str.parse::<i32>() {
-                    Ok(id) => id,
-                    Err(_) => continue,
-                };
+    pub fn load_votes(&mut self, source: DataSource) -> Result<ParseReport> {
+        if self.config.verbose {
+            println!("Loading votes");
+        }
 
-                let vn_id_str = &record[2][1..];
-                let vn_id = match vn_id_str.parse::<i32>() {
-                    Ok(id) => id,
-                    Err(_) => continue,
-                };
+        let (path, reader) = source.open("votes")?;
+        if self.config.verbose {
+            println!("Using votes file: {:?}", path);
+        }
 
-                let raw_rating = match record[4].parse::<f64>() {
-                    Ok(r) => r,
-                    Err(_) => continue,
-                };
+        let Parsed {
+            mut records,
+            report,
+        } = parser::parse_votes(reader, &path)?;
+        self.log_report(&report);
 
-                // Skip ignored tags
-                if self.ignore_tags.contains(&tag_id) {
-                    continue;
-                }
+        if records.is_empty() {
+            return Err(RecommenderError::EmptyDataset("votes"));
+        }
 
-                // Apply tag exponential transformation
-                let rating = raw_rating.signum() * raw_rating.abs().powf(self.tag_exp);
+        // Apply the vote exponential transformation
+        for rating in &mut records {
+            rating.rating = rating.rating.signum() * rating.rating.abs().powf(self.config.vote_exp);
+        }
+        self.ratings = records;
 
-                tags.push(Tag {
-                    tag_id,
-                    vn_id,
-                    rating,
-                });
-            }
+        // Calculate average ratings
+        if self.config.verbose {
+            println!("Calculating average ratings");
         }
 
-        self.tags = tags;
+        self.calculate_average_ratings();
+        self.build_rating_index();
+        // Embeddings of the previous votes no longer match
+        self.embeddings = None;
+        self.refit_scorers()?;
+
+        Ok(report)
+    }
 
-        // Calculate average vote for each tag for each VN
-        if self.verbose {
-            println!("Building average tags");
+    pub fn load_tags(&mut self, source: DataSource) -> Result<ParseReport> {
+        let report = self.read_tags(source)?;
+        self.build_tag_model()?;
+        Ok(report)
+    }
+
+    fn read_tags(&mut self, source: DataSource) -> Result<ParseReport> {
+        if self.config.verbose {
+            println!("Loading tags_vn");
         }
 
-        let mut tag_sums: HashMap<(i32, i32), f64> = HashMap::new();
-        let mut tag_counts: HashMap<(i32, i32), i32> = HashMap::new();
+        let (path, reader) = source.open("tags_vn")?;
+        let Parsed { records, report } = parser::parse_tags(reader, &path)?;
+        self.log_report(&report);
 
-        for tag in &self.tags {
-            let key = (tag.vn_id, tag.tag_id);
-            *tag_sums.entry(key).or_insert(0.0) += tag.rating;
-            *tag_counts.entry(key).or_insert(0) += 1;
+        // Ignored tags are kept here and left out when the matrix is built,
+        // as tag rules may need the tag catalog loaded after this. The
+        // exponent is applied then too, as the tag filters need the votes
+        // as given.
+        if records.is_empty() {
+            return Err(RecommenderError::EmptyDataset("tags_vn"));
         }
+        self.tags = records;
 
-        let average_tag_votes: Vec<(i32, i32, f64)> = tag_sums
-            .iter()
-            .map(|((vn_id, tag_id), sum)| {
-                let count = *tag_counts.get(&(*vn_id, *tag_id)).unwrap_or(&1) as f64;
-                let avg = sum / count;
-                (*vn_id, *tag_id, avg)
-            })
-            .filter(|(_, _, rating)| *rating != 0.0) // Filter out zero ratings
-            .collect();
+        Ok(report)
+    }
 
-        // Create a sparse matrix for VN x tag
-        if self.verbose {
+    fn build_tag_model(&mut self) -> Result<()> {
+        // Calculate average vote for each tag for each VN
+        if self.config.verbose {
+            let ignored: Vec<String> = self
+                .ignore_tag_rules()
+                .iter()
+                .filter_map(|rule| rule.tag.as_deref())
+                .filter_map(|tag| tag_rules::find_tag(tag, &self.tag_catalog).ok())
+                .map(|tag_id| match self.tag_catalog.get(tag_id) {
+                    Some(tag) => format!("{} (g{})", tag.name, tag_id),
+                    None => format!("g{}", tag_id),
+                })
+                .collect();
+            if !ignored.is_empty() {
+                println!("Ignoring tags: {}", ignored.join(", "));
+            }
+            if self.config.ignore_tags.is_none() && !self.tag_catalog.is_empty() {
+                for (name, tag_id) in DEFAULT_IGNORE_TAGS {
+                    if self.tag_catalog.find(name).map(|tag| tag.tag_id) != Some(tag_id) {
+                        println!(
+                            "The tags table has no '{}' as g{}; ignoring g{}",
+                            name, tag_id, tag_id
+                        );
+                    }
+                }
+            }
+            println!("Building average tags");
             println!("Calculating tag similarity matrix.");
         }
 
-        // Find max VN and tag IDs to size the matrix
-        let max_vn_id = average_tag_votes
-            .iter()
-            .map(|(vn_id, _, _)| *vn_id)
-            .max()
-            .unwrap_or(0) as usize;
-        let max_tag_id = average_tag_votes
-            .iter()
-            .map(|(_, tag_id, _)| *tag_id)
-            .max()
-            .unwrap_or(0) as usize;
-
-        // Create triplet matrix
-        let mut triplet_matrix = TriMat::new((max_vn_id + 1, max_tag_id + 1));
+        self.build_tag_votes();
+        self.build_similarity_matrix()?;
 
-        // Fill the matrix
-        for (vn_id, tag_id, rating) in average_tag_votes {
-            triplet_matrix.add_triplet(vn_id as usize, tag_id as usize, rating);
+        if self.config.verbose {
+            println!("Similarity matrix computed.");
         }
 
-        // Convert to CSR format
-        let data_sparse = triplet_matrix.to_csr();
+        self.refit_scorers()
+    }
 
-        self.similarity_matrix = Some(data_sparse);
+    // Load the tag catalog (names, categories and default spoiler levels)
+    // from the `tags` table of the dump. Tags that are already loaded are
+    // rebuilt to take it into account.
+    pub fn load_tag_info(&mut self, source: DataSource) -> Result<ParseReport> {
+        let report = self.read_tag_info(source)?;
+        if !self.tags.is_empty() {
+            self.build_tag_model()?;
+        }
+        Ok(report)
+    }
 
-        if self.verbose {
-            println!("Similarity matrix computed.");
-            println!("Loading complete.");
-            println!();
+    fn read_tag_info(&mut self, source: DataSource) -> Result<ParseReport> {
+        if self.config.verbose {
+            println!("Loading tags");
         }
 
-        Ok(())
+        let (path, reader) = source.open("tags")?;
+        let Parsed { records, report } = parser::parse_tag_info(reader, &path)?;
+        self.log_report(&report);
+
+        self.tag_catalog = TagCatalog::new(records);
+
+        Ok(report)
     }
-    pub fn get_average_rating(&self, vn_id: i32) -> Result<f64, String> {
-        match self.average_ratings.get(&vn_id) {
-            Some(rating) => Ok(*rating),
-            None => Err("No ratings available for this VN.".to_string()),
+
+    // Load the tag hierarchy from the `tags_parents` table of the dump. Tags
+    // that are already loaded are rebuilt to take it into account.
+    pub fn load_tag_parents(&mut self, source: DataSource) -> Result<ParseReport> {
+        let report = self.read_tag_parents(source)?;
+        if !self.tags.is_empty() {
+            self.build_tag_model()?;
         }
+        Ok(report)
     }
 
-    pub fn get_last_vn_id(&self) -> Option<i32> {
-        self.vn_titles.iter().map(|vn| vn.vn_id).max()
+    fn read_tag_parents(&mut self, source: DataSource) -> Result<ParseReport> {
+        if self.config.verbose {
+            println!("Loading tags_parents");
+        }
+
+        let (path, reader) = source.open("tags_parents")?;
+        let Parsed { records, report } = parser::parse_tag_parents(reader, &path)?;
+        self.log_report(&report);
+
+        self.tag_tree = TagTree::new(records);
+
+        Ok(report)
     }
 
-    pub fn get_title(&self, vn_id: i32) -> Arc<str> {
-        // Get titles for this VN ID
-        let vn_data: Vec<&VnTitle> = self
-            .vn_titles
-            .iter()
-            .filter(|vn| vn.vn_id == vn_id)
-            .collect();
+    // Load `vn_titles` and `tags_vn` straight out of a VNDB database dump
+    // tarball (`vndb-db-*.tar.zst`) without unpacking it
+    pub fn load_db_dump(&mut self, source: DataSource) -> Result<Vec<ParseReport>> {
+        let want_tags = !self.config.skip_recs;
+        self.load_db_dump_entries(source, true, want_tags, want_tags, want_tags)
+    }
 
-        if vn_data.is_empty() {
-            return format!("v{}", vn_id).into();
+    fn load_db_dump_entries(
+        &mut self,
+        source: DataSource,
+        mut want_titles: bool,
+        mut want_tags: bool,
+        mut want_tag_info: bool,
+        mut want_tag_parents: bool,
+    ) -> Result<Vec<ParseReport>> {
+        if self.config.verbose {
+            println!("Reading database dump");
         }
 
-        // Check for English title
-        if let Some(en_title) = vn_data
-            .iter()
-            .find(|vn| vn.language == "en".into())
-            .map(|vn| &vn.title)
-        {
-            return en_title.clone();
-        }
+        let (path, reader) = source.open("db")?;
+        let mut archive = tar::Archive::new(reader);
+        let entries = archive
+            .entries()
+            .map_err(|e| RecommenderError::io(&path, e))?;
+        let mut reports = Vec::new();
+        // The tag metadata and hierarchy are optional, and may come after
+        // tags_vn, so the tag matrix is built once all are read
+        let mut read_tags = false;
+
+        for entry in entries {
+            if !want_titles && !want_tags && !want_tag_info && !want_tag_parents {
+                break;
+            }
 
-        // Check for Latin Japanese title
-        if let Some(jp_latin_title) = vn_data
-            .iter()
-            .find(|vn| vn.language == "ja".into() && vn.latin_title.is_some())
-            .and_then(|vn| vn.latin_title.as_ref())
-        {
-            if jp_latin_title != &"\\N".into() {
-                return jp_latin_title.clone();
+            let entry = entry.map_err(|e| RecommenderError::io(&path, e))?;
+            let entry_path = entry
+                .path()
+                .map_err(|e| RecommenderError::io(&path, e))?
+                .into_owned();
+
+            // Entries are stored as `db/<table>`, next to `db/<table>.header`
+            match entry_path.file_name().and_then(|name| name.to_str()) {
+                Some("vn_titles") if want_titles => {
+                    reports.push(self.load_titles(DataSource::reader(entry))?);
+                    want_titles = false;
+                }
+                Some("tags_vn") if want_tags => {
+                    reports.push(self.read_tags(DataSource::reader(entry))?);
+                    want_tags = false;
+                    read_tags = true;
+                }
+                Some("tags") if want_tag_info => {
+                    reports.push(self.read_tag_info(DataSource::reader(entry))?);
+                    want_tag_info = false;
+                }
+                Some("tags_parents") if want_tag_parents => {
+                    reports.push(self.read_tag_parents(DataSource::reader(entry))?);
+                    want_tag_parents = false;
+                }
+                _ => {}
             }
         }
 
-        // Check for Japanese title
-        if let Some(jp_title) = vn_data
-            .iter()
-            .find(|vn| vn.language == "ja".into())
-            .map(|vn| &vn.title)
-        {
-            return jp_title.clone();
+        if want_titles {
+            return Err(RecommenderError::MissingFile(path.join("db/vn_titles")));
         }
-
-        // Check for official Latin title
-        if let Some(official_latin_title) = vn_data
-            .iter()
-            .find(|vn| vn.official && vn.latin_title.is_some())
-            .and_then(|vn| vn.latin_title.as_ref())
-        {
-            if official_latin_title != &"\\N".into() {
-                return official_latin_title.clone();
-            }
+        if want_tags {
+            return Err(RecommenderError::MissingFile(path.join("db/tags_vn")));
         }
-
-        // Check for official title
-        if let Some(official_title) = vn_data.iter().find(|vn| vn.official).map(|vn| &vn.title) {
-            return official_title.clone();
+        if read_tags {
+            self.build_tag_model()?;
         }
 
-        format!("v{}", vn_id).into()
+        Ok(reports)
     }
 
-    // Helper function for min-max normalization
-    fn min_max_normalize(&self, scores: &mut HashMap<i32, f64>) {
-        if scores.is_empty() {
-            return;
+    fn log_report(&self, report: &ParseReport) {
+        if self.config.verbose && report.total_skipped() > 0 {
+            println!("{}", report);
         }
+    }
 
-        // Find min and max values
-        let values: Vec<f64> = scores.values().cloned().collect();
-        let min_val = values
-            .iter()
-            .min_by(|a, b| a.partial_cmp(b).unwrap())
-            .unwrap_or(&0.0);
-        let max_val = values
-            .iter()
-            .max_by(|a, b| a.partial_cmp(b).unwrap())
-            .unwrap_or(&1.0);
-
-        // Avoid division by zero
-        let range = max_val - min_val;
-        if range.abs() < f64::EPSILON {
-            // If all values are the same, set them all to 1.0
-            for val in scores.values_mut() {
-                *val = 1.0;
+    pub fn get_average_rating(&self, vn_id: i32) -> Result<f64> {
+        match self.average_ratings.get(&vn_id) {
+            Some(rating) => Ok(*rating),
+            None => {
+                self.ensure_known(vn_id)?;
+                Err(RecommenderError::NoRatings(vn_id))
             }
-            return;
         }
+    }
 
-        // Apply normalization
-        for val in scores.values_mut() {
-            *val = (*val - *min_val) / range;
+    // Fail with UnknownVn if the id appears neither in the titles nor in the votes
+    pub fn ensure_known(&self, vn_id: i32) -> Result<()> {
+        if self.titles.contains(vn_id) || self.average_ratings.contains_key(&vn_id) {
+            Ok(())
+        } else {
+            Err(RecommenderError::UnknownVn(vn_id))
         }
     }
 
-    pub fn get_user_recommendations_scores(&self, vn_id: i32) -> HashMap<i32, f64> {
-        // Find users who rated this VN
-        let users_who_rated: Vec<i32> = self
-            .ratings
-            .iter()
-            .filter(|rating| rating.vn_id == vn_id)
-            .map(|rating| rating.user_id)
-            .collect();
+    pub fn get_last_vn_id(&self) -> Option<i32> {
+        self.titles.last_vn_id()
+    }
 
-        // Find VNs these users rated
-        let mut similar_vns: HashMap<i32, (f64, usize)> = HashMap::new();
+    // Display title in the configured languages, or `v<id>` if there is none
+    pub fn get_title(&self, vn_id: i32) -> Arc<str> {
+        self.get_title_in(
+            vn_id,
+            &self.config.title_languages,
+            self.config.romanized_titles,
+        )
+    }
 
-        for rating in self.ratings.iter() {
-            if users_who_rated.contains(&rating.user_id) && rating.vn_id != vn_id {
-                let entry = similar_vns.entry(rating.vn_id).or_insert((0.0, 0));
-                entry.0 += rating.rating;
-                entry.1 += 1;
-            }
-        }
+    // Display title in the first of `languages` the VN has a title in
+    pub fn get_title_in(&self, vn_id: i32, languages: &[String], romanized: bool) -> Arc<str> {
+        self.titles
+            .display_title(vn_id, languages, romanized)
+            .unwrap_or_else(|| format!("v{}", vn_id).into())
+    }
 
-        // Calculate average and scores
-        let scores: HashMap<i32, f64> = similar_vns
-            .iter()
-            .map(|(vn_id, (total_rating, count))| {
-                let avg_rating = total_rating / *count as f64;
-                (*vn_id, avg_rating * *count as f64)
-            })
-            .collect();
+    // Scores of the vote model for weighted seed VNs; seeds with a negative
+    // weight lower the scores of VNs their co-raters liked
+    pub fn get_user_recommendations_scores(&self, seeds: &[(i32, f64)]) -> HashMap<i32, f64> {
+        let seeds = self.query_seeds(seeds);
+        VoteScorer.score(self, &seeds, self.config.num_vns)
+    }
 
-        // Get top N VNs by score
-        let mut score_vec: Vec<(i32, f64)> = scores.into_iter().collect();
-        score_vec.sort_by(|a, b| b.1.partial_cmp(&a.1).unwrap());
+    // Scores of the tag model for weighted seed VNs; seeds with a negative
+    // weight subtract their tag similarity
+    pub fn get_tag_recommendations_score(&self, seeds: &[(i32, f64)]) -> HashMap<i32, f64> {
+        let seeds = self.query_seeds(seeds);
+        TagScorer::default().score(self, &seeds, self.config.num_vns)
+    }
 
-        score_vec.truncate(self.num_vns);
+    pub fn get_combined_recommendations_score(&self, seeds: &[(i32, f64)]) -> HashMap<i32, f64> {
+        let seeds = self.query_seeds(seeds);
+        let filter = CandidateFilter::default();
+        self.blend(
+            &self.combined_scorers(&TagScorer::default()),
+            &seeds,
+            &filter,
+        )
+        .1
+    }
 
-        // Convert to HashMap with VN IDs as keys
-        let mut scores: HashMap<i32, f64> = score_vec.clone().into_iter().collect();
+    // Scale the weight of negative seeds by the configured strength
+    fn query_seeds(&self, seeds: &[(i32, f64)]) -> Vec<(i32, f64)> {
+        scoring::scale_negative_seeds(seeds, self.config.negative_seed_strength)
+    }
 
-        // Apply min-max normalization
-        self.min_max_normalize(&mut scores);
+    pub fn get_user_recommendations(&self, vn_id: i32) -> Result<Vec<Recommendation>> {
+        self.get_recommendations_with(&[(&VoteScorer, 1.0)], &[(vn_id, 1.0)])
+    }
 
-        scores
+    pub fn get_tag_recommendations(&self, vn_id: i32) -> Result<Vec<Recommendation>> {
+        self.get_recommendations_with(&[(&TagScorer::default(), 1.0)], &[(vn_id, 1.0)])
     }
 
-    pub fn get_tag_recommendations_score(&self, vn_id: i32) -> HashMap<i32, f64> {
-        // Check if we have a data matrix
-        let data_matrix = match &self.similarity_matrix {
-            Some(matrix) => matrix,
-            None => return HashMap::new(),
-        };
+    // Blend of the tag and vote models and any scorers added with add_scorer
+    pub fn get_combined_recommendations(&self, vn_id: i32) -> Result<Vec<Recommendation>> {
+        let tag_scorer = TagScorer::default();
+        self.get_recommendations_with(&self.combined_scorers(&tag_scorer), &[(vn_id, 1.0)])
+    }
 
-        // Find the row corresponding to the given VN
-        let row_idx = vn_id as usize;
-        if row_idx >= data_matrix.rows() {
-            return HashMap::new();
-        }
+    // Tag, vote and combined recommendations, and a list for each scorer
+    // added with add_scorer, for several seed VNs at once, given as
+    // (vn_id, weight) pairs. Each model adds up its per-seed scores
+    // times the seed weights, and the seeds are left out of the results.
+    // Seeds with a negative weight mean "less like this".
+    pub fn recommend_for_seeds(&self, seeds: &[(i32, f64)]) -> Result<RecommendationLists> {
+        self.recommend_for_seeds_with(seeds, &QueryOptions::default())
+    }
 
-        // Get the row for this VN
-        let vn_row = match data_matrix.outer_view(row_idx) {
-            Some(row) => row,
-            None => return HashMap::new(),
-        };
+    // Resolve tag rules given with a query against the tag catalog and
+    // hierarchy, into a tag scorer that applies them. Their weights multiply
+    // the weights the tag matrix was built with: `Mystery=1` keeps a tag the
+    // config boosts at that boost, and tags the config ignores stay ignored.
+    pub fn tag_scorer(&self, rules: &[TagRule]) -> Result<TagScorer> {
+        Ok(TagScorer {
+            tag_weights: tag_rules::resolve(rules, &self.tag_catalog, &self.tag_tree)?,
+        })
+    }
 
-        // Calculate cosine similarities with other VNs on-demand
-        let mut similarities = Vec::new();
+    // recommend_for_seeds with per-query options such as tag rules and tag
+    // filters
+    pub fn recommend_for_seeds_with(
+        &self,
+        seeds: &[(i32, f64)],
+        options: &QueryOptions,
+    ) -> Result<RecommendationLists> {
+        let tag_scorer = self.tag_scorer(&options.tag_rules)?;
+        let filter = options
+            .tag_filter
+            .resolve(&self.tag_catalog, &self.tag_tree)?;
+        let recommend = |scorers: &[(&dyn Scorer, f64)]| self.recommend(scorers, seeds, &filter);
+        Ok(RecommendationLists {
+            tag: recommend(&[(&tag_scorer, 1.0)])?,
+            vote: recommend(&[(&VoteScorer, 1.0)])?,
+            combined: recommend(&self.combined_scorers(&tag_scorer))?,
+            tag_weighting: self.config.tag_weighting,
+            extra: self
+                .scorers
+                .iter()
+                .map(|(scorer, _)| {
+                    let list = recommend(&[(scorer.as_ref(), 1.0)])?;
+                    Ok((scorer.name().to_string(), list))
+                })
+                .collect::<Result<_>>()?,
+        })
+    }
 
-        // Get the magnitude of the current VN vector for cosine similarity
-        let vn_magnitude = (vn_row.iter().map(|(_, val)| val * val).sum::<f64>()).sqrt();
-        if vn_magnitude == 0.0 {
-            return HashMap::new();
+    // The votes of a VNDB user as (vn_id, weight) seeds, each weighted by how
+    // far the vote lies above or below the user's mean vote
+    pub fn user_profile(&self, user_id: i32) -> Result<Vec<(i32, f64)>> {
+        let profile = match &self.user_vns {
+            Some(user_vns) => scoring::user_profile(user_vns.view(), user_id),
+            None => Vec::new(),
+        };
+        if profile.is_empty() {
+            return Err(RecommenderError::UnknownUser(user_id));
         }
+        Ok(profile)
+    }
 
-        // Calculate similarities only for a subset of VNs (e.g., 10000 most popular)
-        let top_vns: Vec<usize> = self
-            .tags
-            .iter()
-            .map(|t| t.vn_id as usize)
-            .filter(|&id| id != row_idx && id < data_matrix.rows())
-            .collect::<std::collections::HashSet<_>>()
-            .into_iter()
-            .collect();
-
-        for other_id in top_vns {
-            if let Some(other_row) = data_matrix.outer_view(other_id) {
-                let other_magnitude =
-                    (other_row.iter().map(|(_, val)| val * val).sum::<f64>()).sqrt();
-                if other_magnitude == 0.0 {
-                    continue;
-                }
-
-                // Calculate dot product
-                let mut dot_product = 0.0;
-                for (idx1, val1) in vn_row.iter() {
-                    for (idx2, val2) in other_row.iter() {
-                        if idx1 == idx2 {
-                            dot_product += val1 * val2;
-                        }
-                    }
-                }
+    // Personal recommendations for a user in the loaded votes, from their
+    // profile. Everything the user voted on is left out of the results.
+    pub fn recommend_for_user(&self, user_id: i32) -> Result<RecommendationLists> {
+        self.recommend_for_seeds(&self.user_profile(user_id)?)
+    }
 
-                let similarity = dot_product / (vn_magnitude * other_magnitude);
-                similarities.push((other_id, similarity));
-            }
+    // An imported vote list of (vn_id, vote) pairs, e.g. from
+    // parser::parse_vote_list, as seeds in the form of user_profile.
+    // `vote_scale` is the top of the scale the list votes on: 100 for VNDB's
+    // XML and JSON exports, 10 for a list of 1-10 votes. Votes for VNs
+    // missing from the loaded data are dropped.
+    pub fn vote_list_profile(
+        &self,
+        votes: &[(i32, f64)],
+        vote_scale: f64,
+    ) -> Result<Vec<(i32, f64)>> {
+        scoring::check_vote_scale(vote_scale)?;
+        let profile =
+            scoring::vote_list_profile(votes, vote_scale, self.config.vote_exp, |vn_id| {
+                self.ensure_known(vn_id).is_ok()
+            });
+        if profile.is_empty() {
+            return Err(RecommenderError::EmptyDataset("vote list"));
         }
-
-        // Sort by similarity score in descending order
-        similarities.sort_by(|a, b| b.1.partial_cmp(&a.1).unwrap_or(std::cmp::Ordering::Equal));
-
-        // Take top N items
-        similarities.truncate(self.num_vns);
-
-        // Convert to HashMap with VN IDs as keys
-        let mut scores: HashMap<i32, f64> = similarities
-            .into_iter()
-            .map(|(idx, score)| (idx as i32, score))
-            .collect();
-
-        // Apply min-max normalization
-        self.min_max_normalize(&mut scores);
-
-        scores
+        Ok(profile)
     }
 
-    pub fn get_combined_recommendations_score(&self, vn_id: i32) -> HashMap<i32, f64> {
-        // Get recommendations from both models
-        let user_recs = self.get_user_recommendations_scores(vn_id);
-        let tag_recs = self.get_tag_recommendations_score(vn_id);
+    // Personal recommendations from an imported vote list, for users who are
+    // not in the votes dump or have voted since it was made
+    pub fn recommend_for_vote_list(
+        &self,
+        votes: &[(i32, f64)],
+        vote_scale: f64,
+    ) -> Result<RecommendationLists> {
+        self.recommend_for_seeds(&self.vote_list_profile(votes, vote_scale)?)
+    }
 
-        // Combine scores with weights
-        let mut combined_scores: HashMap<i32, f64> = HashMap::new();
+    // Rank by the weighted sum of the scores of any list of fitted scorers
+    pub fn get_recommendations_with(
+        &self,
+        scorers: &[(&dyn Scorer, f64)],
+        seeds: &[(i32, f64)],
+    ) -> Result<Vec<Recommendation>> {
+        self.recommend(scorers, seeds, &CandidateFilter::default())
+    }
 
-        // Add user-based recommendations
-        for (id, score) in user_recs {
-            *combined_scores.entry(id).or_insert(0.0) += score * self.vote_weight;
+    // get_recommendations_with, leaving out the candidates a tag filter
+    // rejects
+    fn recommend(
+        &self,
+        scorers: &[(&dyn Scorer, f64)],
+        seeds: &[(i32, f64)],
+        filter: &CandidateFilter,
+    ) -> Result<Vec<Recommendation>> {
+        if !seeds.iter().any(|(_, weight)| *weight > 0.0) {
+            return Err(RecommenderError::EmptyQuery);
         }
-
-        // Add tag-based recommendations
-        for (id, score) in tag_recs {
-            *combined_scores.entry(id).or_insert(0.0) += score * self.tag_weight;
+        for (vn_id, _) in seeds {
+            self.ensure_known(*vn_id)?;
         }
 
-        // Sort and take top N
-        let mut score_vec: Vec<(i32, f64)> = combined_scores.into_iter().collect();
-        score_vec.sort_by(|a, b| b.1.partial_cmp(&a.1).unwrap());
-        score_vec.truncate(self.num_vns);
-
-        score_vec.into_iter().collect()
+        let seeds = self.query_seeds(seeds);
+        let (scores, combined) = self.blend(scorers, &seeds, filter);
+        let sources: Vec<(&str, &HashMap<i32, f64>)> = scorers
+            .iter()
+            .zip(&scores)
+            .map(|((scorer, _), scores)| (scorer.name(), scores))
+            .collect();
+        Ok(scoring::ranked(&combined, &sources, self.config.num_vns))
     }
 
-    pub fn resize_list(&self, mut list: Vec<i32>) -> Vec<i32> {
-        // Pad with zeros if too short
-        if list.len() < self.num_vns {
-            list.resize(self.num_vns, 0);
-        }
+    // Scores of each scorer and their weighted sum. With a tag filter the
+    // scorers rank every candidate, so that the filter still leaves num_vns.
+    fn blend(
+        &self,
+        scorers: &[(&dyn Scorer, f64)],
+        seeds: &[(i32, f64)],
+        filter: &CandidateFilter,
+    ) -> (Vec<HashMap<i32, f64>>, HashMap<i32, f64>) {
+        let num_vns = self.config.num_vns;
+        let allows = |vn_id: i32| self.allows(filter, vn_id);
+        let scores: Vec<HashMap<i32, f64>> = scorers
+            .iter()
+            .map(|(scorer, _)| {
+                if filter.is_empty() {
+                    scorer.score(self, seeds, num_vns)
+                } else {
+                    let scores = scorer.score(self, seeds, usize::MAX);
+                    scoring::filter_scores(scores, num_vns, allows)
+                }
+            })
+            .collect();
+        let weighted: Vec<(&HashMap<i32, f64>, f64)> = scores
+            .iter()
+            .zip(scorers)
+            .map(|(scores, (_, weight))| (scores, *weight))
+            .collect();
+        let combined = scoring::combine_scores(&weighted, self.config.num_vns);
 
-        // Truncate if too long
-        if list.len() > self.num_vns {
-            list.truncate(self.num_vns);
-        }
+        (scores, combined)
+    }
 
-        list
+    // Whether a tag filter lets a VN through, by its average tag votes
+    fn allows(&self, filter: &CandidateFilter, vn_id: i32) -> bool {
+        let vn_tags = self
+            .tag_votes
+            .as_ref()
+            .and_then(|matrix| matrix.outer_view(vn_id as usize));
+        filter.allows(vn_tags)
     }
 
-    pub fn get_user_recommendations(&self, vn_id: i32) -> Vec<i32> {
-        let scores = self.get_user_recommendations_scores(vn_id);
-        let mut score_vec: Vec<(i32, f64)> = scores.into_iter().collect();
-        score_vec.sort_by(|a, b| b.1.partial_cmp(&a.1).unwrap());
-        let ids: Vec<i32> = score_vec.into_iter().map(|(id, _)| id).collect();
-        self.resize_list(ids)
+    fn combined_scorers<'a>(&'a self, tag_scorer: &'a TagScorer) -> Vec<(&'a dyn Scorer, f64)> {
+        let mut scorers: Vec<(&dyn Scorer, f64)> = vec![
+            (tag_scorer, self.config.tag_weight),
+            (&VoteScorer, self.config.vote_weight),
+        ];
+        scorers.extend(
+            self.scorers
+                .iter()
+                .map(|(scorer, weight)| (scorer.as_ref(), *weight)),
+        );
+        scorers
     }
 
-    pub fn get_tag_recommendations(&self, vn_id: i32) -> Vec<i32> {
-        let scores = self.get_tag_recommendations_score(vn_id);
-        let mut score_vec: Vec<(i32, f64)> = scores.into_iter().collect();
-        score_vec.sort_by(|a, b| b.1.partial_cmp(&a.1).unwrap());
-        let ids: Vec<i32> = score_vec.into_iter().map(|(id, _)| id).collect();
-        self.resize_list(ids)
+    // Train VN and user embeddings on the loaded votes by alternating least
+    // squares. They are kept until new votes are loaded and saved in snapshots.
+    pub fn train_als(&mut self, als: &Als) -> Result<()> {
+        let (Some(vn_users), Some(user_vns)) = (&self.vn_users, &self.user_vns) else {
+            return Err(RecommenderError::EmptyDataset("votes"));
+        };
+        if self.config.verbose {
+            println!(
+                "Training ALS embeddings ({} factors, {} iterations)",
+                als.factors, als.iterations
+            );
+        }
+
+        self.embeddings = Some(als.train(vn_users, user_vns));
+        self.refit_scorers()
     }
 
-    pub fn get_combined_recommendations(&self, vn_id: i32) -> Vec<i32> {
-        let scores = self.get_combined_recommendations_score(vn_id);
-        let mut score_vec: Vec<(i32, f64)> = scores.into_iter().collect();
-        score_vec.sort_by(|a, b| b.1.partial_cmp(&a.1).unwrap());
-        let ids: Vec<i32> = score_vec.into_iter().map(|(id, _)| id).collect();
-        self.resize_list(ids)
+    // The VNs a user would rate highest by their ALS embedding, leaving out
+    // the VNs they already voted on
+    pub fn get_als_user_recommendations(&self, user_id: i32) -> Result<Vec<Recommendation>> {
+        self.get_als_user_recommendations_with(user_id, &QueryOptions::default())
     }
 
-    // WASM-specific data loading methods
-    #[cfg(target_arch = "wasm32")]
-    pub fn load_vn_titles_from_string(&mut self, data: &str) -> Result<(), Box<dyn Error>> {
-        let mut vn_titles = Vec::new();
+    // get_als_user_recommendations with per-query options; only the tag
+    // filter applies, as the ALS model does not use tags
+    pub fn get_als_user_recommendations_with(
+        &self,
+        user_id: i32,
+        options: &QueryOptions,
+    ) -> Result<Vec<Recommendation>> {
+        let embeddings = self
+            .embeddings
+            .as_ref()
+            .ok_or(RecommenderError::ModelNotTrained(ALS_SOURCE))?;
+        let voted: HashSet<i32> = self
+            .user_profile(user_id)?
+            .into_iter()
+            .map(|(vn_id, _)| vn_id)
+            .collect();
 
-        for line in data.lines() {
-            let parts: Vec<&str> = line.split('\t').collect();
-            if parts.len() >= 5 {
-                let vn_id_str = parts[0].trim_start_matches('v');
-                let vn_id = match vn_id_str.parse::<i32>() {
-                    Ok(id) => id,
-                    Err(_) => continue,
-                };
+        let filter = options
+            .tag_filter
+            .resolve(&self.tag_catalog, &self.tag_tree)?;
 
-                let language = parts[1].to_string();
-                let official = parts[2] == "t";
-                let title = parts[3].to_string();
-                let latin_title = if parts[4] == "\\N" {
-                    None
-                } else {
-                    Some(parts[4].to_string())
-                };
-
-                vn_titles.push(VnTitle {
-                    vn_id,
-                    language: language.into(),
-                    official,
-                    title: title.into(),
-                    latin_title: latin_title.map(|x| x.into()),
-                });
-            }
-        }
+        let scores = embeddings.user_scores(user_id, |vn_id| {
+            voted.contains(&vn_id) || !self.allows(&filter, vn_id)
+        });
+        let mut scores: HashMap<i32, f64> = scoring::top_n(scores, self.config.num_vns)
+            .into_iter()
+            .collect();
+        scoring::min_max_normalize(&mut scores);
 
-        self.vn_titles = vn_titles;
-        Ok(())
+        Ok(scoring::ranked(
+            &scores,
+            &[(ALS_SOURCE, &scores)],
+            self.config.num_vns,
+        ))
     }
 
-    #[cfg(target_arch = "wasm32")]
-    pub fn load_ratings_from_string(&mut self, data: &str) -> Result<(), Box<dyn Error>> {
-        let mut ratings = Vec::new();
-
-        for line in data.lines() {
-            let parts: Vec<&str> = line.split_whitespace().collect();
-            if parts.len() >= 4 {
-                let vn_id = match parts[0].parse::<i32>() {
-                    Ok(id) => id,
-                    Err(_) => continue,
-                };
-
-                let user_id = match parts[1].parse::<i32>() {
-                    Ok(id) => id,
-                    Err(_) => continue,
-                };
-
-                let raw_rating = match parts[2].parse::<f64>() {
-                    Ok(r) => r,
-                    Err(_) => continue,
-                };
-
-                let rating = raw_rating.signum() * raw_rating.abs().powf(self.vote_exp);
-                let date = parts[3].to_string();
-
-                ratings.push(Rating {
-                    vn_id,
-                    user_id,
-                    rating,
-                    date: date.into(),
-                });
-            }
-        }
-
-        self.ratings = ratings;
-        self.calculate_average_ratings();
+    // Fit a scorer on the loaded data and add it to the combined blend
+    pub fn add_scorer(&mut self, mut scorer: Box<dyn Scorer>, weight: f64) -> Result<()> {
+        scorer.fit(self)?;
+        self.scorers.push((scorer, weight));
         Ok(())
     }
 
-    #[cfg(target_arch = "wasm32")]
-    pub fn load_tags_from_string(&mut self, data: &str) -> Result<(), Box<dyn Error>> {
-        let mut reader = ReaderBuilder::new()
-            .delimiter(b'\t')
-            .has_headers(false)
-            .from_reader(data.as_bytes());
-
-        let mut tags = Vec::new();
-
-        for result in reader.records() {
-            let record = result?;
-            if record.len() >= 5 {
-                let tag_id_str = &record[1][1..];
-                let tag_id = match tag_id_str.parse::<i32>() {
-                    Ok(id) => id,
-                    Err(_) => continue,
-                };
-
-                let vn_id_str = &record[2][1..];
-                let vn_id = match vn_id_str.parse::<i32>() {
-                    Ok(id) => id,
-                    Err(_) => continue,
-                };
-
-                let raw_rating = match record[4].parse::<f64>() {
-                    Ok(r) => r,
-                    Err(_) => continue,
-                };
-
-                if self.ignore_tags.contains(&tag_id) {
-                    continue;
-                }
-
-                let rating = raw_rating.signum() * raw_rating.abs().powf(self.tag_exp);
-
-                tags.push(Tag {
-                    tag_id,
-                    vn_id,
-                    rating,
-                });
-            }
-        }
-
-        self.tags = tags;
-        self.build_similarity_matrix()?;
-        Ok(())
+    // Refit the added scorers after new data was loaded
+    fn refit_scorers(&mut self) -> Result<()> {
+        let mut scorers = std::mem::take(&mut self.scorers);
+        let result = scorers
+            .iter_mut()
+            .try_for_each(|(scorer, _)| scorer.fit(self));
+        self.scorers = scorers;
+        result
     }
 
     fn calculate_average_ratings(&mut self) {
@@ -751,18 +768,93 @@ impl VisualNovelRecommender {
             .collect();
     }
 
-    fn build_similarity_matrix(&mut self) -> Result<(), Box<dyn Error>> {
+    // Index the ratings by VN and by user. Duplicate votes of a user for the
+    // same VN are summed.
+    pub(crate) fn build_rating_index(&mut self) {
+        let ratings: Vec<&Rating> = self
+            .ratings
+            .iter()
+            .filter(|r| r.vn_id >= 0 && r.user_id >= 0)
+            .collect();
+        if ratings.is_empty() {
+            self.vn_users = None;
+            self.user_vns = None;
+            return;
+        }
+
+        let vn_rows = ratings.iter().map(|r| r.vn_id).max().unwrap_or(0) as usize + 1;
+        let user_cols = ratings.iter().map(|r| r.user_id).max().unwrap_or(0) as usize + 1;
+        let mut triplet_matrix = TriMat::new((vn_rows, user_cols));
+        for rating in ratings {
+            triplet_matrix.add_triplet(
+                rating.vn_id as usize,
+                rating.user_id as usize,
+                rating.rating,
+            );
+        }
+
+        let vn_users: CsMat<f64> = triplet_matrix.to_csr();
+        self.user_vns = Some(vn_users.to_csc());
+        self.vn_users = Some(vn_users);
+    }
+
+    fn build_tag_votes(&mut self) {
+        let mut tag_sums: HashMap<(i32, i32), (f64, i32)> = HashMap::new();
+        for tag in &self.tags {
+            let sum = tag_sums.entry((tag.vn_id, tag.tag_id)).or_insert((0.0, 0));
+            sum.0 += tag.rating;
+            sum.1 += 1;
+        }
+
+        let shape = tag_sums
+            .keys()
+            .fold((0, 0), |(rows, cols), (vn_id, tag_id)| {
+                (
+                    rows.max(*vn_id as usize + 1),
+                    cols.max(*tag_id as usize + 1),
+                )
+            });
+        let mut triplet_matrix = TriMat::new(shape);
+        for ((vn_id, tag_id), (sum, count)) in tag_sums {
+            let average = sum / count as f64;
+            if average != 0.0 {
+                triplet_matrix.add_triplet(vn_id as usize, tag_id as usize, average);
+            }
+        }
+        self.tag_votes = Some(triplet_matrix.to_csr());
+    }
+
+    fn build_similarity_matrix(&mut self) -> Result<()> {
+        let tag_weights = self.build_tag_weights()?;
+        let excluded = |tag_id: i32| tag_weights.get(&tag_id) == Some(&0.0);
+
         let mut tag_sums: HashMap<(i32, i32), f64> = HashMap::new();
         let mut tag_counts: HashMap<(i32, i32), i32> = HashMap::new();
+        // Sum and count of the spoiler levels given, and the number of votes
+        // calling the tag a lie
+        let mut spoilers: HashMap<(i32, i32), (f64, i32, i32)> = HashMap::new();
 
-        for tag in &self.tags {
+        for tag in self.tags.iter().filter(|tag| !excluded(tag.tag_id)) {
             let key = (tag.vn_id, tag.tag_id);
-            *tag_sums.entry(key).or_insert(0.0) += tag.rating;
+            let rating = tag.rating.signum() * tag.rating.abs().powf(self.config.tag_exp);
+            *tag_sums.entry(key).or_insert(0.0) += rating;
             *tag_counts.entry(key).or_insert(0) += 1;
+            let spoiler = spoilers.entry(key).or_insert((0.0, 0, 0));
+            if let Some(level) = tag.spoiler {
+                spoiler.0 += level as f64;
+                spoiler.1 += 1;
+            }
+            if tag.lie {
+                spoiler.2 += 1;
+            }
         }
 
-        let average_tag_votes: Vec<(i32, i32, f64)> = tag_sums
+        let mut average_tag_votes: Vec<(i32, i32, f64)> = tag_sums
             .iter()
+            .filter(|(key, _)| {
+                self.spoiler_level(key.1, spoilers[key], tag_counts[key])
+                    <= self.config.max_tag_spoiler
+            })
             .map(|((vn_id, tag_id), sum)| {
                 let count = *tag_counts.get(&(*vn_id, *tag_id)).unwrap_or(&1) as f64;
                 let avg = sum / count;
@@ -770,6 +862,13 @@ impl VisualNovelRecommender {
             })
             .filter(|(_, _, rating)| *rating != 0.0)
             .collect();
+        self.propagate_tag_votes(&mut average_tag_votes, excluded);
+        for (_, tag_id, rating) in average_tag_votes.iter_mut() {
+            if let Some(weight) = tag_weights.get(tag_id) {
+                *rating *= weight;
+            }
+        }
+        self.weigh_tag_votes(&mut average_tag_votes);
 
         let max_vn_id = average_tag_votes
             .iter()
@@ -790,7 +889,476 @@ impl VisualNovelRecommender {
 
         let data_sparse = triplet_matrix.to_csr();
         self.similarity_matrix = Some(data_sparse);
-
+        self.build_tag_index();
         Ok(())
     }
+
+    // Weights of the tags the config ignores or boosts; 0 leaves a tag out
+    // of the similarity matrix. The tag rules come last so that they can
+    // make exceptions to the ignored ids and categories.
+    fn build_tag_weights(&self) -> Result<HashMap<i32, f64>> {
+        let rules: Vec<TagRule> = self
+            .ignore_tag_rules()
+            .into_iter()
+            .chain(
+                self.config
+                    .ignore_tag_categories
+                    .iter()
+                    .map(|category| TagRule::category(*category)),
+            )
+            .chain(self.config.tag_rules.iter().cloned())
+            .collect();
+        tag_rules::resolve(&rules, &self.tag_catalog, &self.tag_tree)
+    }
+
+    // Rules for the ignored tags: ignore_tags by id, or DEFAULT_IGNORE_TAGS
+    // when it is not set
+    fn ignore_tag_rules(&self) -> Vec<TagRule> {
+        match &self.config.ignore_tags {
+            Some(tag_ids) => tag_ids
+                .iter()
+                .map(|tag_id| TagRule::tag(format!("g{}", tag_id)))
+                .collect(),
+            None => tag_rules::default_ignore_rules(&self.tag_catalog),
+        }
+    }
+
+    // Spoiler level of a tag on a VN: the rounded average of the levels its
+    // voters gave, or the tag's default level when none did. Like on VNDB,
+    // tags most voters call a lie only show at the highest level.
+    fn spoiler_level(&self, tag_id: i32, spoilers: (f64, i32, i32), votes: i32) -> u8 {
+        let (sum, count, lies) = spoilers;
+        if lies * 2 > votes {
+            return MAX_SPOILER;
+        }
+        if count > 0 {
+            (sum / count as f64).round().clamp(0.0, MAX_SPOILER as f64) as u8
+        } else {
+            self.tag_catalog
+                .get(tag_id)
+                .map_or(0, |tag| tag.default_spoiler)
+        }
+    }
+
+    // Pass positive (vn_id, tag_id, average vote) entries on to the ancestors
+    // of the tag, multiplied by tag_parent_decay for each level. Votes the VN
+    // got for a tag directly are kept; otherwise the strongest passed on
+    // vote counts.
+    fn propagate_tag_votes(
+        &self,
+        votes: &mut Vec<(i32, i32, f64)>,
+        excluded: impl Fn(i32) -> bool,
+    ) {
+        let decay = self.config.tag_parent_decay.clamp(0.0, 1.0);
+        if decay == 0.0 || self.tag_tree.is_empty() {
+            return;
+        }
+
+        let direct: HashSet<(i32, i32)> = votes
+            .iter()
+            .map(|(vn_id, tag_id, _)| (*vn_id, *tag_id))
+            .collect();
+        let mut ancestors: HashMap<i32, Vec<(i32, u32)>> = HashMap::new();
+        let mut propagated: HashMap<(i32, i32), f64> = HashMap::new();
+        for &(vn_id, tag_id, rating) in votes.iter().filter(|(.., rating)| *rating > 0.0) {
+            let tag_ancestors = ancestors
+                .entry(tag_id)
+                .or_insert_with(|| self.tag_tree.ancestors(tag_id));
+            for &(ancestor_id, depth) in tag_ancestors.iter() {
+                if direct.contains(&(vn_id, ancestor_id)) || excluded(ancestor_id) {
+                    continue;
+                }
+                let rating = rating * decay.powi(depth as i32);
+                let best = propagated.entry((vn_id, ancestor_id)).or_insert(0.0);
+                *best = best.max(rating);
+            }
+        }
+
+        votes.extend(
+            propagated
+                .into_iter()
+                .map(|((vn_id, tag_id), rating)| (vn_id, tag_id, rating)),
+        );
+    }
+
+    // Apply the configured tag weighting to (vn_id, tag_id, average vote)
+    // entries. Tags are weighed by how many VNs have them, and the sign of
+    // the vote is kept.
+    fn weigh_tag_votes(&self, votes: &mut [(i32, i32, f64)]) {
+        if self.config.tag_weighting == TagWeighting::None || votes.is_empty() {
+            return;
+        }
+
+        let mut tag_counts: HashMap<i32, f64> = HashMap::new();
+        let mut vn_lengths: HashMap<i32, f64> = HashMap::new();
+        for (vn_id, tag_id, rating) in votes.iter() {
+            *tag_counts.entry(*tag_id).or_insert(0.0) += 1.0;
+            *vn_lengths.entry(*vn_id).or_insert(0.0) += rating.abs();
+        }
+        let num_vns = vn_lengths.len() as f64;
+        let average_length = vn_lengths.values().sum::<f64>() / num_vns;
+
+        let (k1, b) = (
+            self.config.bm25_k1.max(0.0),
+            self.config.bm25_b.clamp(0.0, 1.0),
+        );
+        for (vn_id, tag_id, rating) in votes.iter_mut() {
+            let count = tag_counts[tag_id];
+            let weight = match self.config.tag_weighting {
+                TagWeighting::None => 1.0,
+                // Smoothed so that a tag on every VN still counts a little
+                TagWeighting::Idf => ((1.0 + num_vns) / (1.0 + count)).ln() + 1.0,
+                TagWeighting::Bm25 => {
+                    let idf = (1.0 + (num_vns - count + 0.5) / (count + 0.5)).ln();
+                    let length = vn_lengths[vn_id] / average_length;
+                    let saturation = (k1 + 1.0) / (rating.abs() + k1 * (1.0 - b + b * length));
+                    idf * saturation
+                }
+            };
+            *rating *= weight;
+        }
+    }
+
+    // Derive the inverted index and row norms from the similarity matrix
+    pub(crate) fn build_tag_index(&mut self) {
+        match &self.similarity_matrix {
+            Some(matrix) => {
+                self.tag_index = Some(matrix.to_csc());
+                self.tag_norms = scoring::row_norms(matrix.view());
+            }
+            None => {
+                self.tag_index = None;
+                self.tag_norms = Vec::new();
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::TagParent;
+    use crate::test_data;
+
+    fn load_error(sources: DataSources) -> RecommenderError {
+        match VisualNovelRecommender::from_sources(RecommenderConfig::default(), sources) {
+            Ok(_) => panic!("loading succeeded"),
+            Err(err) => err,
+        }
+    }
+
+    #[test]
+    fn loading_errors() {
+        let missing = DataSources::new(DataSource::path("/nonexistent/vn_titles"));
+        assert!(matches!(
+            load_error(missing),
+            RecommenderError::MissingFile(path) if path.ends_with("vn_titles")
+        ));
+
+        let no_titles = DataSources::new(DataSource::text("not a title line\n"));
+        assert!(matches!(
+            load_error(no_titles),
+            RecommenderError::EmptyDataset("vn_titles")
+        ));
+
+        let no_votes = DataSources::new(DataSource::text(test_data::TITLES))
+            .votes(DataSource::text("garbage\n"));
+        assert!(matches!(
+            load_error(no_votes),
+            RecommenderError::EmptyDataset("votes")
+        ));
+    }
+
+    #[test]
+    fn lists_hold_only_scored_vns() {
+        let recommender = test_data::recommender(RecommenderConfig::default());
+        for list in [
+            recommender.get_tag_recommendations(1).unwrap(),
+            recommender.get_user_recommendations(1).unwrap(),
+            recommender.get_combined_recommendations(1).unwrap(),
+        ] {
+            // Eight VNs leave at most seven candidates, however long num_vns
+            assert!(!list.is_empty() && list.len() <= 7);
+            assert!(list.iter().all(|rec| (2..=8).contains(&rec.vn_id)));
+            let ranks: Vec<usize> = list.iter().map(|rec| rec.rank).collect();
+            assert_eq!(ranks, (1..=list.len()).collect::<Vec<_>>());
+            assert!(list.windows(2).all(|pair| pair[0].score >= pair[1].score));
+            assert!(list.iter().all(|rec| !rec.source_scores.is_empty()));
+        }
+        // A single model's scores are normalized, its best to 1
+        assert_eq!(
+            recommender.get_tag_recommendations(1).unwrap()[0].score,
+            1.0
+        );
+    }
+
+    #[test]
+    fn one_seed_gives_the_single_vn_lists() {
+        let recommender = test_data::recommender(RecommenderConfig::default());
+        for vn_id in 1..=8 {
+            let lists = recommender.recommend_for_seeds(&[(vn_id, 1.0)]).unwrap();
+            test_data::assert_same_list(
+                &lists.tag,
+                &recommender.get_tag_recommendations(vn_id).unwrap(),
+            );
+            test_data::assert_same_list(
+                &lists.vote,
+                &recommender.get_user_recommendations(vn_id).unwrap(),
+            );
+            test_data::assert_same_list(
+                &lists.combined,
+                &recommender.get_combined_recommendations(vn_id).unwrap(),
+            );
+        }
+    }
+
+    #[test]
+    fn lookup_errors() {
+        let sources = DataSources::new(DataSource::text(test_data::TITLES))
+            .votes(DataSource::text("1 1 90 2020-01-01\n2 1 70 2020-01-01\n"));
+        let recommender =
+            VisualNovelRecommender::from_sources(RecommenderConfig::default(), sources).unwrap();
+
+        assert_eq!(recommender.get_average_rating(1).unwrap(), 90.0);
+        assert!(matches!(
+            recommender.get_average_rating(3),
+            Err(RecommenderError::NoRatings(3))
+        ));
+        assert!(matches!(
+            recommender.get_average_rating(99),
+            Err(RecommenderError::UnknownVn(99))
+        ));
+        assert!(matches!(
+            recommender.get_user_recommendations(99),
+            Err(RecommenderError::UnknownVn(99))
+        ));
+        assert!(matches!(
+            recommender.recommend_for_seeds(&[(1, -1.0)]),
+            Err(RecommenderError::EmptyQuery)
+        ));
+    }
+
+    #[test]
+    fn user_profiles() {
+        let recommender = test_data::recommender(RecommenderConfig::default());
+        // User 1 voted 90, 80, 70 and 60, a mean of 75
+        assert_eq!(
+            recommender.user_profile(1).unwrap(),
+            [(1, 15.0), (2, 5.0), (3, -5.0), (4, -15.0)]
+        );
+        for user_id in [0, 7, -1] {
+            assert!(matches!(
+                recommender.user_profile(user_id),
+                Err(RecommenderError::UnknownUser(id)) if id == user_id
+            ));
+        }
+
+        // Equal votes carry no preference between them and weigh as voted
+        let sources = DataSources::new(DataSource::text(test_data::TITLES))
+            .votes(DataSource::text("1 1 80 2020-01-01\n2 1 80 2020-01-01\n"));
+        let recommender =
+            VisualNovelRecommender::from_sources(RecommenderConfig::default(), sources).unwrap();
+        assert_eq!(recommender.user_profile(1).unwrap(), [(1, 80.0), (2, 80.0)]);
+    }
+
+    #[test]
+    fn user_recommendations_leave_out_voted_vns() {
+        let recommender = test_data::recommender(RecommenderConfig::default());
+        let lists = recommender.recommend_for_user(1).unwrap();
+        test_data::assert_same_lists(
+            &lists,
+            &recommender
+                .recommend_for_seeds(&recommender.user_profile(1).unwrap())
+                .unwrap(),
+        );
+        for list in [&lists.tag, &lists.vote, &lists.combined] {
+            assert!(!list.is_empty());
+            assert!(list.iter().all(|rec| (5..=8).contains(&rec.vn_id)));
+        }
+        assert!(matches!(
+            recommender.recommend_for_user(7),
+            Err(RecommenderError::UnknownUser(7))
+        ));
+    }
+
+    #[test]
+    fn vote_list_recommendations() {
+        let recommender = test_data::recommender(RecommenderConfig::default());
+        let votes = [(1, 9.0), (2, 8.0), (3, 7.0), (4, 6.0), (99, 10.0)];
+        // The same votes as user 1 in the dump, on the 1-10 scale
+        test_data::assert_same_lists(
+            &recommender.recommend_for_vote_list(&votes, 10.0).unwrap(),
+            &recommender.recommend_for_user(1).unwrap(),
+        );
+        assert!(matches!(
+            recommender.vote_list_profile(&[(99, 10.0)], 10.0),
+            Err(RecommenderError::EmptyDataset("vote list"))
+        ));
+        assert!(matches!(
+            recommender.vote_list_profile(&votes, 0.0),
+            Err(RecommenderError::InvalidConfig(_))
+        ));
+    }
+
+    fn weighed_tag_votes(tag_weighting: TagWeighting) -> Vec<(i32, i32, f64)> {
+        let recommender = VisualNovelRecommender::empty(RecommenderConfig {
+            tag_weighting,
+            ..RecommenderConfig::default()
+        });
+        // g10 is on all three VNs, g20 and g30 on one each
+        let mut votes = vec![
+            (1, 10, 2.0),
+            (1, 20, 1.0),
+            (2, 10, 1.0),
+            (3, 10, -1.0),
+            (3, 30, 2.0),
+        ];
+        recommender.weigh_tag_votes(&mut votes);
+        votes
+    }
+
+    fn assert_tag_votes(votes: &[(i32, i32, f64)], expected: &[(i32, i32, f64)]) {
+        assert_eq!(votes.len(), expected.len());
+        for (vote, expected) in votes.iter().zip(expected) {
+            assert_eq!((vote.0, vote.1), (expected.0, expected.1));
+            assert!(
+                (vote.2 - expected.2).abs() < 1e-12,
+                "{:?} != {:?}",
+                vote,
+                expected
+            );
+        }
+    }
+
+    #[test]
+    fn idf_tag_weighting() {
+        // ln((1 + 3) / (1 + n)) + 1: 1 for the tag on every VN, 1 + ln 2 for
+        // the others
+        let rare = 1.0 + 2.0f64.ln();
+        assert_tag_votes(
+            &weighed_tag_votes(TagWeighting::Idf),
+            &[
+                (1, 10, 2.0),
+                (1, 20, rare),
+                (2, 10, 1.0),
+                (3, 10, -1.0),
+                (3, 30, 2.0 * rare),
+            ],
+        );
+        assert_tag_votes(
+            &weighed_tag_votes(TagWeighting::None),
+            &[
+                (1, 10, 2.0),
+                (1, 20, 1.0),
+                (2, 10, 1.0),
+                (3, 10, -1.0),
+                (3, 30, 2.0),
+            ],
+        );
+    }
+
+    #[test]
+    fn bm25_tag_weighting() {
+        // IDF ln(1 + (3 - n + 0.5) / (n + 0.5)): ln(8/7) for the tag on every
+        // VN, still above zero, and ln(8/3) for the others. The VN lengths
+        // 3, 1 and 3 average 7/3, so with k1 = 1.2 and b = 0.75 a vote r
+        // becomes r * 2.2 / (|r| + 1.2 * (0.25 + 0.75 * length * 3/7)) times
+        // the IDF: 14/11 for r = 2 on v1 and v3, 77/86 for r = 1 on them and
+        // 77/59 for r = 1 on v2.
+        let (common, rare) = ((8.0f64 / 7.0).ln(), (8.0f64 / 3.0).ln());
+        assert_tag_votes(
+            &weighed_tag_votes(TagWeighting::Bm25),
+            &[
+                (1, 10, 14.0 / 11.0 * common),
+                (1, 20, 77.0 / 86.0 * rare),
+                (2, 10, 77.0 / 59.0 * common),
+                (3, 10, -77.0 / 86.0 * common),
+                (3, 30, 14.0 / 11.0 * rare),
+            ],
+        );
+    }
+
+    fn propagated_tag_votes(
+        tag_parent_decay: f64,
+        links: &[(i32, i32)],
+        excluded: impl Fn(i32) -> bool,
+    ) -> Vec<(i32, i32, f64)> {
+        let mut recommender = VisualNovelRecommender::empty(RecommenderConfig {
+            tag_parent_decay,
+            ..RecommenderConfig::default()
+        });
+        recommender.tag_tree = TagTree::new(
+            links
+                .iter()
+                .map(|&(tag_id, parent_id)| TagParent { tag_id, parent_id }),
+        );
+        let mut votes = vec![
+            (1, 3, 2.0),
+            (1, 6, 3.0),
+            (2, 3, 2.0),
+            (2, 2, 0.5),
+            (3, 4, 2.0),
+            (4, 3, -1.0),
+        ];
+        recommender.propagate_tag_votes(&mut votes, excluded);
+        votes.sort_by_key(|(vn_id, tag_id, _)| (*vn_id, *tag_id));
+        votes
+    }
+
+    #[test]
+    fn tag_votes_pass_to_ancestors() {
+        // g3 and g6 under g2 under g1, and g4 under both g1 and g5
+        let links = [(3, 2), (6, 2), (2, 1), (4, 1), (4, 5)];
+        assert_eq!(
+            propagated_tag_votes(0.5, &links, |_| false),
+            [
+                // Halved per level, the strongest of v1's g3 and g6 votes
+                (1, 1, 0.75),
+                (1, 2, 1.5),
+                (1, 3, 2.0),
+                (1, 6, 3.0),
+                // The direct g2 vote stays, though g3 passes on more; g3
+                // gives g1 more than g2 does
+                (2, 1, 0.5),
+                (2, 2, 0.5),
+                (2, 3, 2.0),
+                // Both parents of g4
+                (3, 1, 1.0),
+                (3, 4, 2.0),
+                (3, 5, 1.0),
+                // Negative votes are not passed on
+                (4, 3, -1.0),
+            ]
+        );
+
+        // Ignored ancestors get nothing, while tags above them still do
+        assert_eq!(
+            propagated_tag_votes(0.5, &links, |tag_id| tag_id == 2 || tag_id == 5),
+            [
+                (1, 1, 0.75),
+                (1, 3, 2.0),
+                (1, 6, 3.0),
+                (2, 1, 0.5),
+                (2, 2, 0.5),
+                (2, 3, 2.0),
+                (3, 1, 1.0),
+                (3, 4, 2.0),
+                (4, 3, -1.0),
+            ]
+        );
+    }
+
+    #[test]
+    fn tag_votes_stay_without_decay_or_tree() {
+        let direct = [
+            (1, 3, 2.0),
+            (1, 6, 3.0),
+            (2, 2, 0.5),
+            (2, 3, 2.0),
+            (3, 4, 2.0),
+            (4, 3, -1.0),
+        ];
+        assert_eq!(propagated_tag_votes(0.0, &[(3, 2)], |_| false), direct);
+        assert_eq!(propagated_tag_votes(0.5, &[], |_| false), direct);
+    }
 }
@@ -0,0 +1,193 @@
+// Recommendation models. A Scorer is fitted once on the loaded data and then
+// scores candidate VNs for a query; the recommender blends any list of
+// weighted scorers into one ranking.
+use crate::error::Result;
+use crate::recommender::VisualNovelRecommender;
+use crate::scoring;
+use std::collections::HashMap;
+
+pub trait Scorer {
+    // Name of the model in Recommendation::source_scores
+    fn name(&self) -> &str;
+
+    // Build the model from the data loaded into `data`. Called again when
+    // the recommender loads new votes or tags.
+    fn fit(&mut self, data: &VisualNovelRecommender) -> Result<()>;
+
+    // Scores in [0, 1] of the `num_vns` VNs that best match the seed VNs,
+    // given as (vn_id, weight) pairs. The seeds themselves are left out.
+    fn score(
+        &self,
+        data: &VisualNovelRecommender,
+        seeds: &[(i32, f64)],
+        num_vns: usize,
+    ) -> HashMap<i32, f64>;
+}
+
+// Cosine similarity of the VNs' tag vectors
+#[derive(Debug, Clone, Default)]
+pub struct TagScorer {
+    // Multipliers for the tag votes, by tag id, applied on top of the tag
+    // matrix for this scorer only; see VisualNovelRecommender::tag_scorer
+    pub tag_weights: HashMap<i32, f64>,
+}
+
+impl Scorer for TagScorer {
+    fn name(&self) -> &str {
+        scoring::TAG_SOURCE
+    }
+
+    // The tag matrix and its inverted index are built when the tags are loaded
+    fn fit(&mut self, _data: &VisualNovelRecommender) -> Result<()> {
+        Ok(())
+    }
+
+    fn score(
+        &self,
+        data: &VisualNovelRecommender,
+        seeds: &[(i32, f64)],
+        num_vns: usize,
+    ) -> HashMap<i32, f64> {
+        match (&data.similarity_matrix, &data.tag_index) {
+            (Some(matrix), Some(index)) => {
+                let index = scoring::TagIndex {
+                    vn_tags: matrix.view(),
+                    tag_vns: index.view(),
+                    norms: &data.tag_norms,
+                    tag_weights: &self.tag_weights,
+                };
+                scoring::tag_scores(index, seeds, num_vns)
+            }
+            _ => HashMap::new(),
+        }
+    }
+}
+
+// Summed ratings from the users who also rated the seed VNs
+#[derive(Debug, Clone, Copy, Default)]
+pub struct VoteScorer;
+
+impl Scorer for VoteScorer {
+    fn name(&self) -> &str {
+        scoring::VOTE_SOURCE
+    }
+
+    // The rating index is built when the votes are loaded
+    fn fit(&mut self, _data: &VisualNovelRecommender) -> Result<()> {
+        Ok(())
+    }
+
+    fn score(
+        &self,
+        data: &VisualNovelRecommender,
+        seeds: &[(i32, f64)],
+        num_vns: usize,
+    ) -> HashMap<i32, f64> {
+        match (&data.vn_users, &data.user_vns) {
+            (Some(vn_users), Some(user_vns)) => {
+                scoring::corater_scores(vn_users.view(), user_vns.view(), seeds, num_vns)
+            }
+            _ => HashMap::new(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::RecommenderConfig;
+    use crate::source::DataSource;
+    use crate::test_data;
+    use std::{cell::Cell, rc::Rc};
+
+    // Scores v8 highest and v2 lowest whatever the seeds, and counts its fits
+    #[derive(Default)]
+    struct FixedScorer {
+        fits: Rc<Cell<usize>>,
+    }
+
+    impl Scorer for FixedScorer {
+        fn name(&self) -> &str {
+            "fixed"
+        }
+
+        fn fit(&mut self, _data: &VisualNovelRecommender) -> Result<()> {
+            self.fits.set(self.fits.get() + 1);
+            Ok(())
+        }
+
+        fn score(
+            &self,
+            _data: &VisualNovelRecommender,
+            seeds: &[(i32, f64)],
+            _num_vns: usize,
+        ) -> HashMap<i32, f64> {
+            let mut scores = HashMap::from([(8, 1.0), (5, 0.5), (2, 0.0)]);
+            for (vn_id, _) in seeds {
+                scores.remove(vn_id);
+            }
+            scores
+        }
+    }
+
+    #[test]
+    fn blend_is_the_weighted_sum() {
+        let recommender = test_data::recommender(RecommenderConfig::default());
+        let list = recommender
+            .get_recommendations_with(
+                &[(&TagScorer::default(), 2.0), (&VoteScorer, 0.5)],
+                &[(1, 1.0)],
+            )
+            .unwrap();
+
+        assert!(!list.is_empty());
+        for rec in &list {
+            let score = |name: &str| rec.source_scores.get(name).copied().unwrap_or(0.0);
+            let expected = 2.0 * score(scoring::TAG_SOURCE) + 0.5 * score(scoring::VOTE_SOURCE);
+            assert!((rec.score - expected).abs() < 1e-12, "v{}", rec.vn_id);
+        }
+
+        // A single scorer at weight 1 gives that model's own list
+        test_data::assert_same_list(
+            &recommender
+                .get_recommendations_with(&[(&VoteScorer, 1.0)], &[(1, 1.0)])
+                .unwrap(),
+            &recommender.get_user_recommendations(1).unwrap(),
+        );
+    }
+
+    #[test]
+    fn added_scorers_join_the_blend() {
+        let mut recommender = test_data::recommender(RecommenderConfig::default());
+        recommender
+            .add_scorer(Box::new(FixedScorer::default()), 10.0)
+            .unwrap();
+        let lists = recommender.recommend_for_seeds(&[(1, 1.0)]).unwrap();
+
+        let fixed: Vec<(i32, f64)> = lists.extra["fixed"]
+            .iter()
+            .map(|rec| (rec.vn_id, rec.score))
+            .collect();
+        assert_eq!(fixed, [(8, 1.0), (5, 0.5), (2, 0.0)]);
+        // At weight 10 the fixed scores lead the combined list
+        assert_eq!(lists.combined[0].vn_id, 8);
+        assert_eq!(lists.combined[0].source_scores["fixed"], 1.0);
+    }
+
+    #[test]
+    fn added_scorers_are_refitted() {
+        let mut recommender = test_data::recommender(RecommenderConfig::default());
+        let scorer = FixedScorer::default();
+        let fits = Rc::clone(&scorer.fits);
+        recommender.add_scorer(Box::new(scorer), 1.0).unwrap();
+        assert_eq!(fits.get(), 1);
+        recommender
+            .load_votes(DataSource::text(test_data::VOTES))
+            .unwrap();
+        recommender
+            .load_tags(DataSource::text(test_data::TAGS))
+            .unwrap();
+
+        assert_eq!(fits.get(), 3);
+    }
+}
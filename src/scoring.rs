@@ -0,0 +1,726 @@
+// Scoring routines shared by the in-memory recommender and the memory-mapped
+// snapshot. They work on sparse matrix views, so it does not matter whether
+// the arrays are owned or borrowed from a mapped file.
+use crate::data::Recommendation;
+use crate::error::{RecommenderError, Result};
+use sprs::{CsMatViewI, SpIndex};
+use std::collections::{BTreeMap, HashMap};
+
+// Names of the models in Recommendation::source_scores
+pub(crate) const TAG_SOURCE: &str = "tag";
+pub(crate) const VOTE_SOURCE: &str = "vote";
+
+// Helper function for min-max normalization
+pub(crate) fn min_max_normalize(scores: &mut HashMap<i32, f64>) {
+    if scores.is_empty() {
+        return;
+    }
+
+    // Find min and max values
+    let values: Vec<f64> = scores.values().cloned().collect();
+    let min_val = values
+        .iter()
+        .min_by(|a, b| a.partial_cmp(b).unwrap())
+        .unwrap_or(&0.0);
+    let max_val = values
+        .iter()
+        .max_by(|a, b| a.partial_cmp(b).unwrap())
+        .unwrap_or(&1.0);
+
+    // Avoid division by zero
+    let range = max_val - min_val;
+    if range.abs() < f64::EPSILON {
+        // If all values are the same, set them all to 1.0
+        for val in scores.values_mut() {
+            *val = 1.0;
+        }
+        return;
+    }
+
+    // Apply normalization
+    for val in scores.values_mut() {
+        *val = (*val - *min_val) / range;
+    }
+}
+
+// Sort scores in descending order and keep the first `n`. Ties go to the
+// lower VN id so results do not depend on hash map order.
+pub(crate) fn top_n(scores: impl IntoIterator<Item = (i32, f64)>, n: usize) -> Vec<(i32, f64)> {
+    let mut score_vec: Vec<(i32, f64)> = scores.into_iter().collect();
+    score_vec.sort_by(|a, b| {
+        b.1.partial_cmp(&a.1)
+            .unwrap_or(std::cmp::Ordering::Equal)
+            .then(a.0.cmp(&b.0))
+    });
+    score_vec.truncate(n);
+    score_vec
+}
+
+// Euclidean norm of every row of the VN x tag matrix
+pub(crate) fn row_norms<I: SpIndex, Iptr: SpIndex>(matrix: CsMatViewI<f64, I, Iptr>) -> Vec<f64> {
+    matrix
+        .outer_iterator()
+        .map(|row| row.iter().map(|(_, val)| val * val).sum::<f64>().sqrt())
+        .collect()
+}
+
+// Multiply the weight of negative seeds by `strength`
+pub(crate) fn scale_negative_seeds(seeds: &[(i32, f64)], strength: f64) -> Vec<(i32, f64)> {
+    seeds
+        .iter()
+        .map(|&(vn_id, weight)| {
+            if weight < 0.0 {
+                (vn_id, weight * strength)
+            } else {
+                (vn_id, weight)
+            }
+        })
+        .collect()
+}
+
+// Scores of the seed VNs, weighted and summed by `raw_scores`, with the seeds
+// themselves left out and the `num_vns` best kept, min-max normalized. Seeds
+// with a negative weight ("less like this") only lower the scores of
+// candidates found through the positive seeds.
+pub(crate) fn seed_scores(
+    seeds: &[(i32, f64)],
+    num_vns: usize,
+    raw_scores: impl Fn(&[(i32, f64)]) -> HashMap<i32, f64>,
+) -> HashMap<i32, f64> {
+    let (positive, negative): (Vec<_>, Vec<_>) = seeds
+        .iter()
+        .filter(|(_, weight)| *weight != 0.0)
+        .partition(|(_, weight)| *weight > 0.0);
+
+    let mut totals = raw_scores(&positive);
+    if !negative.is_empty() {
+        for (other_id, score) in raw_scores(&negative) {
+            if let Some(total) = totals.get_mut(&other_id) {
+                *total += score;
+            }
+        }
+    }
+    for (vn_id, _) in seeds {
+        totals.remove(vn_id);
+    }
+
+    // Take top N items
+    let mut scores: HashMap<i32, f64> = top_n(totals, num_vns).into_iter().collect();
+
+    // Apply min-max normalization
+    min_max_normalize(&mut scores);
+
+    scores
+}
+
+// Scores of a model for all candidates narrowed down to those `keep` allows,
+// keeping the `num_vns` best, min-max normalized again. Normalizing is
+// invariant to the scaling the first normalization applied, so this gives
+// the scores the model would have given the allowed candidates alone.
+pub(crate) fn filter_scores(
+    mut scores: HashMap<i32, f64>,
+    num_vns: usize,
+    keep: impl Fn(i32) -> bool,
+) -> HashMap<i32, f64> {
+    scores.retain(|vn_id, _| keep(*vn_id));
+    let mut scores: HashMap<i32, f64> = top_n(scores, num_vns).into_iter().collect();
+    min_max_normalize(&mut scores);
+    scores
+}
+
+// The tag matrix as the tag model uses it. `vn_tags` is the VN x tag matrix,
+// `tag_vns` the same matrix in CSC order so that each tag lists the VNs that
+// have it, and `norms` the row norms. `tag_weights` multiplies the votes of
+// some tags for one query; the norms of the VNs involved are then recomputed.
+#[derive(Clone, Copy)]
+pub(crate) struct TagIndex<'a, I: SpIndex, Iptr: SpIndex> {
+    pub(crate) vn_tags: CsMatViewI<'a, f64, I, Iptr>,
+    pub(crate) tag_vns: CsMatViewI<'a, f64, I, Iptr>,
+    pub(crate) norms: &'a [f64],
+    pub(crate) tag_weights: &'a HashMap<i32, f64>,
+}
+
+impl<I: SpIndex, Iptr: SpIndex> TagIndex<'_, I, Iptr> {
+    fn tag_weight(&self, tag_id: usize) -> f64 {
+        self.tag_weights
+            .get(&(tag_id as i32))
+            .copied()
+            .unwrap_or(1.0)
+    }
+
+    fn norm(&self, row_idx: usize) -> f64 {
+        if self.tag_weights.is_empty() {
+            return self.norms[row_idx];
+        }
+        self.vn_tags.outer_view(row_idx).map_or(0.0, |row| {
+            row.iter()
+                .map(|(tag_id, value)| (value * self.tag_weight(tag_id)).powi(2))
+                .sum::<f64>()
+                .sqrt()
+        })
+    }
+}
+
+// Weighted sum of the cosine similarities between the tag vectors of the seed
+// VNs and every VN sharing at least one tag with them
+pub(crate) fn tag_similarities<I: SpIndex, Iptr: SpIndex>(
+    index: TagIndex<I, Iptr>,
+    seeds: &[(i32, f64)],
+) -> HashMap<i32, f64> {
+    // Sum the seeds' tag vectors, each scaled to unit length, so the index is
+    // walked once however many seeds there are
+    let mut query: HashMap<usize, f64> = HashMap::new();
+    for &(vn_id, weight) in seeds {
+        let row_idx = vn_id as usize;
+        let Some(vn_row) = index.vn_tags.outer_view(row_idx) else {
+            continue;
+        };
+        let vn_magnitude = index.norm(row_idx);
+        if vn_magnitude == 0.0 {
+            continue;
+        }
+        for (tag_id, value) in vn_row.iter() {
+            let tag_weight = index.tag_weight(tag_id);
+            if tag_weight != 0.0 {
+                *query.entry(tag_id).or_insert(0.0) += weight * value * tag_weight / vn_magnitude;
+            }
+        }
+    }
+
+    // Accumulate dot products through the inverted index, so only VNs that
+    // share a tag with the seeds are visited
+    let mut dot_products: HashMap<usize, f64> = HashMap::new();
+    for (tag_id, weight) in query {
+        let Some(tag_column) = index.tag_vns.outer_view(tag_id) else {
+            continue;
+        };
+        let tag_weight = index.tag_weight(tag_id);
+        for (other_id, other_weight) in tag_column.iter() {
+            *dot_products.entry(other_id).or_insert(0.0) += weight * other_weight * tag_weight;
+        }
+    }
+
+    dot_products
+        .into_iter()
+        .filter_map(|(other_id, dot_product)| {
+            let norm = index.norm(other_id);
+            (norm != 0.0).then(|| (other_id as i32, dot_product / norm))
+        })
+        .collect()
+}
+
+// Tag similarity to a set of weighted seed VNs
+pub(crate) fn tag_scores<I: SpIndex, Iptr: SpIndex>(
+    index: TagIndex<I, Iptr>,
+    seeds: &[(i32, f64)],
+    num_vns: usize,
+) -> HashMap<i32, f64> {
+    seed_scores(seeds, num_vns, |seeds| tag_similarities(index, seeds))
+}
+
+// Sum of the ratings that users who also rated the seed VNs gave to each
+// other VN, weighted by the seeds they rated. `vn_users` is the VN x user
+// rating matrix and `user_vns` its transpose.
+pub(crate) fn corater_sums<I: SpIndex, Iptr: SpIndex>(
+    vn_users: CsMatViewI<f64, I, Iptr>,
+    user_vns: CsMatViewI<f64, I, Iptr>,
+    seeds: &[(i32, f64)],
+) -> HashMap<i32, f64> {
+    // Find users who rated the seeds, with the summed weight of those seeds,
+    // so each user's votes are read once
+    let mut user_weights: HashMap<usize, f64> = HashMap::new();
+    for &(vn_id, weight) in seeds {
+        let Some(users_who_rated) = vn_users.outer_view(vn_id as usize) else {
+            continue;
+        };
+        for (user_id, _) in users_who_rated.iter() {
+            *user_weights.entry(user_id).or_insert(0.0) += weight;
+        }
+    }
+
+    // Find VNs these users rated
+    let mut similar_vns: HashMap<i32, f64> = HashMap::new();
+    for (user_id, weight) in user_weights {
+        let Some(user_row) = user_vns.outer_view(user_id) else {
+            continue;
+        };
+        for (other_id, rating) in user_row.iter() {
+            *similar_vns.entry(other_id as i32).or_insert(0.0) += weight * rating;
+        }
+    }
+
+    similar_vns
+}
+
+// Co-rater scores for a set of weighted seed VNs
+pub(crate) fn corater_scores<I: SpIndex, Iptr: SpIndex>(
+    vn_users: CsMatViewI<f64, I, Iptr>,
+    user_vns: CsMatViewI<f64, I, Iptr>,
+    seeds: &[(i32, f64)],
+    num_vns: usize,
+) -> HashMap<i32, f64> {
+    seed_scores(seeds, num_vns, |seeds| {
+        corater_sums(vn_users, user_vns, seeds)
+    })
+}
+
+// The votes of a user in the rating matrix as seeds for personal
+// recommendations. `user_vns` lists the VNs of each user.
+pub(crate) fn user_profile<I: SpIndex, Iptr: SpIndex>(
+    user_vns: CsMatViewI<f64, I, Iptr>,
+    user_id: i32,
+) -> Vec<(i32, f64)> {
+    let Some(user_row) = usize::try_from(user_id)
+        .ok()
+        .and_then(|user_id| user_vns.outer_view(user_id))
+    else {
+        return Vec::new();
+    };
+    center_votes(
+        user_row
+            .iter()
+            .map(|(vn_id, rating)| (vn_id as i32, *rating))
+            .collect(),
+    )
+}
+
+// The top of the scale of an imported vote list must be a positive number
+pub(crate) fn check_vote_scale(vote_scale: f64) -> Result<()> {
+    if vote_scale.is_finite() && vote_scale > 0.0 {
+        Ok(())
+    } else {
+        Err(RecommenderError::InvalidConfig(format!(
+            "invalid vote scale {}",
+            vote_scale
+        )))
+    }
+}
+
+// An imported vote list as seeds, in the same form as user_profile. Votes on
+// a scale up to `vote_scale` are brought to the 10-100 scale of the votes
+// dump and transformed by `vote_exp` like the dump's votes. VNs that
+// `is_known` rejects are dropped, and of repeated VNs the last vote counts.
+pub(crate) fn vote_list_profile(
+    votes: &[(i32, f64)],
+    vote_scale: f64,
+    vote_exp: f64,
+    is_known: impl Fn(i32) -> bool,
+) -> Vec<(i32, f64)> {
+    let scale = 100.0 / vote_scale;
+    let votes: BTreeMap<i32, f64> = votes
+        .iter()
+        .filter(|(vn_id, _)| is_known(*vn_id))
+        .map(|&(vn_id, vote)| {
+            let vote = vote * scale;
+            (vn_id, vote.signum() * vote.abs().powf(vote_exp))
+        })
+        .collect();
+    center_votes(votes.into_iter().collect())
+}
+
+// Weigh each voted VN by how far its vote lies above or below the mean vote.
+// When all votes are equal the raw votes are used as weights instead.
+fn center_votes(votes: Vec<(i32, f64)>) -> Vec<(i32, f64)> {
+    if votes.is_empty() {
+        return votes;
+    }
+
+    let mean = votes.iter().map(|(_, rating)| rating).sum::<f64>() / votes.len() as f64;
+    if votes
+        .iter()
+        .all(|(_, rating)| (rating - mean).abs() < f64::EPSILON)
+    {
+        return votes;
+    }
+    votes
+        .into_iter()
+        .map(|(vn_id, rating)| (vn_id, rating - mean))
+        .collect()
+}
+
+// Weighted sum of the scores of several models, keeping the `num_vns` best
+pub(crate) fn combine_scores(
+    weighted: &[(&HashMap<i32, f64>, f64)],
+    num_vns: usize,
+) -> HashMap<i32, f64> {
+    // Combine scores with weights
+    let mut combined_scores: HashMap<i32, f64> = HashMap::new();
+    for (scores, weight) in weighted {
+        for (id, score) in *scores {
+            *combined_scores.entry(*id).or_insert(0.0) += score * weight;
+        }
+    }
+
+    // Sort and take top N
+    top_n(combined_scores, num_vns).into_iter().collect()
+}
+
+// The best `num_vns` entries of a score map as a ranked list. `sources` are
+// the score maps of the models behind it, reported per entry.
+pub(crate) fn ranked(
+    scores: &HashMap<i32, f64>,
+    sources: &[(&str, &HashMap<i32, f64>)],
+    num_vns: usize,
+) -> Vec<Recommendation> {
+    top_n(scores.iter().map(|(id, score)| (*id, *score)), num_vns)
+        .into_iter()
+        .enumerate()
+        .map(|(i, (vn_id, score))| Recommendation {
+            vn_id,
+            score,
+            source_scores: sources
+                .iter()
+                .filter_map(|(name, scores)| Some((name.to_string(), *scores.get(&vn_id)?)))
+                .collect(),
+            rank: i + 1,
+        })
+        .collect()
+}
+
+// Small deterministic random generator, for ALS initialization and random
+// walks. The state must not be zero.
+pub(crate) struct XorShift(pub(crate) u64);
+
+impl XorShift {
+    // Uniform in [0, 1)
+    pub(crate) fn next_f64(&mut self) -> f64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        (self.0 >> 11) as f64 / (1u64 << 53) as f64
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use sprs::{CsMat, TriMat};
+
+    fn matrix(shape: (usize, usize), entries: &[(usize, usize, f64)]) -> CsMat<f64> {
+        let mut triplets = TriMat::new(shape);
+        for &(row, col, value) in entries {
+            triplets.add_triplet(row, col, value);
+        }
+        triplets.to_csr()
+    }
+
+    fn assert_close(left: &HashMap<i32, f64>, right: &HashMap<i32, f64>) {
+        let mut left_ids: Vec<_> = left.keys().collect();
+        let mut right_ids: Vec<_> = right.keys().collect();
+        left_ids.sort();
+        right_ids.sort();
+        assert_eq!(left_ids, right_ids);
+        for (vn_id, score) in left {
+            assert!(
+                (score - right[vn_id]).abs() < 1e-12,
+                "v{}: {} != {}",
+                vn_id,
+                score,
+                right[vn_id]
+            );
+        }
+    }
+
+    // VN x tag votes: v1 and v2 share tag 0, v1 and v3 tag 1, v2 and v3 tag
+    // 2; v4 shares nothing with them and v5 has no tags
+    fn tag_matrix() -> CsMat<f64> {
+        matrix(
+            (6, 4),
+            &[
+                (1, 0, 9.0),
+                (1, 1, 1.0),
+                (2, 0, 4.0),
+                (2, 2, 4.0),
+                (3, 1, 9.0),
+                (3, 2, 1.0),
+                (4, 3, 1.0),
+            ],
+        )
+    }
+
+    // Cosine similarity of every pair of VNs by comparing their whole rows,
+    // as the tag model computed it before the inverted index
+    fn all_pairs_cosine(
+        matrix: &CsMat<f64>,
+        vn_id: i32,
+        tag_weights: &HashMap<i32, f64>,
+    ) -> HashMap<i32, f64> {
+        let weigh = |row_idx: usize| -> Vec<f64> {
+            let mut row = vec![0.0; matrix.cols()];
+            if let Some(view) = matrix.outer_view(row_idx) {
+                for (tag_id, value) in view.iter() {
+                    row[tag_id] = value * tag_weights.get(&(tag_id as i32)).copied().unwrap_or(1.0);
+                }
+            }
+            row
+        };
+        let norm = |row: &[f64]| row.iter().map(|x| x * x).sum::<f64>().sqrt();
+        let seed = weigh(vn_id as usize);
+        (0..matrix.rows())
+            .filter_map(|other_id| {
+                let other = weigh(other_id);
+                let dot: f64 = seed.iter().zip(&other).map(|(a, b)| a * b).sum();
+                (dot != 0.0).then(|| (other_id as i32, dot / (norm(&seed) * norm(&other))))
+            })
+            .collect()
+    }
+
+    fn similarities(
+        matrix: &CsMat<f64>,
+        seeds: &[(i32, f64)],
+        tag_weights: &HashMap<i32, f64>,
+    ) -> HashMap<i32, f64> {
+        let tag_vns = matrix.to_csc();
+        let norms = row_norms(matrix.view());
+        let index = TagIndex {
+            vn_tags: matrix.view(),
+            tag_vns: tag_vns.view(),
+            norms: &norms,
+            tag_weights,
+        };
+        tag_similarities(index, seeds)
+    }
+
+    #[test]
+    fn tag_similarities_match_all_pairs() {
+        let matrix = tag_matrix();
+        let no_weights = HashMap::new();
+        let weights = HashMap::from([(0, 2.0), (2, 0.0)]);
+        for tag_weights in [&no_weights, &weights] {
+            for vn_id in 1..=4 {
+                assert_close(
+                    &similarities(&matrix, &[(vn_id, 1.0)], tag_weights),
+                    &all_pairs_cosine(&matrix, vn_id, tag_weights),
+                );
+            }
+        }
+
+        // VNs without tags, or missing from the matrix, match nothing
+        assert!(similarities(&matrix, &[(5, 1.0)], &no_weights).is_empty());
+        assert!(similarities(&matrix, &[(9, 1.0)], &no_weights).is_empty());
+    }
+
+    #[test]
+    fn tag_similarities_by_hand() {
+        // v1 = (9, 1, 0) and v2 = (4, 0, 4): 36 / (sqrt(82) * sqrt(32))
+        let scores = similarities(&tag_matrix(), &[(1, 1.0)], &HashMap::new());
+        assert!((scores[&2] - 36.0 / (82f64.sqrt() * 32f64.sqrt())).abs() < 1e-12);
+        assert!((scores[&1] - 1.0).abs() < 1e-12);
+        assert!(!scores.contains_key(&4));
+    }
+
+    // (vn_id, user_id, vote)
+    const VOTES: [(i32, i32, f64); 10] = [
+        (1, 1, 90.0),
+        (1, 2, 60.0),
+        (2, 1, 80.0),
+        (2, 3, 70.0),
+        (3, 1, 40.0),
+        (3, 2, 100.0),
+        (3, 3, 50.0),
+        (4, 3, 30.0),
+        (5, 4, 80.0),
+        (1, 5, 20.0),
+    ];
+
+    fn rating_matrices() -> (CsMat<f64>, CsMat<f64>) {
+        let entries: Vec<_> = VOTES
+            .iter()
+            .map(|&(vn_id, user_id, vote)| (vn_id as usize, user_id as usize, vote))
+            .collect();
+        let vn_users = matrix((6, 6), &entries);
+        let user_vns = vn_users.to_csc();
+        (vn_users, user_vns)
+    }
+
+    // Sum of the votes each other VN got from the users who voted on the
+    // seed, found by scanning every vote, as the vote model did before the
+    // rating index
+    fn scan_votes(seed: i32) -> HashMap<i32, f64> {
+        let users: Vec<i32> = VOTES
+            .iter()
+            .filter(|(vn_id, ..)| *vn_id == seed)
+            .map(|(_, user_id, _)| *user_id)
+            .collect();
+        let mut sums = HashMap::new();
+        for &(vn_id, user_id, vote) in &VOTES {
+            if users.contains(&user_id) && vn_id != seed {
+                *sums.entry(vn_id).or_insert(0.0) += vote;
+            }
+        }
+        sums
+    }
+
+    #[test]
+    fn corater_sums_match_scanning_votes() {
+        let (vn_users, user_vns) = rating_matrices();
+        for seed in 1..=5 {
+            let mut sums = corater_sums(vn_users.view(), user_vns.view(), &[(seed, 1.0)]);
+            sums.remove(&seed);
+            assert_close(&sums, &scan_votes(seed));
+        }
+        assert!(corater_sums(vn_users.view(), user_vns.view(), &[(9, 1.0)]).is_empty());
+    }
+
+    #[test]
+    fn corater_sums_by_hand() {
+        // Users 1, 2 and 5 voted on v1; v3 got 40 and 100 from users 1 and 2
+        let (vn_users, user_vns) = rating_matrices();
+        let sums = corater_sums(vn_users.view(), user_vns.view(), &[(1, 1.0)]);
+        assert_eq!(sums[&2], 80.0);
+        assert_eq!(sums[&3], 140.0);
+        assert!(!sums.contains_key(&4) && !sums.contains_key(&5));
+    }
+
+    #[test]
+    fn ranked_lists() {
+        let combined = HashMap::from([(3, 0.5), (7, 1.0), (2, 0.5), (9, 0.0)]);
+        let tag = HashMap::from([(3, 1.0), (7, 0.5)]);
+        let vote = HashMap::from([(7, 1.0), (2, 1.0), (8, 0.2)]);
+        let list = ranked(&combined, &[("tag", &tag), ("vote", &vote)], 3);
+
+        // Best first, ties to the lower id, cut to num_vns
+        let entries: Vec<(usize, i32, f64)> = list
+            .iter()
+            .map(|rec| (rec.rank, rec.vn_id, rec.score))
+            .collect();
+        assert_eq!(entries, [(1, 7, 1.0), (2, 2, 0.5), (3, 3, 0.5)]);
+        // Only the models that scored a VN are listed for it
+        assert_eq!(
+            list[0].source_scores,
+            BTreeMap::from([("tag".to_string(), 0.5), ("vote".to_string(), 1.0)])
+        );
+        assert_eq!(
+            list[1].source_scores,
+            BTreeMap::from([("vote".to_string(), 1.0)])
+        );
+
+        // Fewer candidates than num_vns give a shorter list, not padding
+        let list = ranked(&tag, &[("tag", &tag)], 25);
+        assert_eq!(list.len(), 2);
+        assert!(ranked(&HashMap::new(), &[], 25).is_empty());
+    }
+
+    // Weighted sum of single-seed scores
+    fn sum_of_single_seeds(
+        seeds: &[(i32, f64)],
+        scores: impl Fn(i32) -> HashMap<i32, f64>,
+    ) -> HashMap<i32, f64> {
+        let mut totals = HashMap::new();
+        for &(vn_id, weight) in seeds {
+            for (other_id, score) in scores(vn_id) {
+                *totals.entry(other_id).or_insert(0.0) += weight * score;
+            }
+        }
+        totals
+    }
+
+    #[test]
+    fn several_seeds_sum_their_scores() {
+        let seeds = [(1, 1.0), (3, 0.5), (4, 2.0)];
+
+        let matrix = tag_matrix();
+        let no_weights = HashMap::new();
+        assert_close(
+            &similarities(&matrix, &seeds, &no_weights),
+            &sum_of_single_seeds(&seeds, |vn_id| {
+                similarities(&matrix, &[(vn_id, 1.0)], &no_weights)
+            }),
+        );
+
+        let (vn_users, user_vns) = rating_matrices();
+        assert_close(
+            &corater_sums(vn_users.view(), user_vns.view(), &seeds),
+            &sum_of_single_seeds(&seeds, |vn_id| {
+                corater_sums(vn_users.view(), user_vns.view(), &[(vn_id, 1.0)])
+            }),
+        );
+    }
+
+    #[test]
+    fn seed_scores_leave_out_seeds() {
+        let raw =
+            |_: &[(i32, f64)]| HashMap::from([(1, 9.0), (2, 4.0), (3, 2.0), (4, 1.0), (5, 8.0)]);
+        let scores = seed_scores(&[(1, 1.0), (5, 0.5)], 2, raw);
+        // v2 to v4 remain, the best two normalized to [0, 1]
+        assert_eq!(scores, HashMap::from([(2, 1.0), (3, 0.0)]));
+    }
+
+    #[test]
+    fn negative_seeds_only_lower_found_candidates() {
+        // v1 finds v3 and v4, v2 ("less like this") finds v4 and v5
+        let raw = |seeds: &[(i32, f64)]| {
+            let mut scores = HashMap::new();
+            for &(vn_id, weight) in seeds {
+                let found: &[(i32, f64)] = match vn_id {
+                    1 => &[(3, 4.0), (4, 6.0), (2, 1.0)],
+                    2 => &[(4, 3.0), (5, 5.0), (1, 1.0)],
+                    _ => &[],
+                };
+                for &(other_id, score) in found {
+                    *scores.entry(other_id).or_insert(0.0) += weight * score;
+                }
+            }
+            scores
+        };
+
+        let positive = seed_scores(&[(1, 1.0)], 10, raw);
+        assert_eq!(positive, HashMap::from([(2, 0.0), (3, 0.6), (4, 1.0)]));
+
+        // v4 drops from 6 to 6 - 3 = 3 below v3; v5 is not added and both
+        // seeds are left out
+        let steered = seed_scores(&[(1, 1.0), (2, -1.0)], 10, raw);
+        assert_eq!(steered, HashMap::from([(3, 1.0), (4, 0.0)]));
+
+        // Without positive seeds there is nothing to lower
+        assert!(seed_scores(&[(2, -1.0)], 10, raw).is_empty());
+    }
+
+    #[test]
+    fn negative_seed_strength() {
+        let seeds = [(1, 1.0), (2, -0.5), (3, 0.0)];
+        assert_eq!(
+            scale_negative_seeds(&seeds, 2.0),
+            [(1, 1.0), (2, -1.0), (3, 0.0)]
+        );
+    }
+
+    #[test]
+    fn vote_list_profiles() {
+        let known = |vn_id: i32| vn_id != 9;
+        // Of the repeated v1 the last vote counts and the unknown v9 is
+        // dropped, leaving 80, 60 and 70 with a mean of 70
+        let votes = [(1, 90.0), (2, 60.0), (9, 100.0), (3, 70.0), (1, 80.0)];
+        let expected = [(1, 10.0), (2, -10.0), (3, 0.0)];
+        assert_eq!(vote_list_profile(&votes, 100.0, 1.0, known), expected);
+
+        // Votes on the 1-10 scale are brought to the dump's scale
+        let votes = [(1, 8.0), (2, 6.0), (3, 7.0)];
+        assert_eq!(vote_list_profile(&votes, 10.0, 1.0, known), expected);
+
+        // vote_exp transforms votes like those of the dump: 100, 400 and 900
+        let votes = [(1, 10.0), (2, 20.0), (3, 30.0)];
+        let profile = vote_list_profile(&votes, 100.0, 2.0, known);
+        let mean = 1400.0 / 3.0;
+        assert_eq!(
+            profile,
+            [(1, 100.0 - mean), (2, 400.0 - mean), (3, 900.0 - mean)]
+        );
+
+        // Equal votes weigh as voted
+        let votes = [(1, 8.0), (2, 8.0)];
+        assert_eq!(
+            vote_list_profile(&votes, 10.0, 1.0, known),
+            [(1, 80.0), (2, 80.0)]
+        );
+        assert!(vote_list_profile(&[(9, 80.0)], 100.0, 1.0, known).is_empty());
+
+        assert!(check_vote_scale(10.0).is_ok());
+        for vote_scale in [0.0, -10.0, f64::NAN, f64::INFINITY] {
+            assert_eq!(
+                check_vote_scale(vote_scale).unwrap_err().kind(),
+                "InvalidConfig"
+            );
+        }
+    }
+}
@@ -0,0 +1,231 @@
+// Binary snapshot of a fully built recommender, so that it can be loaded
+// without re-parsing the dumps and rebuilding the tag matrix.
+//
+// Layout (all integers little-endian):
+//   magic     4 bytes  "VNRS"
+//   version   u32
+//   length    u64      payload length in bytes
+//   checksum  u32      CRC32 of the payload
+//   payload            bincode-encoded SnapshotData
+use crate::als::Embeddings;
+use crate::config::RecommenderConfig;
+use crate::data::{Rating, TagInfo, TagParent, VnTitle};
+use crate::error::{RecommenderError, Result};
+use crate::recommender::VisualNovelRecommender;
+use crate::source::DataSource;
+use crate::tag_catalog::TagCatalog;
+use crate::tag_tree::TagTree;
+use crate::titles::TitleIndex;
+use serde::{Deserialize, Serialize};
+use sprs::CsMat;
+use std::{
+    collections::HashMap,
+    io::{Read, Write},
+    sync::Arc,
+};
+
+pub const SNAPSHOT_MAGIC: [u8; 4] = *b"VNRS";
+pub const SNAPSHOT_VERSION: u32 = 1;
+
+const HEADER_LEN: usize = 4 + 4 + 8 + 4;
+
+#[derive(Serialize, Deserialize)]
+struct SnapshotData {
+    // RecommenderConfig as JSON, so that fields added to the config later
+    // take their defaults instead of breaking the snapshot format
+    config: String,
+    vn_titles: Vec<VnTitle>,
+    // (vn_id, user_id, rating) with the vote exponent already applied
+    ratings: Vec<(i32, i32, f64)>,
+    average_ratings: HashMap<i32, f64>,
+    similarity_matrix: Option<CsMat<f64>>,
+    // Average tag votes, for the tag filters of queries
+    tag_votes: Option<CsMat<f64>>,
+    embeddings: Option<Embeddings>,
+    // Tag catalog and hierarchy, for resolving tag rules given with queries
+    tag_info: Vec<TagInfo>,
+    tag_parents: Vec<TagParent>,
+}
+
+fn invalid(reason: impl Into<String>) -> RecommenderError {
+    RecommenderError::InvalidSnapshot(reason.into())
+}
+
+impl VisualNovelRecommender {
+    // Write the titles, ratings, tag matrix, average tag votes and catalog,
+    // ALS embeddings and config as a snapshot. Vote dates and the individual
+    // tag votes are not needed for queries and are left out.
+    pub fn save_snapshot(&self, mut writer: impl Write) -> Result<()> {
+        let data = SnapshotData {
+            config: serde_json::to_string(&self.config).map_err(|e| invalid(e.to_string()))?,
+            vn_titles: self.titles.iter().cloned().collect(),
+            ratings: self
+                .ratings
+                .iter()
+                .map(|r| (r.vn_id, r.user_id, r.rating))
+                .collect(),
+            average_ratings: self.average_ratings.clone(),
+            similarity_matrix: self.similarity_matrix.clone(),
+            tag_votes: self.tag_votes.clone(),
+            embeddings: self.embeddings.clone(),
+            tag_info: self.tag_catalog.iter().cloned().collect(),
+            tag_parents: self.tag_tree.links().collect(),
+        };
+        let payload = bincode::serialize(&data).map_err(|e| invalid(e.to_string()))?;
+
+        let mut header = Vec::with_capacity(HEADER_LEN);
+        header.extend_from_slice(&SNAPSHOT_MAGIC);
+        header.extend_from_slice(&SNAPSHOT_VERSION.to_le_bytes());
+        header.extend_from_slice(&(payload.len() as u64).to_le_bytes());
+        header.extend_from_slice(&crc32fast::hash(&payload).to_le_bytes());
+
+        writer
+            .write_all(&header)
+            .and_then(|_| writer.write_all(&payload))
+            .and_then(|_| writer.flush())
+            .map_err(|e| RecommenderError::io("snapshot", e))
+    }
+
+    // Load a recommender from a snapshot written by save_snapshot
+    pub fn load_snapshot(source: DataSource) -> Result<Self> {
+        let (path, mut reader) = source.open("snapshot")?;
+
+        let mut header = [0u8; HEADER_LEN];
+        reader
+            .read_exact(&mut header)
+            .map_err(|_| invalid("file is too short to be a snapshot"))?;
+        if header[0..4] != SNAPSHOT_MAGIC {
+            return Err(invalid("not a snapshot file"));
+        }
+        let version = u32::from_le_bytes(header[4..8].try_into().unwrap());
+        if version != SNAPSHOT_VERSION {
+            return Err(invalid(format!(
+                "unsupported snapshot version {} (expected {})",
+                version, SNAPSHOT_VERSION
+            )));
+        }
+        let length = u64::from_le_bytes(header[8..16].try_into().unwrap());
+        let checksum = u32::from_le_bytes(header[16..20].try_into().unwrap());
+
+        let mut payload = Vec::new();
+        reader
+            .take(length)
+            .read_to_end(&mut payload)
+            .map_err(|e| RecommenderError::io(&path, e))?;
+        if payload.len() as u64 != length {
+            return Err(invalid("snapshot is truncated"));
+        }
+        if crc32fast::hash(&payload) != checksum {
+            return Err(invalid("checksum mismatch"));
+        }
+
+        let data: SnapshotData =
+            bincode::deserialize(&payload).map_err(|e| invalid(e.to_string()))?;
+
+        let config: RecommenderConfig =
+            serde_json::from_str(&data.config).map_err(|e| invalid(format!("config: {}", e)))?;
+        let mut recommender = Self::empty(config);
+        recommender.titles = TitleIndex::new(data.vn_titles);
+
+        let no_date: Arc<str> = Arc::from("");
+        recommender.ratings = data
+            .ratings
+            .into_iter()
+            .map(|(vn_id, user_id, rating)| Rating {
+                vn_id,
+                user_id,
+                rating,
+                date: no_date.clone(),
+            })
+            .collect();
+        recommender.average_ratings = data.average_ratings;
+        recommender.build_rating_index();
+        recommender.similarity_matrix = data.similarity_matrix;
+        recommender.build_tag_index();
+        recommender.tag_votes = data.tag_votes;
+        recommender.embeddings = data.embeddings;
+        recommender.tag_catalog = TagCatalog::new(data.tag_info);
+        recommender.tag_tree = TagTree::new(data.tag_parents);
+
+        Ok(recommender)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_data;
+
+    fn snapshot() -> (VisualNovelRecommender, Vec<u8>) {
+        let recommender = test_data::recommender(RecommenderConfig::default());
+        let mut bytes = Vec::new();
+        recommender.save_snapshot(&mut bytes).unwrap();
+        (recommender, bytes)
+    }
+
+    fn load_error(bytes: &[u8]) -> String {
+        match VisualNovelRecommender::load_snapshot(DataSource::bytes(bytes)) {
+            Err(RecommenderError::InvalidSnapshot(reason)) => reason,
+            Err(err) => panic!("expected InvalidSnapshot, got {}", err),
+            Ok(_) => panic!("expected InvalidSnapshot, got a recommender"),
+        }
+    }
+
+    #[test]
+    fn round_trip() {
+        let (recommender, bytes) = snapshot();
+        let loaded = VisualNovelRecommender::load_snapshot(DataSource::bytes(&bytes)).unwrap();
+
+        assert_eq!(loaded.config, recommender.config);
+        assert_eq!(loaded.titles.len(), recommender.titles.len());
+        assert_eq!(loaded.ratings.len(), recommender.ratings.len());
+        assert_eq!(loaded.similarity_matrix, recommender.similarity_matrix);
+        assert_eq!(loaded.tag_votes, recommender.tag_votes);
+        assert_eq!(loaded.tag_catalog.len(), recommender.tag_catalog.len());
+        assert_eq!(
+            loaded.tag_tree.descendants(1),
+            recommender.tag_tree.descendants(1)
+        );
+        for seeds in [vec![(1, 1.0)], vec![(3, 1.0), (6, 0.5), (2, -1.0)]] {
+            test_data::assert_same_lists(
+                &loaded.recommend_for_seeds(&seeds).unwrap(),
+                &recommender.recommend_for_seeds(&seeds).unwrap(),
+            );
+        }
+    }
+
+    #[test]
+    fn bad_magic() {
+        let (_, mut bytes) = snapshot();
+        bytes[0..4].copy_from_slice(b"VNRX");
+        assert_eq!(load_error(&bytes), "not a snapshot file");
+    }
+
+    #[test]
+    fn wrong_version() {
+        let (_, mut bytes) = snapshot();
+        bytes[4..8].copy_from_slice(&(SNAPSHOT_VERSION + 1).to_le_bytes());
+        assert!(load_error(&bytes).starts_with("unsupported snapshot version"));
+    }
+
+    #[test]
+    fn truncated() {
+        let (_, bytes) = snapshot();
+        assert_eq!(
+            load_error(&bytes[..HEADER_LEN - 1]),
+            "file is too short to be a snapshot"
+        );
+        assert_eq!(
+            load_error(&bytes[..bytes.len() - 1]),
+            "snapshot is truncated"
+        );
+    }
+
+    #[test]
+    fn corrupt_payload() {
+        let (_, mut bytes) = snapshot();
+        let middle = HEADER_LEN + (bytes.len() - HEADER_LEN) / 2;
+        bytes[middle] ^= 0x01;
+        assert_eq!(load_error(&bytes), "checksum mismatch");
+    }
+}
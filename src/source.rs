@@ -0,0 +1,494 @@
+use crate::error::{RecommenderError, Result};
+use flate2::bufread::MultiGzDecoder;
+use ruzstd::decoding::{FrameDecoder, StreamingDecoder};
+use std::{
+    fs::{self, File},
+    io::{self, BufRead, BufReader, Read},
+    path::{Path, PathBuf},
+};
+
+const GZIP_MAGIC: [u8; 2] = [0x1f, 0x8b];
+const ZSTD_MAGIC: [u8; 4] = [0x28, 0xb5, 0x2f, 0xfd];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Compression {
+    None,
+    Gzip,
+    Zstd,
+}
+
+impl Compression {
+    // Detect the compression format from the first bytes of a stream
+    pub fn detect(header: &[u8]) -> Self {
+        if header.starts_with(&GZIP_MAGIC) {
+            Compression::Gzip
+        } else if header.starts_with(&ZSTD_MAGIC) {
+            Compression::Zstd
+        } else {
+            Compression::None
+        }
+    }
+}
+
+// Wrap a reader so that gzip and zstd input is decompressed on the fly
+pub fn decompress<'a>(mut reader: impl BufRead + 'a) -> io::Result<Box<dyn BufRead + 'a>> {
+    match Compression::detect(reader.fill_buf()?) {
+        Compression::None => Ok(Box::new(reader)),
+        Compression::Gzip => Ok(Box::new(BufReader::new(MultiGzDecoder::new(reader)))),
+        Compression::Zstd => Ok(Box::new(BufReader::new(ZstdDecoder::new(reader)?))),
+    }
+}
+
+// Streaming zstd decoder that, unlike StreamingDecoder, continues with the
+// next frame when a file holds several of them
+struct ZstdDecoder<R: BufRead> {
+    decoder: Option<StreamingDecoder<R, FrameDecoder>>,
+}
+
+impl<R: BufRead> ZstdDecoder<R> {
+    fn new(reader: R) -> io::Result<Self> {
+        let decoder = StreamingDecoder::new(reader)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+        Ok(Self {
+            decoder: Some(decoder),
+        })
+    }
+}
+
+impl<R: BufRead> Read for ZstdDecoder<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        loop {
+            let Some(decoder) = self.decoder.as_mut() else {
+                return Ok(0);
+            };
+            let read = decoder.read(buf)?;
+            if read > 0 || buf.is_empty() {
+                return Ok(read);
+            }
+
+            // The current frame is finished, start on the next one if any
+            let mut reader = self.decoder.take().map(|d| d.into_inner()).unwrap();
+            if reader.fill_buf()?.is_empty() {
+                return Ok(0);
+            }
+            *self = Self::new(reader)?;
+        }
+    }
+}
+
+// Where a single data file is read from
+pub enum DataSource<'a> {
+    // A file on disk
+    Path(PathBuf),
+    // Any reader, e.g. a network stream or a file handle opened elsewhere
+    Reader(Box<dyn Read + 'a>),
+    // Data already held in memory
+    Str(&'a str),
+}
+
+impl<'a> DataSource<'a> {
+    pub fn path(path: impl Into<PathBuf>) -> Self {
+        DataSource::Path(path.into())
+    }
+
+    pub fn reader(reader: impl Read + 'a) -> Self {
+        DataSource::Reader(Box::new(reader))
+    }
+
+    pub fn text(data: &'a str) -> Self {
+        DataSource::Str(data)
+    }
+
+    pub fn bytes(data: &'a [u8]) -> Self {
+        DataSource::Reader(Box::new(data))
+    }
+
+    // Open the source for reading, decompressing gzip and zstd input. The
+    // returned path names the source in errors; non-file sources are
+    // labelled with `name`.
+    pub fn open(self, name: &str) -> Result<(PathBuf, Box<dyn BufRead + 'a>)> {
+        let (path, reader): (PathBuf, Box<dyn BufRead + 'a>) = match self {
+            DataSource::Path(path) => {
+                let file = File::open(&path).map_err(|e| RecommenderError::io(&path, e))?;
+                (path, Box::new(BufReader::new(file)))
+            }
+            DataSource::Reader(reader) => (PathBuf::from(name), Box::new(BufReader::new(reader))),
+            DataSource::Str(data) => (PathBuf::from(name), Box::new(data.as_bytes())),
+        };
+
+        let reader = decompress(reader).map_err(|e| RecommenderError::io(&path, e))?;
+        Ok((path, reader))
+    }
+}
+
+// The set of dumps a recommender is built from. Votes and tags are optional
+// so that a recommender can be built with only some of the models. Titles and
+// tags can also come from a VNDB database dump tarball (`vndb-db-*.tar.zst`);
+// files given separately take precedence over the ones in the tarball.
+pub struct DataSources<'a> {
+    pub titles: Option<DataSource<'a>>,
+    pub votes: Option<DataSource<'a>>,
+    pub tags: Option<DataSource<'a>>,
+    // Tag metadata and hierarchy (the `tags` and `tags_parents` tables)
+    pub tag_info: Option<DataSource<'a>>,
+    pub tag_parents: Option<DataSource<'a>>,
+    pub db_dump: Option<DataSource<'a>>,
+}
+
+impl<'a> DataSources<'a> {
+    pub fn new(titles: DataSource<'a>) -> Self {
+        Self {
+            titles: Some(titles),
+            votes: None,
+            tags: None,
+            tag_info: None,
+            tag_parents: None,
+            db_dump: None,
+        }
+    }
+
+    // Read titles and tags from a database dump tarball
+    pub fn from_db_dump(db_dump: DataSource<'a>) -> Self {
+        Self {
+            titles: None,
+            votes: None,
+            tags: None,
+            tag_info: None,
+            tag_parents: None,
+            db_dump: Some(db_dump),
+        }
+    }
+
+    pub fn votes(mut self, votes: DataSource<'a>) -> Self {
+        self.votes = Some(votes);
+        self
+    }
+
+    pub fn tags(mut self, tags: DataSource<'a>) -> Self {
+        self.tags = Some(tags);
+        self
+    }
+
+    pub fn tag_info(mut self, tag_info: DataSource<'a>) -> Self {
+        self.tag_info = Some(tag_info);
+        self
+    }
+
+    pub fn tag_parents(mut self, tag_parents: DataSource<'a>) -> Self {
+        self.tag_parents = Some(tag_parents);
+        self
+    }
+
+    pub fn titles(mut self, titles: DataSource<'a>) -> Self {
+        self.titles = Some(titles);
+        self
+    }
+
+    pub fn db_dump(mut self, db_dump: DataSource<'a>) -> Self {
+        self.db_dump = Some(db_dump);
+        self
+    }
+
+    // Use the standard VNDB layout of a directory: `vn_titles`, `tags_vn` and
+    // the latest `vndb-votes-*` file, each possibly compressed, and `tags`
+    // and `tags_parents` when present. Tables missing from the directory are
+    // read from the latest `vndb-db-*` tarball, which must be there when
+    // `vn_titles` is not.
+    pub fn from_dir(dir: impl AsRef<Path>) -> Result<Self> {
+        let dir = dir.as_ref();
+        let votes = DataSource::path(latest_votes_file(dir)?);
+
+        let sources = match find_data_file(dir, "vn_titles") {
+            Some(titles) => Self::new(DataSource::path(titles)),
+            None => Self::from_db_dump(DataSource::path(latest_db_dump(dir)?)),
+        };
+        let sources = match find_data_file(dir, "tags_vn") {
+            Some(tags) => sources.tags(DataSource::path(tags)),
+            None => sources,
+        };
+        let sources = match find_data_file(dir, "tags") {
+            Some(tag_info) => sources.tag_info(DataSource::path(tag_info)),
+            None => sources,
+        };
+        let sources = match find_data_file(dir, "tags_parents") {
+            Some(tag_parents) => sources.tag_parents(DataSource::path(tag_parents)),
+            None => sources,
+        };
+
+        let missing_tables =
+            sources.tags.is_none() || sources.tag_info.is_none() || sources.tag_parents.is_none();
+        let sources = match latest_file(dir, "vndb-db-")? {
+            Some(db_dump) if sources.db_dump.is_none() && missing_tables => {
+                sources.db_dump(DataSource::path(db_dump))
+            }
+            _ => sources,
+        };
+
+        Ok(sources.votes(votes))
+    }
+}
+
+// Find `name` in a directory, or a compressed `name.gz` / `name.zst`
+pub fn find_data_file(dir: &Path, name: &str) -> Option<PathBuf> {
+    ["", ".gz", ".zst"]
+        .iter()
+        .map(|ext| dir.join(format!("{}{}", name, ext)))
+        .find(|path| path.is_file())
+}
+
+// Find the file with the latest date among those starting with `prefix`
+fn latest_file(dir: &Path, prefix: &str) -> Result<Option<PathBuf>> {
+    let mut files: Vec<_> = fs::read_dir(dir)
+        .map_err(|e| RecommenderError::io(dir, e))?
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| {
+            entry
+                .file_name()
+                .to_str()
+                .is_some_and(|name| name.starts_with(prefix))
+        })
+        .collect();
+
+    // Sort files by date in filename (descending) to get the latest
+    files.sort_by_key(|entry| entry.file_name());
+    files.reverse();
+
+    Ok(files.first().map(|entry| entry.path()))
+}
+
+// Find the latest `vndb-votes-*` file in a directory
+pub fn latest_votes_file(dir: &Path) -> Result<PathBuf> {
+    latest_file(dir, "vndb-votes-")?.ok_or_else(|| RecommenderError::NoVotesFile(dir.to_path_buf()))
+}
+
+// Find the latest `vndb-db-*` tarball in a directory
+pub fn latest_db_dump(dir: &Path) -> Result<PathBuf> {
+    latest_file(dir, "vndb-db-")?
+        .ok_or_else(|| RecommenderError::MissingFile(dir.join("vn_titles")))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::RecommenderConfig;
+    use crate::recommender::VisualNovelRecommender;
+    use crate::test_data;
+    use flate2::{Compression as GzLevel, write::GzEncoder};
+    use ruzstd::encoding::{CompressionLevel, compress_to_vec};
+    use std::io::Write;
+
+    fn gzip(data: &[u8]) -> Vec<u8> {
+        let mut encoder = GzEncoder::new(Vec::new(), GzLevel::default());
+        encoder.write_all(data).unwrap();
+        encoder.finish().unwrap()
+    }
+
+    fn zstd(data: &[u8]) -> Vec<u8> {
+        compress_to_vec(data, CompressionLevel::Fastest)
+    }
+
+    // A database dump tarball holding the given tables under `db/`
+    fn db_dump(tables: &[(&str, &str)]) -> Vec<u8> {
+        let mut builder = tar::Builder::new(Vec::new());
+        for (name, contents) in tables {
+            let mut header = tar::Header::new_gnu();
+            header.set_size(contents.len() as u64);
+            header.set_mode(0o644);
+            header.set_cksum();
+            builder
+                .append_data(&mut header, format!("db/{}", name), contents.as_bytes())
+                .unwrap();
+        }
+        builder.into_inner().unwrap()
+    }
+
+    // A fresh directory under the temp dir, removed when dropped
+    struct TempDir(PathBuf);
+
+    impl TempDir {
+        fn new(name: &str) -> Self {
+            let dir = std::env::temp_dir().join(format!("{}.{}", name, std::process::id()));
+            let _ = fs::remove_dir_all(&dir);
+            fs::create_dir_all(&dir).unwrap();
+            Self(dir)
+        }
+
+        fn write(&self, name: &str, contents: &[u8]) {
+            fs::write(self.0.join(name), contents).unwrap();
+        }
+    }
+
+    impl Drop for TempDir {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.0);
+        }
+    }
+
+    fn read(source: DataSource, name: &str) -> (PathBuf, String) {
+        let (path, mut reader) = source.open(name).unwrap();
+        let mut contents = String::new();
+        reader.read_to_string(&mut contents).unwrap();
+        (path, contents)
+    }
+
+    #[test]
+    fn open_sources() {
+        let text = "v1\ten\tt\tFirst\t\\N\n";
+        let expected = (PathBuf::from("vn_titles"), text.to_string());
+        assert_eq!(read(DataSource::text(text), "vn_titles"), expected);
+        assert_eq!(
+            read(DataSource::bytes(text.as_bytes()), "vn_titles"),
+            expected
+        );
+        assert_eq!(
+            read(DataSource::reader(io::Cursor::new(text)), "vn_titles"),
+            expected
+        );
+
+        let path = std::env::temp_dir().join(format!("vn_titles.{}", std::process::id()));
+        fs::write(&path, text).unwrap();
+        let read_path = read(DataSource::path(&path), "vn_titles");
+        fs::remove_file(&path).unwrap();
+        assert_eq!(read_path, (path, text.to_string()));
+    }
+
+    #[test]
+    fn open_missing_file() {
+        let err = DataSource::path("/nonexistent/tags_vn")
+            .open("tags_vn")
+            .err()
+            .unwrap();
+        assert!(matches!(err, RecommenderError::MissingFile(path) if path.ends_with("tags_vn")));
+    }
+
+    #[test]
+    fn load_from_any_source() {
+        let from_text = test_data::recommender(RecommenderConfig::default());
+        let sources = DataSources::new(DataSource::bytes(test_data::TITLES.as_bytes()))
+            .votes(DataSource::reader(test_data::VOTES.as_bytes()))
+            .tags(DataSource::reader(io::Cursor::new(test_data::TAGS)))
+            .tag_info(DataSource::text(test_data::TAG_INFO))
+            .tag_parents(DataSource::bytes(test_data::TAG_PARENTS.as_bytes()));
+        let from_readers =
+            VisualNovelRecommender::from_sources(RecommenderConfig::default(), sources).unwrap();
+
+        for seeds in [vec![(1, 1.0)], vec![(4, 1.0), (7, 0.5)]] {
+            test_data::assert_same_lists(
+                &from_readers.recommend_for_seeds(&seeds).unwrap(),
+                &from_text.recommend_for_seeds(&seeds).unwrap(),
+            );
+        }
+    }
+
+    #[test]
+    fn detect_compression() {
+        assert_eq!(Compression::detect(&gzip(b"v1")), Compression::Gzip);
+        assert_eq!(Compression::detect(&zstd(b"v1")), Compression::Zstd);
+        assert_eq!(Compression::detect(b"v1\ten"), Compression::None);
+        assert_eq!(Compression::detect(b""), Compression::None);
+    }
+
+    #[test]
+    fn decompress_gzip() {
+        let data = test_data::VOTES.as_bytes();
+        let (_, contents) = read(DataSource::bytes(&gzip(data)), "votes");
+        assert_eq!(contents, test_data::VOTES);
+
+        // Concatenated gzip members, as some tools write large files
+        let (first, second) = data.split_at(data.len() / 2);
+        let members = [gzip(first), gzip(second)].concat();
+        let (_, contents) = read(DataSource::bytes(&members), "votes");
+        assert_eq!(contents, test_data::VOTES);
+    }
+
+    #[test]
+    fn decompress_zstd() {
+        let data = test_data::TAGS.as_bytes();
+        let (_, contents) = read(DataSource::bytes(&zstd(data)), "tags_vn");
+        assert_eq!(contents, test_data::TAGS);
+
+        // Several frames are read one after the other
+        let frames: Vec<u8> = data.chunks(100).flat_map(zstd).collect();
+        let (_, contents) = read(DataSource::bytes(&frames), "tags_vn");
+        assert_eq!(contents, test_data::TAGS);
+
+        let mut corrupt = zstd(data);
+        corrupt.truncate(10);
+        let (_, mut reader) = DataSource::bytes(&corrupt).open("tags_vn").unwrap();
+        assert!(reader.read_to_string(&mut String::new()).is_err());
+    }
+
+    #[test]
+    fn load_db_dump() {
+        let tarball = db_dump(&[
+            ("vn_titles.header", "id\tlang\tofficial\ttitle\tlatin\n"),
+            ("vn_titles", test_data::TITLES),
+            ("tags", test_data::TAG_INFO),
+            ("tags_vn", test_data::TAGS),
+            ("tags_parents", test_data::TAG_PARENTS),
+        ]);
+        let expected = test_data::recommender(RecommenderConfig::default());
+        for tarball in [tarball.clone(), zstd(&tarball), gzip(&tarball)] {
+            let sources = DataSources::from_db_dump(DataSource::bytes(&tarball))
+                .votes(DataSource::text(test_data::VOTES));
+            let recommender =
+                VisualNovelRecommender::from_sources(RecommenderConfig::default(), sources)
+                    .unwrap();
+            assert_eq!(recommender.titles.len(), expected.titles.len());
+            assert_eq!(recommender.tag_catalog.len(), expected.tag_catalog.len());
+            test_data::assert_same_lists(
+                &recommender.recommend_for_seeds(&[(1, 1.0)]).unwrap(),
+                &expected.recommend_for_seeds(&[(1, 1.0)]).unwrap(),
+            );
+        }
+
+        let tarball = db_dump(&[("tags_vn", test_data::TAGS)]);
+        let sources = DataSources::from_db_dump(DataSource::bytes(&tarball));
+        let err = VisualNovelRecommender::from_sources(RecommenderConfig::default(), sources)
+            .err()
+            .unwrap();
+        assert!(
+            matches!(err, RecommenderError::MissingFile(path) if path.ends_with("db/vn_titles"))
+        );
+    }
+
+    #[test]
+    fn dir_falls_back_to_db_dump_per_table() {
+        let dir = TempDir::new("vn_dir");
+        dir.write("vn_titles.gz", &gzip(test_data::TITLES.as_bytes()));
+        dir.write("vndb-votes-2020-01-01", test_data::VOTES.as_bytes());
+        dir.write("tags", test_data::TAG_INFO.as_bytes());
+        dir.write(
+            "vndb-db-2020-01-01.tar.zst",
+            &zstd(&db_dump(&[
+                ("vn_titles", "v1\ten\tt\tFrom the dump\t\\N\n"),
+                ("tags_vn", test_data::TAGS),
+                ("tags_parents", test_data::TAG_PARENTS),
+            ])),
+        );
+
+        let sources = DataSources::from_dir(&dir.0).unwrap();
+        assert!(sources.titles.is_some() && sources.tag_info.is_some());
+        assert!(sources.tags.is_none() && sources.tag_parents.is_none());
+        assert!(sources.db_dump.is_some());
+
+        // Titles come from the directory, tags and parents from the dump
+        let recommender =
+            VisualNovelRecommender::from_sources(RecommenderConfig::default(), sources).unwrap();
+        let expected = test_data::recommender(RecommenderConfig::default());
+        assert_eq!(&*recommender.get_title(1), "First");
+        assert_eq!(
+            recommender.tag_tree.descendants(1),
+            expected.tag_tree.descendants(1)
+        );
+        test_data::assert_same_lists(
+            &recommender.recommend_for_seeds(&[(1, 1.0)]).unwrap(),
+            &expected.recommend_for_seeds(&[(1, 1.0)]).unwrap(),
+        );
+
+        // With every table in the directory the dump is left alone
+        dir.write("tags_vn", test_data::TAGS.as_bytes());
+        dir.write("tags_parents", test_data::TAG_PARENTS.as_bytes());
+        assert!(DataSources::from_dir(&dir.0).unwrap().db_dump.is_none());
+    }
+}
@@ -0,0 +1,44 @@
+// Tag metadata from the `tags` table of the database dump, looked up by id
+// or by name.
+use crate::data::TagInfo;
+use std::collections::HashMap;
+
+#[derive(Debug, Clone, Default)]
+pub struct TagCatalog {
+    tags: HashMap<i32, TagInfo>,
+    // Lowercased names, as tag names are matched case-insensitively
+    ids_by_name: HashMap<String, i32>,
+}
+
+impl TagCatalog {
+    pub fn new(tags: impl IntoIterator<Item = TagInfo>) -> Self {
+        let tags: HashMap<i32, TagInfo> = tags.into_iter().map(|tag| (tag.tag_id, tag)).collect();
+        let ids_by_name = tags
+            .values()
+            .map(|tag| (tag.name.to_lowercase(), tag.tag_id))
+            .collect();
+        Self { tags, ids_by_name }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.tags.is_empty()
+    }
+
+    pub fn len(&self) -> usize {
+        self.tags.len()
+    }
+
+    pub fn get(&self, tag_id: i32) -> Option<&TagInfo> {
+        self.tags.get(&tag_id)
+    }
+
+    pub fn find(&self, name: &str) -> Option<&TagInfo> {
+        self.ids_by_name
+            .get(&name.to_lowercase())
+            .and_then(|tag_id| self.tags.get(tag_id))
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &TagInfo> {
+        self.tags.values()
+    }
+}
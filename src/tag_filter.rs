@@ -0,0 +1,410 @@
+// Per-query filters on the tags of the candidates: tags a recommendation must
+// have and tags it must not have, each with a minimum tag score. They are
+// checked against the average tag votes of each VN, taken before the tag
+// matrix drops or weighs any, so they apply to the candidates of every scorer
+// alike.
+use crate::error::{RecommenderError, Result};
+use crate::tag_catalog::TagCatalog;
+use crate::tag_rules;
+use crate::tag_tree::TagTree;
+use serde::{Deserialize, Serialize};
+use sprs::{CsVecViewI, SpIndex};
+use std::{fmt, str::FromStr};
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TagCondition {
+    // A tag by name, or by id as `g32`
+    pub tag: String,
+    // Also match every descendant of the tag in the tag hierarchy
+    pub descendants: bool,
+    // Lowest tag score, on VNDB's scale of 0 to 3, at which a VN counts as
+    // having the tag; any positive score does at 0
+    pub min_score: f64,
+}
+
+impl TagCondition {
+    pub fn new(tag: impl Into<String>) -> Self {
+        Self {
+            tag: tag.into(),
+            ..Self::default()
+        }
+    }
+
+    pub fn descendants(mut self, descendants: bool) -> Self {
+        self.descendants = descendants;
+        self
+    }
+
+    pub fn min_score(mut self, min_score: f64) -> Self {
+        self.min_score = min_score;
+        self
+    }
+}
+
+// Conditions on the command line: `Mystery`, `g32` or `tree:Romance` (the
+// tag and its descendants), each optionally with `>=SCORE`
+impl FromStr for TagCondition {
+    type Err = RecommenderError;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let (target, min_score) = match s.rsplit_once(">=") {
+            Some((target, score)) => match score.trim().parse() {
+                Ok(score) => (target, score),
+                Err(_) => {
+                    return Err(RecommenderError::InvalidConfig(format!(
+                        "invalid minimum tag score in '{}'",
+                        s
+                    )));
+                }
+            },
+            None => (s, 0.0),
+        };
+        let target = target.trim();
+
+        let condition = match target.strip_prefix("tree:") {
+            Some(tag) => TagCondition::new(tag.trim()).descendants(true),
+            None => TagCondition::new(target),
+        };
+        Ok(condition.min_score(min_score))
+    }
+}
+
+impl fmt::Display for TagCondition {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.descendants {
+            f.write_str("tree:")?;
+        }
+        f.write_str(&self.tag)?;
+        if self.min_score != 0.0 {
+            write!(f, ">={}", self.min_score)?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TagFilter {
+    // Tags every recommendation must have
+    pub required: Vec<TagCondition>,
+    // Tags no recommendation may have
+    pub forbidden: Vec<TagCondition>,
+}
+
+impl TagFilter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn require(mut self, condition: TagCondition) -> Self {
+        self.required.push(condition);
+        self
+    }
+
+    pub fn forbid(mut self, condition: TagCondition) -> Self {
+        self.forbidden.push(condition);
+        self
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.required.is_empty() && self.forbidden.is_empty()
+    }
+
+    // Look up the tags of the conditions in the tag catalog and hierarchy
+    pub(crate) fn resolve(&self, catalog: &TagCatalog, tree: &TagTree) -> Result<CandidateFilter> {
+        let resolve = |conditions: &[TagCondition]| {
+            conditions
+                .iter()
+                .map(|condition| ResolvedCondition::new(condition, catalog, tree))
+                .collect::<Result<Vec<_>>>()
+        };
+        Ok(CandidateFilter {
+            required: resolve(&self.required)?,
+            forbidden: resolve(&self.forbidden)?,
+        })
+    }
+}
+
+// A condition with its tag ids looked up
+#[derive(Debug, Clone)]
+struct ResolvedCondition {
+    tag_ids: Vec<i32>,
+    min_score: f64,
+}
+
+impl ResolvedCondition {
+    fn new(condition: &TagCondition, catalog: &TagCatalog, tree: &TagTree) -> Result<Self> {
+        let tag_id = tag_rules::find_tag(&condition.tag, catalog)?;
+        let mut tag_ids = vec![tag_id];
+        if condition.descendants {
+            // As with tag rules, descendants would silently match nothing
+            if tree.is_empty() {
+                return Err(RecommenderError::InvalidConfig(
+                    "tag descendants need the tags_parents table".into(),
+                ));
+            }
+            tag_ids.extend(tree.descendants(tag_id));
+        }
+        Ok(Self {
+            tag_ids,
+            min_score: condition.min_score,
+        })
+    }
+
+    // Whether a VN's average tag votes have any of the tags at the minimum
+    // score
+    fn matches<I: SpIndex>(&self, vn_tags: Option<CsVecViewI<f64, I>>) -> bool {
+        let Some(vn_tags) = vn_tags else {
+            return false;
+        };
+        self.tag_ids.iter().any(|&tag_id| {
+            vn_tags
+                .get(tag_id as usize)
+                .is_some_and(|&score| score > 0.0 && score >= self.min_score)
+        })
+    }
+}
+
+// A TagFilter resolved for one query
+#[derive(Debug, Clone, Default)]
+pub(crate) struct CandidateFilter {
+    required: Vec<ResolvedCondition>,
+    forbidden: Vec<ResolvedCondition>,
+}
+
+impl CandidateFilter {
+    pub(crate) fn is_empty(&self) -> bool {
+        self.required.is_empty() && self.forbidden.is_empty()
+    }
+
+    // Whether a VN passes, given its average tag votes; VNs without tags
+    // fail every requirement and pass every exclusion
+    pub(crate) fn allows<I: SpIndex>(&self, vn_tags: Option<CsVecViewI<f64, I>>) -> bool {
+        self.required
+            .iter()
+            .all(|condition| condition.matches(vn_tags))
+            && !self
+                .forbidden
+                .iter()
+                .any(|condition| condition.matches(vn_tags))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::als::Als;
+    use crate::config::{QueryOptions, RecommenderConfig, TagWeighting};
+    use crate::data::{Recommendation, RecommendationLists};
+    use crate::tag_rules::TagRule;
+    use crate::test_data;
+    use sprs::CsVec;
+
+    fn condition(s: &str) -> TagCondition {
+        s.parse().unwrap()
+    }
+
+    fn resolve(filter: &TagFilter) -> Result<CandidateFilter> {
+        let recommender = test_data::recommender(RecommenderConfig::default());
+        filter.resolve(&recommender.tag_catalog, &recommender.tag_tree)
+    }
+
+    // A VN's average tag votes, as (tag id, score)
+    fn vn_tags(scores: &[(usize, f64)]) -> CsVec<f64> {
+        let (indices, data) = scores.iter().copied().unzip();
+        CsVec::new(10, indices, data)
+    }
+
+    #[test]
+    fn parse_conditions() {
+        assert_eq!(condition("Mystery"), TagCondition::new("Mystery"));
+        assert_eq!(condition("g32"), TagCondition::new("g32"));
+        assert_eq!(
+            condition("tree:Romance"),
+            TagCondition::new("Romance").descendants(true)
+        );
+        assert_eq!(
+            condition(" tree: Mystery >= 1.5 "),
+            TagCondition::new("Mystery")
+                .descendants(true)
+                .min_score(1.5)
+        );
+
+        let err = "Mystery>=high".parse::<TagCondition>().unwrap_err();
+        assert_eq!(err.kind(), "InvalidConfig");
+    }
+
+    #[test]
+    fn display_round_trips() {
+        for s in ["Mystery", "g32", "tree:Romance", "tree:Mystery>=1.5"] {
+            assert_eq!(condition(s).to_string(), s);
+            assert_eq!(condition(&condition(s).to_string()), condition(s));
+        }
+    }
+
+    #[test]
+    fn resolve_descendants() {
+        let filter = resolve(
+            &TagFilter::new()
+                .require(condition("tree:Theme>=2"))
+                .forbid(condition("g3")),
+        )
+        .unwrap();
+
+        let mut tag_ids = filter.required[0].tag_ids.clone();
+        tag_ids.sort();
+        assert_eq!(tag_ids, [1, 2, 6]);
+        assert_eq!(filter.required[0].min_score, 2.0);
+        assert_eq!(filter.forbidden[0].tag_ids, [3]);
+
+        let filter = resolve(&TagFilter::new().require(condition("Theme"))).unwrap();
+        assert_eq!(filter.required[0].tag_ids, [1]);
+    }
+
+    #[test]
+    fn resolve_unknown_tag() {
+        let err = resolve(&TagFilter::new().forbid(condition("Nonexistent"))).unwrap_err();
+        assert_eq!(err.kind(), "UnknownTag");
+    }
+
+    #[test]
+    fn resolve_descendants_without_tree() {
+        let recommender = test_data::recommender(RecommenderConfig::default());
+        let filter = TagFilter::new().forbid(condition("tree:Theme"));
+        let err = filter
+            .resolve(&recommender.tag_catalog, &TagTree::default())
+            .unwrap_err();
+        assert_eq!(err.kind(), "InvalidConfig");
+
+        let filter = TagFilter::new().forbid(condition("Theme"));
+        assert!(
+            filter
+                .resolve(&recommender.tag_catalog, &TagTree::default())
+                .is_ok()
+        );
+    }
+
+    #[test]
+    fn allows_required_tags() {
+        let filter = resolve(
+            &TagFilter::new()
+                .require(condition("tree:Theme"))
+                .require(condition("Romance")),
+        )
+        .unwrap();
+
+        // Drama counts for Theme through the hierarchy
+        assert!(filter.allows(Some(vn_tags(&[(4, 1.0), (6, 0.5)]).view())));
+        assert!(!filter.allows(Some(vn_tags(&[(6, 0.5)]).view())));
+        assert!(!filter.allows(Some(vn_tags(&[(4, 1.0), (5, 3.0)]).view())));
+        // Negative averages mean the tag does not apply
+        assert!(!filter.allows(Some(vn_tags(&[(2, -1.0), (4, 1.0)]).view())));
+        assert!(!filter.allows::<usize>(None));
+    }
+
+    #[test]
+    fn allows_forbidden_tags() {
+        let filter = resolve(&TagFilter::new().forbid(condition("Netorare"))).unwrap();
+
+        assert!(!filter.allows(Some(vn_tags(&[(2, 3.0), (3, 0.5)]).view())));
+        assert!(filter.allows(Some(vn_tags(&[(2, 3.0), (3, -2.0)]).view())));
+        assert!(filter.allows(Some(vn_tags(&[(2, 3.0)]).view())));
+        assert!(filter.allows::<usize>(None));
+    }
+
+    #[test]
+    fn allows_min_scores() {
+        let filter = resolve(
+            &TagFilter::new()
+                .require(condition("Mystery>=2"))
+                .forbid(condition("Netorare>=1.5")),
+        )
+        .unwrap();
+
+        assert!(filter.allows(Some(vn_tags(&[(2, 2.0)]).view())));
+        assert!(filter.allows(Some(vn_tags(&[(2, 2.5), (3, 1.0)]).view())));
+        assert!(!filter.allows(Some(vn_tags(&[(2, 1.9)]).view())));
+        assert!(!filter.allows(Some(vn_tags(&[(2, 3.0), (3, 1.5)]).view())));
+    }
+
+    fn recommend(mut config: RecommenderConfig, tag_filter: TagFilter) -> RecommendationLists {
+        config.num_vns = 10;
+        let options = QueryOptions {
+            tag_filter,
+            ..QueryOptions::default()
+        };
+        test_data::recommender(config)
+            .recommend_for_seeds_with(&[(1, 1.0)], &options)
+            .unwrap()
+    }
+
+    fn vn_ids(list: &[Recommendation]) -> Vec<i32> {
+        let mut vn_ids: Vec<i32> = list.iter().map(|rec| rec.vn_id).collect();
+        vn_ids.sort();
+        vn_ids
+    }
+
+    #[test]
+    fn excludes_tags_left_out_of_the_matrix() {
+        // The Netorare votes are spoilers, so the matrix leaves them out
+        let config = RecommenderConfig {
+            max_tag_spoiler: 0,
+            ..RecommenderConfig::default()
+        };
+        let lists = recommend(config, TagFilter::new().forbid("Netorare".parse().unwrap()));
+
+        assert!(!lists.vote.is_empty());
+        for list in [&lists.tag, &lists.vote, &lists.combined] {
+            assert!(vn_ids(list).iter().all(|vn_id| ![3, 4].contains(vn_id)));
+        }
+    }
+
+    #[test]
+    fn scores_ignore_tag_weighting() {
+        let config = RecommenderConfig {
+            tag_weighting: TagWeighting::Bm25,
+            ..RecommenderConfig::default()
+        };
+        let lists = recommend(
+            config,
+            TagFilter::new().require("Mystery>=2".parse().unwrap()),
+        );
+
+        assert_eq!(vn_ids(&lists.tag), [2, 3, 7]);
+        for list in [&lists.vote, &lists.combined] {
+            assert!(vn_ids(list).iter().all(|vn_id| [2, 3, 7].contains(vn_id)));
+        }
+    }
+
+    #[test]
+    fn scores_ignore_tag_rules() {
+        let filter = TagFilter::new().require("Mystery>=2".parse().unwrap());
+        let config = RecommenderConfig::default();
+        let weighted = RecommenderConfig {
+            tag_rules: vec![TagRule::tag("Mystery").weight(0.25)],
+            ..RecommenderConfig::default()
+        };
+
+        assert_eq!(vn_ids(&recommend(config, filter.clone()).tag), [2, 3, 7]);
+        assert_eq!(vn_ids(&recommend(weighted, filter).tag), [2, 3, 7]);
+    }
+
+    #[test]
+    fn filters_als_user_recommendations() {
+        let mut recommender = test_data::recommender(RecommenderConfig::default());
+        recommender.train_als(&Als::default()).unwrap();
+        let options = QueryOptions {
+            tag_filter: TagFilter::new().forbid("ADV".parse().unwrap()),
+            ..QueryOptions::default()
+        };
+
+        // User 5 voted on v3 to v6; of the rest only v7 has no ADV votes
+        let unfiltered = recommender.get_als_user_recommendations(5).unwrap();
+        assert_eq!(vn_ids(&unfiltered), [1, 2, 7, 8]);
+        let filtered = recommender
+            .get_als_user_recommendations_with(5, &options)
+            .unwrap();
+        assert_eq!(vn_ids(&filtered), [7]);
+    }
+}
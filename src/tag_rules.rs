@@ -0,0 +1,270 @@
+// Rules that ignore or boost tags by name, id, category or subtree of the
+// tag hierarchy. They are resolved to a weight per tag id against the tag
+// catalog and hierarchy, once when the tag matrix is built and again for
+// any rules given with a query, which then apply on top of the first.
+use crate::config::DEFAULT_IGNORE_TAGS;
+use crate::data::TagCategory;
+use crate::error::{RecommenderError, Result};
+use crate::tag_catalog::TagCatalog;
+use crate::tag_tree::TagTree;
+use serde::{Deserialize, Serialize};
+use std::{collections::HashMap, fmt, str::FromStr};
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TagRule {
+    // A tag by name, or by id as `g32`
+    pub tag: Option<String>,
+    // Every tag of a category
+    pub category: Option<TagCategory>,
+    // Also match every descendant of `tag` in the tag hierarchy
+    pub descendants: bool,
+    // Multiplier for the votes of the matched tags: 0 ignores them, above 1
+    // boosts them
+    pub weight: f64,
+}
+
+impl Default for TagRule {
+    fn default() -> Self {
+        Self {
+            tag: None,
+            category: None,
+            descendants: false,
+            weight: 0.0,
+        }
+    }
+}
+
+impl TagRule {
+    // Ignore a tag, given by name or as `g32`
+    pub fn tag(tag: impl Into<String>) -> Self {
+        Self {
+            tag: Some(tag.into()),
+            ..Self::default()
+        }
+    }
+
+    // Ignore every tag of a category
+    pub fn category(category: TagCategory) -> Self {
+        Self {
+            category: Some(category),
+            ..Self::default()
+        }
+    }
+
+    pub fn descendants(mut self, descendants: bool) -> Self {
+        self.descendants = descendants;
+        self
+    }
+
+    pub fn weight(mut self, weight: f64) -> Self {
+        self.weight = weight;
+        self
+    }
+
+    // The tags this rule matches
+    fn tag_ids(&self, catalog: &TagCatalog, tree: &TagTree) -> Result<Vec<i32>> {
+        let mut tag_ids = Vec::new();
+        if let Some(name) = &self.tag {
+            let tag_id = find_tag(name, catalog)?;
+            tag_ids.push(tag_id);
+            if self.descendants {
+                // Like categories, descendants would silently match nothing
+                if tree.is_empty() {
+                    return Err(RecommenderError::InvalidConfig(
+                        "tag descendants need the tags_parents table".into(),
+                    ));
+                }
+                tag_ids.extend(tree.descendants(tag_id));
+            }
+        }
+        if let Some(category) = self.category {
+            // Without the catalog a category would silently match nothing
+            if catalog.is_empty() {
+                return Err(RecommenderError::InvalidConfig(
+                    "tag categories need the tags table".into(),
+                ));
+            }
+            tag_ids.extend(
+                catalog
+                    .iter()
+                    .filter(|tag| tag.category == category)
+                    .map(|tag| tag.tag_id),
+            );
+        }
+        if self.tag.is_none() && self.category.is_none() {
+            return Err(RecommenderError::InvalidConfig(
+                "a tag rule needs a tag or a category".into(),
+            ));
+        }
+        Ok(tag_ids)
+    }
+}
+
+// The rules of DEFAULT_IGNORE_TAGS: each tag by name where the catalog has
+// that name under the expected id, by id otherwise
+pub(crate) fn default_ignore_rules(catalog: &TagCatalog) -> Vec<TagRule> {
+    DEFAULT_IGNORE_TAGS
+        .iter()
+        .map(|&(name, tag_id)| match catalog.find(name) {
+            Some(tag) if tag.tag_id == tag_id => TagRule::tag(name),
+            _ => TagRule::tag(format!("g{}", tag_id)),
+        })
+        .collect()
+}
+
+// A tag by name, or by id as `g32` without needing the catalog
+pub(crate) fn find_tag(name: &str, catalog: &TagCatalog) -> Result<i32> {
+    match name.strip_prefix('g').and_then(|id| id.parse().ok()) {
+        Some(tag_id) => Ok(tag_id),
+        None => catalog
+            .find(name)
+            .map(|tag| tag.tag_id)
+            .ok_or_else(|| RecommenderError::UnknownTag(name.to_string())),
+    }
+}
+
+// Rules on the command line: `Mystery`, `g32`, `category:technical` or
+// `tree:Sexual Content` (the tag and its descendants), each optionally with
+// `=WEIGHT`; without a weight the tags are ignored
+impl FromStr for TagRule {
+    type Err = RecommenderError;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let (target, weight) = match s.rsplit_once('=') {
+            Some((target, weight)) => match weight.trim().parse() {
+                Ok(weight) => (target, weight),
+                Err(_) => {
+                    return Err(RecommenderError::InvalidConfig(format!(
+                        "invalid tag rule weight in '{}'",
+                        s
+                    )));
+                }
+            },
+            None => (s, 0.0),
+        };
+        let target = target.trim();
+
+        let rule = if let Some(category) = target.strip_prefix("category:") {
+            TagRule::category(category.trim().parse()?)
+        } else if let Some(tag) = target.strip_prefix("tree:") {
+            TagRule::tag(tag.trim()).descendants(true)
+        } else {
+            TagRule::tag(target)
+        };
+        Ok(rule.weight(weight))
+    }
+}
+
+impl fmt::Display for TagRule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match (&self.tag, self.category) {
+            (Some(tag), _) if self.descendants => write!(f, "tree:{}", tag)?,
+            (Some(tag), _) => f.write_str(tag)?,
+            (None, Some(category)) => write!(f, "category:{}", category)?,
+            (None, None) => {}
+        }
+        write!(f, "={}", self.weight)
+    }
+}
+
+// The weight of every tag some rule matches; where several rules match a
+// tag, the last one wins. Rules given with a query are resolved on their
+// own and multiply the tag matrix, which already has the config's rules
+// applied, so they do not replace those.
+pub fn resolve(
+    rules: &[TagRule],
+    catalog: &TagCatalog,
+    tree: &TagTree,
+) -> Result<HashMap<i32, f64>> {
+    let mut weights = HashMap::new();
+    for rule in rules {
+        for tag_id in rule.tag_ids(catalog, tree)? {
+            weights.insert(tag_id, rule.weight.max(0.0));
+        }
+    }
+    Ok(weights)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::{TagInfo, TagParent};
+
+    fn catalog() -> TagCatalog {
+        TagCatalog::new([
+            TagInfo {
+                tag_id: 1,
+                name: "Mystery".into(),
+                category: TagCategory::Content,
+                default_spoiler: 0,
+            },
+            TagInfo {
+                tag_id: 2,
+                name: "ADV".into(),
+                category: TagCategory::Technical,
+                default_spoiler: 0,
+            },
+        ])
+    }
+
+    #[test]
+    fn categories_need_the_catalog() {
+        let rules = [TagRule::category(TagCategory::Technical)];
+        let weights = resolve(&rules, &catalog(), &TagTree::default()).unwrap();
+        assert_eq!(weights, HashMap::from([(2, 0.0)]));
+
+        let err = resolve(&rules, &TagCatalog::default(), &TagTree::default()).unwrap_err();
+        assert_eq!(err.kind(), "InvalidConfig");
+    }
+
+    #[test]
+    fn default_ignore_rules_fall_back_to_ids() {
+        let tag = |tag_id, name: &str| TagInfo {
+            tag_id,
+            name: name.into(),
+            category: TagCategory::Content,
+            default_spoiler: 0,
+        };
+        let (first_name, first_id) = DEFAULT_IGNORE_TAGS[0];
+        let (second_name, second_id) = DEFAULT_IGNORE_TAGS[1];
+        // The first name under its id, the second under another one
+        let catalog = TagCatalog::new([tag(first_id, first_name), tag(7, second_name)]);
+        let rules = default_ignore_rules(&catalog);
+        assert_eq!(rules.len(), DEFAULT_IGNORE_TAGS.len());
+        assert_eq!(rules[0], TagRule::tag(first_name));
+        assert_eq!(rules[1], TagRule::tag(format!("g{}", second_id)));
+
+        // Either way the same tags are ignored, with or without the catalog
+        let ids: HashMap<i32, f64> = DEFAULT_IGNORE_TAGS
+            .iter()
+            .map(|(_, tag_id)| (*tag_id, 0.0))
+            .collect();
+        assert_eq!(resolve(&rules, &catalog, &TagTree::default()).unwrap(), ids);
+        let rules = default_ignore_rules(&TagCatalog::default());
+        assert!(
+            rules
+                .iter()
+                .all(|rule| rule.tag.as_ref().unwrap().starts_with('g'))
+        );
+        let weights = resolve(&rules, &TagCatalog::default(), &TagTree::default()).unwrap();
+        assert_eq!(weights, ids);
+    }
+
+    #[test]
+    fn descendants_need_the_tree() {
+        let rules = [TagRule::tag("Mystery").descendants(true)];
+        let tree = TagTree::new([TagParent {
+            tag_id: 2,
+            parent_id: 1,
+        }]);
+        let weights = resolve(&rules, &catalog(), &tree).unwrap();
+        assert_eq!(weights, HashMap::from([(1, 0.0), (2, 0.0)]));
+
+        let err = resolve(&rules, &catalog(), &TagTree::default()).unwrap_err();
+        assert_eq!(err.kind(), "InvalidConfig");
+        // A plain tag rule needs no tree
+        let rules = [TagRule::tag("Mystery")];
+        assert!(resolve(&rules, &catalog(), &TagTree::default()).is_ok());
+    }
+}
@@ -0,0 +1,85 @@
+// The VNDB tag hierarchy. Tags form a DAG rather than a tree: a tag may have
+// several parents, e.g. "Kuudere Heroine" is under "Heroine" and under
+// "Kuudere".
+use crate::data::TagParent;
+use std::collections::{HashMap, HashSet, VecDeque};
+
+#[derive(Debug, Clone, Default)]
+pub struct TagTree {
+    parents: HashMap<i32, Vec<i32>>,
+    children: HashMap<i32, Vec<i32>>,
+}
+
+impl TagTree {
+    pub fn new(links: impl IntoIterator<Item = TagParent>) -> Self {
+        let mut parents: HashMap<i32, Vec<i32>> = HashMap::new();
+        for link in links {
+            if link.tag_id != link.parent_id {
+                parents.entry(link.tag_id).or_default().push(link.parent_id);
+            }
+        }
+        let mut children: HashMap<i32, Vec<i32>> = HashMap::new();
+        for (tag_id, tag_parents) in parents.iter_mut() {
+            tag_parents.sort_unstable();
+            tag_parents.dedup();
+            for parent_id in tag_parents.iter() {
+                children.entry(*parent_id).or_default().push(*tag_id);
+            }
+        }
+        for tag_children in children.values_mut() {
+            tag_children.sort_unstable();
+        }
+        Self { parents, children }
+    }
+
+    // Every parent link, as loaded
+    pub fn links(&self) -> impl Iterator<Item = TagParent> + '_ {
+        self.parents.iter().flat_map(|(tag_id, parents)| {
+            parents.iter().map(|parent_id| TagParent {
+                tag_id: *tag_id,
+                parent_id: *parent_id,
+            })
+        })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.parents.is_empty()
+    }
+
+    pub fn parents(&self, tag_id: i32) -> &[i32] {
+        self.parents.get(&tag_id).map_or(&[], Vec::as_slice)
+    }
+
+    // Every ancestor of a tag with its distance in levels, by the shortest
+    // path when there are several
+    pub fn ancestors(&self, tag_id: i32) -> Vec<(i32, u32)> {
+        let mut ancestors = Vec::new();
+        let mut seen = HashSet::from([tag_id]);
+        let mut queue = VecDeque::from([(tag_id, 0)]);
+        while let Some((tag_id, depth)) = queue.pop_front() {
+            for &parent_id in self.parents(tag_id) {
+                if seen.insert(parent_id) {
+                    ancestors.push((parent_id, depth + 1));
+                    queue.push_back((parent_id, depth + 1));
+                }
+            }
+        }
+        ancestors
+    }
+
+    // Every tag below a tag in the hierarchy
+    pub fn descendants(&self, tag_id: i32) -> Vec<i32> {
+        let mut descendants = Vec::new();
+        let mut seen = HashSet::from([tag_id]);
+        let mut stack = vec![tag_id];
+        while let Some(tag_id) = stack.pop() {
+            for &child_id in self.children.get(&tag_id).map_or(&[][..], Vec::as_slice) {
+                if seen.insert(child_id) {
+                    descendants.push(child_id);
+                    stack.push(child_id);
+                }
+            }
+        }
+        descendants
+    }
+}
@@ -0,0 +1,123 @@
+// A small dataset in the dump formats for the unit tests: eight VNs, six
+// voters and a handful of tags, with "Mystery" and "Drama" under "Theme" in
+// the tag hierarchy and the Netorare votes of v3 and v4 marked as major
+// spoilers.
+use crate::config::RecommenderConfig;
+use crate::data::{Recommendation, RecommendationLists};
+use crate::recommender::VisualNovelRecommender;
+use crate::source::{DataSource, DataSources};
+
+pub(crate) const TITLES: &str = "\
+v1\ten\tt\tFirst\t\\N
+v2\ten\tt\tSecond\t\\N
+v3\ten\tt\tThird\t\\N
+v4\ten\tt\tFourth\t\\N
+v5\ten\tt\tFifth\t\\N
+v6\ten\tt\tSixth\t\\N
+v7\ten\tt\tSeventh\t\\N
+v8\ten\tt\tEighth\t\\N
+";
+
+pub(crate) const VOTES: &str = "\
+1 1 90 2020-01-01
+2 1 80 2020-01-01
+3 1 70 2020-01-01
+4 1 60 2020-01-01
+1 2 80 2020-01-01
+3 2 90 2020-01-01
+5 2 70 2020-01-01
+7 2 60 2020-01-01
+2 3 70 2020-01-01
+4 3 90 2020-01-01
+6 3 80 2020-01-01
+1 4 60 2020-01-01
+2 4 90 2020-01-01
+7 4 80 2020-01-01
+8 4 50 2020-01-01
+3 5 80 2020-01-01
+4 5 70 2020-01-01
+5 5 60 2020-01-01
+6 5 90 2020-01-01
+5 6 90 2020-01-01
+6 6 70 2020-01-01
+7 6 80 2020-01-01
+8 6 60 2020-01-01
+";
+
+pub(crate) const TAG_INFO: &str = "\
+g1\tcont\t0\tt\tf\tTheme\t
+g2\tcont\t0\tt\tt\tMystery\t
+g3\tero\t0\tt\tt\tNetorare\t
+g4\tcont\t0\tt\tt\tRomance\t
+g5\ttech\t0\tt\tt\tADV\t
+g6\tcont\t0\tt\tt\tDrama\t
+";
+
+pub(crate) const TAG_PARENTS: &str = "\
+g2\tg1\tt
+g6\tg1\tt
+";
+
+pub(crate) const TAGS: &str = "\
+2020-01-01\tg2\tv1\tu1\t3\t0\tf
+2020-01-01\tg4\tv1\tu1\t2\t0\tf
+2020-01-01\tg5\tv1\tu1\t3\t0\tf
+2020-01-01\tg2\tv2\tu1\t3\t0\tf
+2020-01-01\tg4\tv2\tu1\t1\t0\tf
+2020-01-01\tg5\tv2\tu1\t3\t0\tf
+2020-01-01\tg2\tv3\tu1\t2\t0\tf
+2020-01-01\tg3\tv3\tu1\t3\t2\tf
+2020-01-01\tg5\tv3\tu1\t3\t0\tf
+2020-01-01\tg4\tv4\tu1\t3\t0\tf
+2020-01-01\tg3\tv4\tu1\t2\t2\tf
+2020-01-01\tg5\tv4\tu1\t2\t0\tf
+2020-01-01\tg2\tv5\tu1\t1\t0\tf
+2020-01-01\tg6\tv5\tu1\t3\t0\tf
+2020-01-01\tg4\tv6\tu1\t3\t0\tf
+2020-01-01\tg6\tv6\tu1\t2\t0\tf
+2020-01-01\tg2\tv7\tu1\t3\t0\tf
+2020-01-01\tg6\tv7\tu1\t1\t0\tf
+2020-01-01\tg5\tv8\tu1\t3\t0\tf
+";
+
+pub(crate) fn sources() -> DataSources<'static> {
+    DataSources::new(DataSource::text(TITLES))
+        .votes(DataSource::text(VOTES))
+        .tags(DataSource::text(TAGS))
+        .tag_info(DataSource::text(TAG_INFO))
+        .tag_parents(DataSource::text(TAG_PARENTS))
+}
+
+pub(crate) fn recommender(config: RecommenderConfig) -> VisualNovelRecommender {
+    VisualNovelRecommender::from_sources(config, sources()).unwrap()
+}
+
+// Compare ranked lists by VN and score, allowing for the rounding that
+// summing in hash map order brings
+pub(crate) fn assert_same_list(left: &[Recommendation], right: &[Recommendation]) {
+    let ids = |list: &[Recommendation]| list.iter().map(|rec| rec.vn_id).collect::<Vec<_>>();
+    assert_eq!(ids(left), ids(right));
+    for (left, right) in left.iter().zip(right) {
+        assert!(
+            (left.score - right.score).abs() < 1e-9,
+            "v{}: {} != {}",
+            left.vn_id,
+            left.score,
+            right.score
+        );
+    }
+}
+
+pub(crate) fn assert_same_lists(left: &RecommendationLists, right: &RecommendationLists) {
+    assert_same_list(&left.tag, &right.tag);
+    assert_same_list(&left.vote, &right.vote);
+    assert_same_list(&left.combined, &right.combined);
+    assert_eq!(left.tag_weighting, right.tag_weighting);
+    assert_eq!(
+        left.extra.keys().collect::<Vec<_>>(),
+        right.extra.keys().collect::<Vec<_>>()
+    );
+    for (left, right) in left.extra.values().zip(right.extra.values()) {
+        assert_same_list(left, right);
+    }
+}
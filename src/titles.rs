@@ -0,0 +1,204 @@
+// Title lookup by VN id. All titles of a VN are kept together so looking them
+// up does not scan the whole title list, and the displayed title is picked
+// from a language preference list.
+use crate::data::VnTitle;
+use std::{collections::HashMap, sync::Arc};
+
+// Titles grouped by VN id, built once when the titles are loaded
+#[derive(Debug, Clone, Default)]
+pub struct TitleIndex {
+    // All titles, sorted by VN id and in file order within a VN
+    titles: Vec<VnTitle>,
+    // Start and end of each VN's titles in `titles`
+    ranges: HashMap<i32, (usize, usize)>,
+}
+
+impl TitleIndex {
+    pub fn new(mut titles: Vec<VnTitle>) -> Self {
+        titles.sort_by_key(|title| title.vn_id);
+
+        let mut ranges = HashMap::new();
+        let mut start = 0;
+        for (i, title) in titles.iter().enumerate() {
+            if titles[start].vn_id != title.vn_id {
+                ranges.insert(titles[start].vn_id, (start, i));
+                start = i;
+            }
+        }
+        if let Some(last) = titles.last() {
+            ranges.insert(last.vn_id, (start, titles.len()));
+        }
+
+        Self { titles, ranges }
+    }
+
+    // All titles of a VN, empty if it has none
+    pub fn get(&self, vn_id: i32) -> &[VnTitle] {
+        match self.ranges.get(&vn_id) {
+            Some(&(start, end)) => &self.titles[start..end],
+            None => &[],
+        }
+    }
+
+    pub fn contains(&self, vn_id: i32) -> bool {
+        self.ranges.contains_key(&vn_id)
+    }
+
+    pub fn iter(&self) -> std::slice::Iter<'_, VnTitle> {
+        self.titles.iter()
+    }
+
+    // Number of titles (not VNs)
+    pub fn len(&self) -> usize {
+        self.titles.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.titles.is_empty()
+    }
+
+    pub fn last_vn_id(&self) -> Option<i32> {
+        self.titles.last().map(|title| title.vn_id)
+    }
+
+    // The title of a VN in the first available language of `languages`,
+    // falling back to an official title. With `romanized` the latin form is
+    // used when there is one.
+    pub fn display_title(
+        &self,
+        vn_id: i32,
+        languages: &[String],
+        romanized: bool,
+    ) -> Option<Arc<str>> {
+        let vn_data = self.get(vn_id);
+        let refs: Vec<TitleRef> = vn_data
+            .iter()
+            .map(|vn| TitleRef {
+                language: &vn.language,
+                official: vn.official,
+                latin_title: vn.latin_title.as_deref(),
+            })
+            .collect();
+
+        match pick_title(&refs, languages, romanized)? {
+            (i, true) => vn_data[i].latin_title.clone(),
+            (i, false) => Some(vn_data[i].title.clone()),
+        }
+    }
+}
+
+// Borrowed view of the fields of a VnTitle that pick_title looks at
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct TitleRef<'a> {
+    pub language: &'a str,
+    pub official: bool,
+    pub latin_title: Option<&'a str>,
+}
+
+// Pick the display title among all titles of a VN: the first language of
+// `languages` that has a title, then an official title. With `romanized`
+// a title with a latin form is preferred and that form used.
+// Returns the index of the chosen title and whether its latin form is used.
+pub(crate) fn pick_title(
+    titles: &[TitleRef],
+    languages: &[String],
+    romanized: bool,
+) -> Option<(usize, bool)> {
+    let has_latin = |t: &TitleRef| t.latin_title.is_some_and(|latin| latin != "\\N");
+
+    let pick = |matches: &dyn Fn(&TitleRef) -> bool| {
+        // Check for a Latin title
+        if romanized
+            && let Some(i) = titles
+                .iter()
+                .position(|t| matches(t) && t.latin_title.is_some())
+            && has_latin(&titles[i])
+        {
+            return Some((i, true));
+        }
+
+        // Check for the title itself
+        titles.iter().position(matches).map(|i| (i, false))
+    };
+
+    for language in languages {
+        if let Some(choice) = pick(&|t: &TitleRef| t.language.eq_ignore_ascii_case(language)) {
+            return Some(choice);
+        }
+    }
+
+    // Check for official title
+    pick(&|t: &TitleRef| t.official)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn title(
+        vn_id: i32,
+        language: &str,
+        official: bool,
+        title: &str,
+        latin: Option<&str>,
+    ) -> VnTitle {
+        VnTitle {
+            vn_id,
+            language: language.into(),
+            official,
+            title: title.into(),
+            latin_title: latin.map(Into::into),
+        }
+    }
+
+    fn index() -> TitleIndex {
+        TitleIndex::new(vec![
+            title(4, "ja", true, "クラナド", Some("Clannad")),
+            title(17, "en", true, "Ever17", None),
+            title(4, "en", true, "CLANNAD", None),
+            title(17, "ja", true, "エバー17", Some("Ever17 Romaji")),
+            title(4, "zh-Hans", false, "团子大家族", None),
+            title(9, "ru", false, "Unofficial", None),
+        ])
+    }
+
+    fn languages(languages: &[&str]) -> Vec<String> {
+        languages
+            .iter()
+            .map(|language| language.to_string())
+            .collect()
+    }
+
+    #[test]
+    fn groups_titles_by_vn() {
+        let index = index();
+        assert_eq!(index.len(), 6);
+        let clannad: Vec<&str> = index.get(4).iter().map(|t| &*t.title).collect();
+        assert_eq!(clannad, ["クラナド", "CLANNAD", "团子大家族"]);
+        assert_eq!(index.get(17).len(), 2);
+        assert!(index.get(5).is_empty());
+        assert!(index.contains(9) && !index.contains(5));
+        assert_eq!(index.last_vn_id(), Some(17));
+        assert!(TitleIndex::default().is_empty());
+    }
+
+    #[test]
+    fn display_title_by_language() {
+        let index = index();
+        let title = |vn_id, preferred: &[&str], romanized| {
+            index
+                .display_title(vn_id, &languages(preferred), romanized)
+                .map(|title| title.to_string())
+        };
+
+        assert_eq!(title(4, &["en", "ja"], true).as_deref(), Some("CLANNAD"));
+        assert_eq!(title(4, &["zh-hans"], true).as_deref(), Some("团子大家族"));
+        // Titles in non-latin scripts show their romanized form if wanted
+        assert_eq!(title(4, &["ja"], true).as_deref(), Some("Clannad"));
+        assert_eq!(title(4, &["ja"], false).as_deref(), Some("クラナド"));
+        // Without a title in any preferred language an official one is used
+        assert_eq!(title(17, &["de"], false).as_deref(), Some("Ever17"));
+        assert_eq!(title(9, &["de"], false), None);
+        assert_eq!(title(5, &["en"], false), None);
+    }
+}
@@ -1,5 +1,11 @@
+use crate::config::{QueryOptions, RecommenderConfig, TagWeighting};
+use crate::data::Recommendation;
+use crate::error::RecommenderError;
+use crate::parser;
 use crate::recommender::VisualNovelRecommender;
+use crate::source::DataSource;
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
 use wasm_bindgen::prelude::*;
 
 #[wasm_bindgen]
@@ -19,11 +25,22 @@ macro_rules! console_log {
     ($($t:tt)*) => (log(&format_args!($($t)*).to_string()))
 }
 
+// Convert a RecommenderError into a JS Error whose `name` is the error kind,
+// so callers can branch on e.g. `err.name === "UnknownVn"`
+fn to_js_error(e: RecommenderError) -> JsValue {
+    let error = js_sys::Error::new(&e.to_string());
+    error.set_name(e.kind());
+    error.into()
+}
+
 #[derive(Serialize, Deserialize)]
 pub struct RecommendationResult {
     pub tag_recommendations: Vec<RecommendationItem>,
     pub user_recommendations: Vec<RecommendationItem>,
     pub combined_recommendations: Vec<RecommendationItem>,
+    // Weighting of the tag matrix behind tag_recommendations: "none", "idf"
+    // or "bm25"
+    pub tag_weighting: TagWeighting,
 }
 
 #[derive(Serialize, Deserialize)]
@@ -31,6 +48,26 @@ pub struct RecommendationItem {
     pub id: i32,
     pub title: String,
     pub url: String,
+    pub score: f64,
+    pub source_scores: BTreeMap<String, f64>,
+    pub rank: usize,
+}
+
+// Pair VN ids with their weights, 1.0 where none is given
+fn seeds(vn_ids: &[i32], weights: &[f64]) -> Vec<(i32, f64)> {
+    vn_ids
+        .iter()
+        .enumerate()
+        .map(|(i, vn_id)| (*vn_id, weights.get(i).copied().unwrap_or(1.0)))
+        .collect()
+}
+
+// Per-query options from JavaScript; undefined or null for none
+fn query_options(options: JsValue) -> Result<QueryOptions, JsValue> {
+    if options.is_undefined() || options.is_null() {
+        return Ok(QueryOptions::default());
+    }
+    Ok(serde_wasm_bindgen::from_value(options)?)
 }
 
 #[wasm_bindgen]
@@ -38,64 +75,168 @@ pub struct WasmRecommender {
     recommender: VisualNovelRecommender,
 }
 
+impl WasmRecommender {
+    fn recommend(&self, seeds: &[(i32, f64)]) -> Result<JsValue, JsValue> {
+        self.recommend_with(seeds, &QueryOptions::default())
+    }
+
+    fn recommend_with(
+        &self,
+        seeds: &[(i32, f64)],
+        options: &QueryOptions,
+    ) -> Result<JsValue, JsValue> {
+        let lists = self
+            .recommender
+            .recommend_for_seeds_with(seeds, options)
+            .map_err(to_js_error)?;
+
+        let result = RecommendationResult {
+            tag_recommendations: self.items(lists.tag),
+            user_recommendations: self.items(lists.vote),
+            combined_recommendations: self.items(lists.combined),
+            tag_weighting: lists.tag_weighting,
+        };
+
+        // Plain objects rather than Maps for source_scores
+        let serializer = serde_wasm_bindgen::Serializer::new().serialize_maps_as_objects(true);
+        result.serialize(&serializer).map_err(JsValue::from)
+    }
+
+    fn items(&self, recommendations: Vec<Recommendation>) -> Vec<RecommendationItem> {
+        recommendations
+            .into_iter()
+            .map(|rec| RecommendationItem {
+                id: rec.vn_id,
+                title: self.recommender.get_title(rec.vn_id).to_string(),
+                url: format!("https://vndb.org/v{}", rec.vn_id),
+                score: rec.score,
+                source_scores: rec.source_scores,
+                rank: rec.rank,
+            })
+            .collect()
+    }
+}
+
 #[wasm_bindgen]
 impl WasmRecommender {
     #[wasm_bindgen(constructor)]
-    pub fn new() -> WasmRecommender {
+    pub fn new() -> Result<WasmRecommender, JsValue> {
         console_error_panic_hook::set_once();
 
-        let ignore_tags = vec![32, 2040, 2461, 1434, 1431, 43];
+        let recommender =
+            VisualNovelRecommender::new(RecommenderConfig::default()).map_err(to_js_error)?;
+
+        Ok(WasmRecommender { recommender })
+    }
+
+    // Construct from a config object; missing fields take their defaults
+    #[wasm_bindgen]
+    pub fn with_config(config: JsValue) -> Result<WasmRecommender, JsValue> {
+        console_error_panic_hook::set_once();
 
-        let recommender = VisualNovelRecommender::new(
-            25,  // num_recommendations
-            1.5, // tag_weight
-            1.0, // vote_weight
-            2.0, // tag_exp
-            1.0, // vote_exp
-            ignore_tags,
-            false, // verbose
-            false, // skip_recs
-        );
+        let config: RecommenderConfig = serde_wasm_bindgen::from_value(config)?;
+        let recommender = VisualNovelRecommender::new(config).map_err(to_js_error)?;
 
-        WasmRecommender { recommender }
+        Ok(WasmRecommender { recommender })
     }
 
     #[wasm_bindgen]
-    pub fn get_recommendations(&self, vn_id: i32) -> JsValue {
+    pub fn get_recommendations(&self, vn_id: i32) -> Result<JsValue, JsValue> {
         console_log!("Getting recommendations for VN ID: {}", vn_id);
 
-        let tag_recs = self.recommender.get_tag_recommendations(vn_id);
-        let user_recs = self.recommender.get_user_recommendations(vn_id);
-        let combined_recs = self.recommender.get_combined_recommendations(vn_id);
+        self.recommend(&[(vn_id, 1.0)])
+    }
+
+    // Recommendations for several seed VNs; `weights` may be empty to weigh
+    // all seeds equally. A negative weight means "less like this VN".
+    #[wasm_bindgen]
+    pub fn get_recommendations_for_seeds(
+        &self,
+        vn_ids: Vec<i32>,
+        weights: Vec<f64>,
+    ) -> Result<JsValue, JsValue> {
+        console_log!("Getting recommendations for VN IDs: {:?}", vn_ids);
 
-        let result = RecommendationResult {
-            tag_recommendations: tag_recs
-                .iter()
-                .map(|&id| RecommendationItem {
-                    id,
-                    title: self.recommender.get_title(id).to_string(),
-                    url: format!("https://vndb.org/v{}", id),
-                })
-                .collect(),
-            user_recommendations: user_recs
-                .iter()
-                .map(|&id| RecommendationItem {
-                    id,
-                    title: self.recommender.get_title(id).to_string(),
-                    url: format!("https://vndb.org/v{}", id),
-                })
-                .collect(),
-            combined_recommendations: combined_recs
-                .iter()
-                .map(|&id| RecommendationItem {
-                    id,
-                    title: self.recommender.get_title(id).to_string(),
-                    url: format!("https://vndb.org/v{}", id),
-                })
-                .collect(),
-        };
+        self.recommend(&seeds(&vn_ids, &weights))
+    }
+
+    // get_recommendations_for_seeds with per-query options, e.g.
+    // `{ tag_rules: [{ tag: "Mystery", weight: 2 }, { category: "technical" }] }`;
+    // tag rules further ignore or boost tags for this query only, on top of
+    // the rules of the config. A tag filter such as
+    // `{ tag_filter: { required: [{ tag: "Mystery", min_score: 2 }],
+    // forbidden: [{ tag: "Netorare", descendants: true }] } }` keeps only the
+    // recommendations with and without those tags.
+    #[wasm_bindgen]
+    pub fn get_recommendations_for_seeds_with(
+        &self,
+        vn_ids: Vec<i32>,
+        weights: Vec<f64>,
+        options: JsValue,
+    ) -> Result<JsValue, JsValue> {
+        console_log!("Getting recommendations for VN IDs: {:?}", vn_ids);
 
-        serde_wasm_bindgen::to_value(&result).unwrap()
+        let options = query_options(options)?;
+        self.recommend_with(&seeds(&vn_ids, &weights), &options)
+    }
+
+    // Personal recommendations for a VNDB user from their votes in the loaded data
+    #[wasm_bindgen]
+    pub fn get_recommendations_for_user(&self, user_id: i32) -> Result<JsValue, JsValue> {
+        self.get_recommendations_for_user_with(user_id, JsValue::UNDEFINED)
+    }
+
+    // get_recommendations_for_user with per-query options, as for
+    // get_recommendations_for_seeds_with
+    #[wasm_bindgen]
+    pub fn get_recommendations_for_user_with(
+        &self,
+        user_id: i32,
+        options: JsValue,
+    ) -> Result<JsValue, JsValue> {
+        console_log!("Getting recommendations for user ID: {}", user_id);
+
+        let options = query_options(options)?;
+        let seeds = self
+            .recommender
+            .user_profile(user_id)
+            .map_err(to_js_error)?;
+        self.recommend_with(&seeds, &options)
+    }
+
+    // Personal recommendations from the contents of an uploaded vote list:
+    // VNDB's XML list export, or `vn_id,vote` CSV or JSON, with votes on a
+    // scale up to `vote_scale` (100 for VNDB's exports, 10 for 1-10 votes)
+    #[wasm_bindgen]
+    pub fn get_recommendations_for_vote_list(
+        &self,
+        contents: &str,
+        vote_scale: f64,
+    ) -> Result<JsValue, JsValue> {
+        self.get_recommendations_for_vote_list_with(contents, vote_scale, JsValue::UNDEFINED)
+    }
+
+    // get_recommendations_for_vote_list with per-query options, as for
+    // get_recommendations_for_seeds_with
+    #[wasm_bindgen]
+    pub fn get_recommendations_for_vote_list_with(
+        &self,
+        contents: &str,
+        vote_scale: f64,
+        options: JsValue,
+    ) -> Result<JsValue, JsValue> {
+        let options = query_options(options)?;
+        let (path, reader) = DataSource::text(contents)
+            .open("vote list")
+            .map_err(to_js_error)?;
+        let parsed = parser::parse_vote_list(reader, &path).map_err(to_js_error)?;
+        console_log!("{}", parsed.report);
+
+        let seeds = self
+            .recommender
+            .vote_list_profile(&parsed.records, vote_scale)
+            .map_err(to_js_error)?;
+        self.recommend_with(&seeds, &options)
     }
 
     #[wasm_bindgen]
@@ -103,57 +244,227 @@ impl WasmRecommender {
         self.recommender.get_title(vn_id).to_string()
     }
 
+    // Set the languages titles are shown in, most preferred first
+    #[wasm_bindgen]
+    pub fn set_title_languages(&mut self, languages: Vec<String>, romanized: bool) {
+        self.recommender.config.title_languages = languages;
+        self.recommender.config.romanized_titles = romanized;
+    }
+
+    #[wasm_bindgen]
+    pub fn load_vn_titles(&mut self, data: &str) -> Result<(), JsValue> {
+        console_log!("Loading VN titles...");
+
+        match self.recommender.load_titles(DataSource::text(data)) {
+            Ok(report) => {
+                console_log!("VN titles loaded successfully: {}", report);
+                Ok(())
+            }
+            Err(e) => {
+                console_log!("Error loading VN titles: {}", e);
+                Err(to_js_error(e))
+            }
+        }
+    }
+
+    #[wasm_bindgen]
+    pub fn load_votes(&mut self, data: &str) -> Result<(), JsValue> {
+        console_log!("Loading votes data...");
+
+        match self.recommender.load_votes(DataSource::text(data)) {
+            Ok(report) => {
+                console_log!("Votes data loaded successfully: {}", report);
+                Ok(())
+            }
+            Err(e) => {
+                console_log!("Error loading votes: {}", e);
+                Err(to_js_error(e))
+            }
+        }
+    }
+
     #[wasm_bindgen]
-    pub fn load_vn_titles(&mut self, data: &str) -> bool {
+    pub fn load_tags(&mut self, data: &str) -> Result<(), JsValue> {
+        console_log!("Loading tags data...");
+
+        match self.recommender.load_tags(DataSource::text(data)) {
+            Ok(report) => {
+                console_log!("Tags data loaded successfully: {}", report);
+                Ok(())
+            }
+            Err(e) => {
+                console_log!("Error loading tags: {}", e);
+                Err(to_js_error(e))
+            }
+        }
+    }
+
+    // The tag catalog (the `tags` table), for tag names and categories in
+    // tag rules and filters
+    #[wasm_bindgen]
+    pub fn load_tag_info(&mut self, data: &str) -> Result<(), JsValue> {
+        console_log!("Loading tag info data...");
+
+        match self.recommender.load_tag_info(DataSource::text(data)) {
+            Ok(report) => {
+                console_log!("Tag info data loaded successfully: {}", report);
+                Ok(())
+            }
+            Err(e) => {
+                console_log!("Error loading tag info: {}", e);
+                Err(to_js_error(e))
+            }
+        }
+    }
+
+    // The tag hierarchy (the `tags_parents` table)
+    #[wasm_bindgen]
+    pub fn load_tag_parents(&mut self, data: &str) -> Result<(), JsValue> {
+        console_log!("Loading tag parents data...");
+
+        match self.recommender.load_tag_parents(DataSource::text(data)) {
+            Ok(report) => {
+                console_log!("Tag parents data loaded successfully: {}", report);
+                Ok(())
+            }
+            Err(e) => {
+                console_log!("Error loading tag parents: {}", e);
+                Err(to_js_error(e))
+            }
+        }
+    }
+
+    // Byte variants of the loaders above, for uploads that may be gzip or
+    // zstd compressed
+    #[wasm_bindgen]
+    pub fn load_vn_titles_bytes(&mut self, data: &[u8]) -> Result<(), JsValue> {
         console_log!("Loading VN titles...");
 
-        match self.recommender.load_vn_titles_from_string(data) {
-            Ok(_) => {
-                console_log!("VN titles loaded successfully");
-                true
+        match self.recommender.load_titles(DataSource::bytes(data)) {
+            Ok(report) => {
+                console_log!("VN titles loaded successfully: {}", report);
+                Ok(())
             }
             Err(e) => {
                 console_log!("Error loading VN titles: {}", e);
-                false
+                Err(to_js_error(e))
             }
         }
     }
 
     #[wasm_bindgen]
-    pub fn load_votes(&mut self, data: &str) -> bool {
+    pub fn load_votes_bytes(&mut self, data: &[u8]) -> Result<(), JsValue> {
         console_log!("Loading votes data...");
 
-        match self.recommender.load_ratings_from_string(data) {
-            Ok(_) => {
-                console_log!("Votes data loaded successfully");
-                true
+        match self.recommender.load_votes(DataSource::bytes(data)) {
+            Ok(report) => {
+                console_log!("Votes data loaded successfully: {}", report);
+                Ok(())
             }
             Err(e) => {
                 console_log!("Error loading votes: {}", e);
-                false
+                Err(to_js_error(e))
             }
         }
     }
 
     #[wasm_bindgen]
-    pub fn load_tags(&mut self, data: &str) -> bool {
+    pub fn load_tags_bytes(&mut self, data: &[u8]) -> Result<(), JsValue> {
         console_log!("Loading tags data...");
 
-        match self.recommender.load_tags_from_string(data) {
-            Ok(_) => {
-                console_log!("Tags data loaded successfully");
-                true
+        match self.recommender.load_tags(DataSource::bytes(data)) {
+            Ok(report) => {
+                console_log!("Tags data loaded successfully: {}", report);
+                Ok(())
             }
             Err(e) => {
                 console_log!("Error loading tags: {}", e);
-                false
+                Err(to_js_error(e))
             }
         }
     }
 
+    #[wasm_bindgen]
+    pub fn load_tag_info_bytes(&mut self, data: &[u8]) -> Result<(), JsValue> {
+        console_log!("Loading tag info data...");
+
+        match self.recommender.load_tag_info(DataSource::bytes(data)) {
+            Ok(report) => {
+                console_log!("Tag info data loaded successfully: {}", report);
+                Ok(())
+            }
+            Err(e) => {
+                console_log!("Error loading tag info: {}", e);
+                Err(to_js_error(e))
+            }
+        }
+    }
+
+    #[wasm_bindgen]
+    pub fn load_tag_parents_bytes(&mut self, data: &[u8]) -> Result<(), JsValue> {
+        console_log!("Loading tag parents data...");
+
+        match self.recommender.load_tag_parents(DataSource::bytes(data)) {
+            Ok(report) => {
+                console_log!("Tag parents data loaded successfully: {}", report);
+                Ok(())
+            }
+            Err(e) => {
+                console_log!("Error loading tag parents: {}", e);
+                Err(to_js_error(e))
+            }
+        }
+    }
+
+    // Load titles and tags from a database dump tarball (vndb-db-*.tar.zst)
+    #[wasm_bindgen]
+    pub fn load_db_dump(&mut self, data: &[u8]) -> Result<(), JsValue> {
+        console_log!("Loading database dump...");
+
+        match self.recommender.load_db_dump(DataSource::bytes(data)) {
+            Ok(reports) => {
+                for report in reports {
+                    console_log!("Database dump entry loaded: {}", report);
+                }
+                Ok(())
+            }
+            Err(e) => {
+                console_log!("Error loading database dump: {}", e);
+                Err(to_js_error(e))
+            }
+        }
+    }
+
+    // Replace the model with one loaded from a snapshot (see save_snapshot)
+    #[wasm_bindgen]
+    pub fn load_snapshot(&mut self, data: &[u8]) -> Result<(), JsValue> {
+        console_log!("Loading model snapshot...");
+
+        match VisualNovelRecommender::load_snapshot(DataSource::bytes(data)) {
+            Ok(recommender) => {
+                console_log!("Model snapshot loaded successfully");
+                self.recommender = recommender;
+                Ok(())
+            }
+            Err(e) => {
+                console_log!("Error loading model snapshot: {}", e);
+                Err(to_js_error(e))
+            }
+        }
+    }
+
+    #[wasm_bindgen]
+    pub fn save_snapshot(&self) -> Result<Vec<u8>, JsValue> {
+        let mut data = Vec::new();
+        self.recommender
+            .save_snapshot(&mut data)
+            .map_err(to_js_error)?;
+        Ok(data)
+    }
+
     // Keep the old method for backwards compatibility
     #[wasm_bindgen]
-    pub fn process_csv_data(&mut self, csv_data: &str) -> bool {
+    pub fn process_csv_data(&mut self, csv_data: &str) -> Result<(), JsValue> {
         console_log!("Processing generic CSV data...");
         // For now, assume it's votes data if called
         self.load_votes(csv_data)
@@ -54,36 +54,39 @@ class RecommendationApp {
             this.updateDataStatus(statusId, 'loading', 'Reading file...');
             this.showUploadProgress(true);
 
-            const fileContent = await this.readFileAsText(file);
+            // Read raw bytes so gzip / zstd compressed dumps work too
+            const fileContent = new Uint8Array(await this.readFileAsArrayBuffer(file));
             
-            let success = false;
+            // The loaders throw an Error whose name is the failure kind
             switch (dataType) {
                 case 'vnTitles':
-                    success = this.recommender.load_vn_titles(fileContent);
-                    if (success) {
-                        this.dataLoaded.titles = true;
-                        this.updateDataStatus(statusId, 'success', `Loaded (${file.name})`);
+                    if (file.name.endsWith('.snapshot')) {
+                        // A model snapshot replaces all three dumps
+                        this.recommender.load_snapshot(fileContent);
+                        this.dataLoaded.tags = true;
+                        this.dataLoaded.votes = true;
+                        this.updateDataStatus('tagsStatus', 'success', `Loaded (${file.name})`);
+                        this.updateDataStatus('votesStatus', 'success', `Loaded (${file.name})`);
+                    } else if (/^vndb-db-.*\.tar/.test(file.name)) {
+                        // A database dump holds both titles and tags
+                        this.recommender.load_db_dump(fileContent);
+                        this.dataLoaded.tags = true;
+                        this.updateDataStatus('tagsStatus', 'success', `Loaded (${file.name})`);
+                    } else {
+                        this.recommender.load_vn_titles_bytes(fileContent);
                     }
+                    this.dataLoaded.titles = true;
                     break;
                 case 'tags':
-                    success = this.recommender.load_tags(fileContent);
-                    if (success) {
-                        this.dataLoaded.tags = true;
-                        this.updateDataStatus(statusId, 'success', `Loaded (${file.name})`);
-                    }
+                    this.recommender.load_tags_bytes(fileContent);
+                    this.dataLoaded.tags = true;
                     break;
                 case 'votes':
-                    success = this.recommender.load_votes(fileContent);
-                    if (success) {
-                        this.dataLoaded.votes = true;
-                        this.updateDataStatus(statusId, 'success', `Loaded (${file.name})`);
-                    }
+                    this.recommender.load_votes_bytes(fileContent);
+                    this.dataLoaded.votes = true;
                     break;
             }
-
-            if (!success) {
-                throw new Error(`Failed to process ${dataType} data`);
-            }
+            this.updateDataStatus(statusId, 'success', `Loaded (${file.name})`);
 
             // Update progress based on loaded data
             this.updateOverallProgress();
@@ -100,12 +103,12 @@ class RecommendationApp {
         }
     }
 
-    readFileAsText(file) {
+    readFileAsArrayBuffer(file) {
         return new Promise((resolve, reject) => {
             const reader = new FileReader();
             reader.onload = (e) => resolve(e.target.result);
             reader.onerror = (e) => reject(new Error('Failed to read file'));
-            reader.readAsText(file);
+            reader.readAsArrayBuffer(file);
         });
     }
 
@@ -179,6 +182,11 @@ class RecommendationApp {
         document.getElementById('getRecommendations').addEventListener('click', () => {
             this.getRecommendations();
         });
+
+        // Vote list upload handler
+        document.getElementById('voteListFile').addEventListener('change', (event) => {
+            this.getVoteListRecommendations(event.target.files[0]);
+        });
     }
 
     updateGetRecommendationsButton() {
@@ -225,13 +233,51 @@ class RecommendationApp {
             
         } catch (error) {
             console.error('Error getting recommendations:', error);
-            this.showError('Failed to get recommendations. Please try again.');
+            if (error.name === 'UnknownVn') {
+                this.showError(`Visual Novel with ID ${vnId} not found in the database.`);
+            } else {
+                this.showError(`Failed to get recommendations: ${error.message}`);
+            }
+        } finally {
+            this.showLoading(false);
+        }
+    }
+
+    async getVoteListRecommendations(file) {
+        if (!file) return;
+
+        const allDataLoaded = this.dataLoaded.titles && this.dataLoaded.tags && this.dataLoaded.votes;
+        if (!this.recommender || !allDataLoaded) {
+            this.showError('Please wait until all data is loaded.');
+            return;
+        }
+
+        this.showLoading(true);
+        this.hideError();
+        this.hideResults();
+
+        try {
+            // VNs missing from the loaded data are skipped
+            const contents = await file.text();
+            const voteScale = parseFloat(document.getElementById('voteScale').value);
+            const recommendations = this.recommender.get_recommendations_for_vote_list(contents, voteScale);
+
+            this.displayRecommendations(recommendations);
+            this.showResults();
+
+        } catch (error) {
+            console.error('Error getting recommendations:', error);
+            if (error.name === 'EmptyDataset') {
+                this.showError(`No votes for known visual novels found in ${file.name}.`);
+            } else {
+                this.showError(`Failed to get recommendations: ${error.message}`);
+            }
         } finally {
             this.showLoading(false);
         }
     }
 
-    displayRecommendations(recommendations, sourceTitle, sourceId) {
+    displayRecommendations(recommendations, sourceTitle = null, sourceId = null) {
         // Display combined recommendations
         this.displayRecommendationList(
             recommendations.combined_recommendations,
@@ -284,7 +330,7 @@ class RecommendationApp {
             return;
         }
 
-        items.forEach((item, index) => {
+        items.forEach((item) => {
             const itemDiv = document.createElement('div');
             itemDiv.className = 'recommendation-item bg-white border border-gray-200 rounded-lg p-4 hover:shadow-md transition-all duration-300';
             
@@ -292,10 +338,11 @@ class RecommendationApp {
                 <div class="flex items-center justify-between">
                     <div class="flex-1">
                         <span class="inline-flex items-center justify-center w-8 h-8 bg-blue-100 text-blue-800 text-sm font-medium rounded-full mr-3">
-                            ${index + 1}
+                            ${item.rank}
                         </span>
                         <span class="text-lg font-medium text-gray-900">${item.title}</span>
                         <span class="text-sm text-gray-500 ml-2">(ID: ${item.id})</span>
+                        <span class="text-sm text-gray-400 ml-2">score ${item.score.toFixed(3)}</span>
                     </div>
                     <a href="${item.url}" target="_blank" 
                        class="inline-flex items-center px-4 py-2 bg-blue-500 hover:bg-blue-600 text-white text-sm font-medium rounded-lg transition duration-300">